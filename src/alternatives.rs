@@ -0,0 +1,245 @@
+use indexmap::IndexMap;
+
+use crate::{lookup::LookupError, pricing::price_breaks_from_json, quantity::same_value};
+
+/// Attributes that have to match for a part to be a drop-in replacement, compared by
+/// substring of the attribute name as JLCPCB names them slightly differently per category.
+const KEY_ATTRIBUTES: [&str; 5] = [
+    "resistance",
+    "capacitance",
+    "inductance",
+    "tolerance",
+    "voltage",
+];
+
+/// A part from the catalogue search that might replace the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub code: String,
+    pub model: String,
+    pub brand: String,
+    pub package: String,
+    /// "Basic" or "Extended"
+    pub part_type: Option<String>,
+    pub stock: u64,
+    /// unit price at the lowest price break, in USD
+    pub unit_price: Option<f64>,
+    pub attributes: IndexMap<String, String>,
+}
+
+impl Candidate {
+    pub fn is_basic(&self) -> bool {
+        self.part_type.as_deref() == Some("Basic")
+    }
+}
+
+/// Reads one entry of the JLCPCB component search result list.
+pub fn candidate_from_json(data: &serde_json::Value) -> Option<Candidate> {
+    let text = |key: &str| {
+        data.get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let code = text("componentCode");
+    if code.is_empty() {
+        return None;
+    }
+    let part_type = match data.get("componentLibraryType").and_then(|v| v.as_str()) {
+        Some("base") => Some("Basic".to_owned()),
+        Some("expand") => Some("Extended".to_owned()),
+        _ => None,
+    };
+    let mut attributes = IndexMap::new();
+    if let Some(array) = data.get("attributes").and_then(|v| v.as_array()) {
+        for attribute in array {
+            if let (Some(name), Some(value)) = (
+                attribute.get("attribute_name_en").and_then(|v| v.as_str()),
+                attribute
+                    .get("attribute_value_name")
+                    .and_then(|v| v.as_str()),
+            ) {
+                attributes.insert(name.to_owned(), value.to_owned());
+            }
+        }
+    }
+    Some(Candidate {
+        code,
+        model: text("componentModelEn"),
+        brand: text("componentBrandEn"),
+        package: text("componentSpecificationEn"),
+        part_type,
+        stock: data.get("stockCount").and_then(|v| v.as_u64()).unwrap_or(0),
+        unit_price: price_breaks_from_json(data)
+            .first()
+            .map(|price_break| price_break.unit_price),
+        attributes,
+    })
+}
+
+/// The key attributes of a looked up part that alternatives have to share.
+pub fn key_attributes(part: &IndexMap<String, String>) -> IndexMap<String, String> {
+    part.iter()
+        .filter(|(name, value)| {
+            let name = name.to_lowercase();
+            !value.is_empty()
+                && *value != "-"
+                && KEY_ATTRIBUTES.iter().any(|key| name.contains(key))
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Keeps the candidates with the same package and key attributes as `part` and sorts them
+/// Basic parts first, then by stock and price.
+pub fn rank_alternatives(
+    part: &IndexMap<String, String>,
+    candidates: Vec<Candidate>,
+) -> Vec<Candidate> {
+    let code = part.get("Component Code").map(String::as_str);
+    let package = part.get("Specification");
+    let keys = key_attributes(part);
+    let mut alternatives: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|candidate| Some(candidate.code.as_str()) != code && candidate.stock > 0)
+        .filter(|candidate| {
+            package
+                .as_ref()
+                .is_none_or(|p| same_value(p, &candidate.package))
+        })
+        .filter(|candidate| {
+            keys.iter().all(|(name, value)| {
+                candidate
+                    .attributes
+                    .get(name)
+                    .is_some_and(|other| same_value(other, value))
+            })
+        })
+        .collect();
+    // the same part can come from several searches
+    alternatives.sort_by(|a, b| a.code.cmp(&b.code));
+    alternatives.dedup_by(|a, b| a.code == b.code);
+    alternatives.sort_by(|a, b| {
+        b.is_basic()
+            .cmp(&a.is_basic())
+            .then(b.stock.cmp(&a.stock))
+            .then(
+                a.unit_price
+                    .unwrap_or(f64::MAX)
+                    .total_cmp(&b.unit_price.unwrap_or(f64::MAX)),
+            )
+    });
+    alternatives
+}
+
+/// Searches one page of the JLCPCB catalogue in the category and package of the part.
+fn search_catalogue(
+    client: &reqwest::blocking::Client,
+    category: &str,
+    package: &str,
+    basic_only: bool,
+) -> Result<Vec<Candidate>, LookupError> {
+    let mut query = serde_json::json!({
+        "currentPage": 1,
+        "pageSize": 100,
+        "keyword": package,
+        "secondSortName": category,
+        "componentSpecificationList": [package],
+        "stockFlag": true,
+        "searchSource": "search",
+    });
+    if basic_only {
+        query["componentLibraryType"] = "base".into();
+    }
+    let res = client
+        .post("https://jlcpcb.com/api/overseas-pcb-order/v1/shoppingCart/smtGood/selectSmtComponentList")
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(query.to_string())
+        .send()
+        .map_err(|_| LookupError::Unavailable)?;
+    if !res.status().is_success() {
+        return Err(LookupError::Unavailable);
+    }
+    let json: serde_json::Value = res
+        .text()
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .ok_or(LookupError::Unavailable)?;
+    let list = json
+        .pointer("/data/componentPageInfo/list")
+        .and_then(|list| list.as_array())
+        .ok_or(LookupError::NotFound)?;
+    Ok(list.iter().filter_map(candidate_from_json).collect())
+}
+
+/// Finds parts that could replace `part`, best suggestion first.
+pub fn find_alternatives(part: &IndexMap<String, String>) -> Result<Vec<Candidate>, LookupError> {
+    let (Some(category), Some(package)) =
+        (part.get("Secondary Category"), part.get("Specification"))
+    else {
+        return Err(LookupError::NotFound);
+    };
+    let client = reqwest::blocking::Client::new();
+    // the Basic parts get a search of their own, so they are not crowded out of the first page
+    let mut candidates = search_catalogue(&client, category, package, true)?;
+    candidates.extend(search_catalogue(&client, category, package, false)?);
+    Ok(rank_alternatives(part, candidates))
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    fn candidate(
+        code: &str,
+        library_type: &str,
+        stock: u64,
+        price: f64,
+        tolerance: &str,
+    ) -> Candidate {
+        candidate_from_json(&serde_json::json!({
+            "componentCode": code,
+            "componentModelEn": "0402WGF1001TCE",
+            "componentBrandEn": "UNI-ROYAL",
+            "componentSpecificationEn": "0402",
+            "componentLibraryType": library_type,
+            "stockCount": stock,
+            "componentPrices": [{"startNumber": 100, "productPrice": price}],
+            "attributes": [
+                {"attribute_name_en": "Resistance", "attribute_value_name": "1kΩ"},
+                {"attribute_name_en": "Tolerance", "attribute_value_name": tolerance},
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn ranks_matching_parts() {
+        let part: IndexMap<String, String> = indexmap! {
+            "Component Code".to_owned() => "C1".to_owned(),
+            "Specification".to_owned() => "0402".to_owned(),
+            "Description".to_owned() => "some resistor".to_owned(),
+            "Resistance".to_owned() => "1kΩ".to_owned(),
+            "Tolerance".to_owned() => "±1%".to_owned(),
+        };
+        assert_eq!(key_attributes(&part).len(), 2);
+
+        let candidates = vec![
+            candidate("C1", "base", 1000, 0.001, "±1%"),
+            candidate("C2", "expand", 900000, 0.0001, "±1%"),
+            candidate("C3", "base", 5000, 0.002, "±1%"),
+            candidate("C4", "base", 50000, 0.001, "± 1 %"),
+            candidate("C3", "base", 5000, 0.002, "±1%"),
+            candidate("C5", "base", 50000, 0.001, "±5%"),
+            candidate("C6", "base", 0, 0.001, "±1%"),
+        ];
+        let codes: Vec<String> = rank_alternatives(&part, candidates)
+            .into_iter()
+            .map(|candidate| candidate.code)
+            .collect();
+        assert_eq!(codes, vec!["C4", "C3", "C2"]);
+    }
+}
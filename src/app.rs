@@ -1,21 +1,61 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     fs::{File, create_dir_all, read_to_string},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, TryRecvError, channel},
+    },
+    thread,
+    time::Duration,
 };
 
 use arboard::Clipboard;
 use egui::{TextEdit, Vec2, Window};
 use egui_dropdown::DropDownBox;
 use egui_extras::{Column, TableBuilder};
+use egui_plot::{Line, Plot, PlotPoints};
 use glob::glob;
+use image::DynamicImage;
 use indexmap::{IndexMap, indexmap};
-use regex::Regex;
 use subprocess::Exec;
 
+use crate::{
+    alternatives::{Candidate, find_alternatives},
+    audit::{AuditReport, Severity, audit},
+    compare::{compare_rows, to_csv, to_markdown},
+    config::{Config, Override, config_path, read_config, resolve, write_config},
+    diff::{LineChange, diff_lines, with_context},
+    git::{self, RepoStatus, commit_message, repo_status},
+    lcsc_qr::{LcscLabel, parse_lcsc_label},
+    library::{LibraryIndex, LibraryMatch, find_in_library},
+    library_manager::{
+        LibraryEntry, LibraryPaths, delete_entry, normalize_libraries, normalize_library,
+        rename_footprint, rename_model, rename_symbol, scan_library,
+    },
+    lock::{self, LibraryLock, LockError},
+    lookup::{LookupError, get_part, lookup_part},
+    manifest::{self, Drift, LOCK_FILE, MANIFEST_FILE, PartSpec, SyncReport, append_to_manifest},
+    paths::{DirCheck, check_dir, expand_path_with, find_executable},
+    pricing::{MAX_QUANTITY, PriceTable},
+    profile::{DEFAULT_PROFILE, Profile, unique_name},
+    project::{PROJECT_VARIABLE, Project},
+    provenance::{self, Provenance},
+    qr_decode::decode_qr_codes,
+    quantity::{PartFilter, Quantity},
+    regenerate::{PartDiff, accept, diff_part, run_converter, staged_paths},
+    sexpr::ParseError,
+    sexpr_diff::{ChangeKind, NodeChange, diff_sexpr},
+    stock_history::{Snapshot, StockHistory, format_date},
+    watchlist::{RefreshResult, Watchlist, lookup_all},
+};
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// How long adding a part waits for another instance to finish writing to the library.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
@@ -33,7 +73,45 @@ pub struct MyApp {
     skip_existing: bool,
     no_footprint: bool,
     no_symbol: bool,
+    normalize_library: bool,
+    git_commit: bool,
+    git_pull: bool,
+    git_push: bool,
+    record_scanned_stock: bool,
+    calc_quantity: u64,
+    find_filter: String,
     history: VecDeque<String>,
+    tabs: Vec<PartTab>,
+    active_tab: usize,
+    reuse_tab: bool,
+    diff_as_text: bool,
+    received_stock: IndexMap<String, u64>,
+    /// name of the active profile, whose settings are the library settings above
+    profile: String,
+    profiles: IndexMap<String, Profile>,
+    /// the `.kicad_pro` of the active profile, for importing into a project
+    project: Option<String>,
+    /// lookup of a restored tab that was selected, with its part number
+    #[serde(skip)]
+    tab_loading: Option<Receiver<TabLookup>>,
+    #[serde(skip)]
+    profile_name: String,
+    #[serde(skip)]
+    profile_error: Option<String>,
+    /// the config file the settings are written back to, `None` if it could not be read
+    #[serde(skip)]
+    config_file: Option<PathBuf>,
+    /// settings set by `E2K_*` variables, which are not written back
+    #[serde(skip)]
+    config_overrides: Vec<Override>,
+    /// the config file as last read or written, so it is only written on changes
+    #[serde(skip)]
+    config_text: String,
+    /// checks of directory settings, as the settings window shows them every frame
+    #[serde(skip)]
+    dir_checks: HashMap<PathBuf, DirCheck>,
+    #[serde(skip)]
+    exe_checks: HashMap<String, Option<PathBuf>>,
     #[serde(skip)]
     tempdir: Option<tempfile::TempDir>,
     #[serde(skip)]
@@ -44,6 +122,131 @@ pub struct MyApp {
     search_good: bool,
     #[serde(skip)]
     current_part: IndexMap<String, String>,
+    #[serde(skip)]
+    last_scan: Option<LcscLabel>,
+    #[serde(skip)]
+    stock_history: StockHistory,
+    #[serde(skip)]
+    scan_open: bool,
+    #[serde(skip)]
+    scan_results: Vec<ScanResult>,
+    #[serde(skip)]
+    scan_running: Option<Receiver<Vec<RefreshResult>>>,
+    #[serde(skip)]
+    watchlist: Watchlist,
+    #[serde(skip)]
+    watchlist_open: bool,
+    #[serde(skip)]
+    watch_refresh: Option<Receiver<Vec<RefreshResult>>>,
+    #[serde(skip)]
+    alerts: Vec<String>,
+    /// the part alternatives are searched for and the result, `None` while searching
+    #[serde(skip)]
+    alternatives: Option<(String, Option<Alternatives>)>,
+    #[serde(skip)]
+    alternatives_running: Option<Receiver<Alternatives>>,
+    #[serde(skip)]
+    compare_parts: Vec<IndexMap<String, String>>,
+    #[serde(skip)]
+    compare_open: bool,
+    #[serde(skip)]
+    find_open: bool,
+    #[serde(skip)]
+    find_results: Option<Result<Vec<FoundPart>, String>>,
+    #[serde(skip)]
+    library_index: LibraryIndex,
+    #[serde(skip)]
+    library_matches: Vec<LibraryMatch>,
+    #[serde(skip)]
+    library_open: bool,
+    #[serde(skip)]
+    library_query: String,
+    #[serde(skip)]
+    library_entries: Vec<LibraryEntry>,
+    #[serde(skip)]
+    library_rename: Option<(usize, RenameKind, String)>,
+    #[serde(skip)]
+    library_delete: Option<usize>,
+    #[serde(skip)]
+    sync_open: bool,
+    #[serde(skip)]
+    sync_running: Option<Receiver<Result<SyncReport, String>>>,
+    #[serde(skip)]
+    sync_result: Option<Result<SyncReport, String>>,
+    #[serde(skip)]
+    verify_result: Option<Result<Vec<Drift>, String>>,
+    #[serde(skip)]
+    provenance: Option<(String, Result<Provenance, String>)>,
+    #[serde(skip)]
+    library_error: Option<String>,
+    #[serde(skip)]
+    regenerate_open: bool,
+    #[serde(skip)]
+    regenerate_staging: Option<tempfile::TempDir>,
+    #[serde(skip)]
+    regenerate_running: Option<Receiver<Regenerated>>,
+    #[serde(skip)]
+    regenerate_pending: usize,
+    #[serde(skip)]
+    regenerate_results: Vec<Regenerated>,
+    #[serde(skip)]
+    regenerate_error: Option<String>,
+    #[serde(skip)]
+    file_diff: Option<(PathBuf, PathBuf, TextDiff)>,
+    #[serde(skip)]
+    git_status: Option<RepoStatus>,
+    #[serde(skip)]
+    git_checked: i64,
+    #[serde(skip)]
+    git_refresh: Option<Receiver<Option<RepoStatus>>>,
+    /// last outcome of adding a part in the background, shown in the top bar
+    #[serde(skip)]
+    status_message: Arc<Mutex<Option<String>>>,
+    #[serde(skip)]
+    audit_open: bool,
+    #[serde(skip)]
+    audit_online: bool,
+    #[serde(skip)]
+    audit_running: Option<Receiver<AuditReport>>,
+    #[serde(skip)]
+    audit_report: Option<AuditReport>,
+    #[serde(skip)]
+    audit_error: Option<String>,
+}
+
+/// What of a library entry gets renamed.
+#[derive(Clone, Copy, PartialEq)]
+enum RenameKind {
+    Symbol,
+    Footprint,
+    Model,
+}
+
+/// A part opened in its own tab. Only the part number is persisted, the details are
+/// looked up again once the tab is shown after a restart.
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct PartTab {
+    code: String,
+    #[serde(skip)]
+    data: IndexMap<String, String>,
+}
+
+/// A part matching the filter of the "Find Parts" window.
+struct FoundPart {
+    code: Option<String>,
+    name: String,
+    /// where we know the part from, the lookup history or a library
+    origin: String,
+    /// the value that met the first condition of the filter
+    value: Option<String>,
+}
+
+/// Outcome of the lookup for one QR code found in a label image.
+struct ScanResult {
+    part: String,
+    label: Option<LcscLabel>,
+    found: bool,
 }
 
 impl Default for MyApp {
@@ -62,7 +265,30 @@ impl Default for MyApp {
             skip_existing: false,
             no_footprint: false,
             no_symbol: false,
+            normalize_library: true,
+            git_commit: false,
+            git_pull: false,
+            git_push: false,
+            record_scanned_stock: false,
+            calc_quantity: 100,
+            find_filter: String::new(),
             history: VecDeque::with_capacity(11),
+            tabs: vec![],
+            active_tab: 0,
+            reuse_tab: false,
+            tab_loading: None,
+            diff_as_text: false,
+            received_stock: indexmap! {},
+            profile: DEFAULT_PROFILE.to_owned(),
+            profiles: IndexMap::new(),
+            project: None,
+            profile_name: String::new(),
+            profile_error: None,
+            config_file: None,
+            config_overrides: vec![],
+            config_text: String::new(),
+            dir_checks: HashMap::new(),
+            exe_checks: HashMap::new(),
             tempdir: tempfile::Builder::new()
                 .prefix("easyedatokicadlib")
                 .tempdir()
@@ -71,6 +297,50 @@ impl Default for MyApp {
             is_init: false,
             search_good: true,
             current_part: indexmap! {},
+            last_scan: None,
+            stock_history: StockHistory::default(),
+            scan_open: false,
+            scan_results: vec![],
+            scan_running: None,
+            watchlist: Watchlist::default(),
+            watchlist_open: false,
+            watch_refresh: None,
+            alerts: vec![],
+            alternatives: None,
+            alternatives_running: None,
+            compare_parts: vec![],
+            compare_open: false,
+            find_open: false,
+            find_results: None,
+            library_index: LibraryIndex::default(),
+            library_matches: vec![],
+            library_open: false,
+            library_query: String::new(),
+            library_entries: vec![],
+            library_rename: None,
+            library_delete: None,
+            sync_open: false,
+            sync_running: None,
+            sync_result: None,
+            verify_result: None,
+            provenance: None,
+            library_error: None,
+            regenerate_open: false,
+            regenerate_staging: None,
+            regenerate_running: None,
+            regenerate_pending: 0,
+            regenerate_results: vec![],
+            regenerate_error: None,
+            file_diff: None,
+            git_status: None,
+            git_checked: 0,
+            git_refresh: None,
+            status_message: Arc::default(),
+            audit_open: false,
+            audit_online: false,
+            audit_running: None,
+            audit_report: None,
+            audit_error: None,
         }
     }
 }
@@ -83,236 +353,875 @@ impl MyApp {
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
-        }
-
-        Default::default()
-    }
-
-    fn get_imglist(lcscnumber: &str, client: &reqwest::blocking::Client) -> Option<Vec<String>> {
-        // this is the fallback function for when JLCPCB gives us no images, then we resort to asking LCSC
-        let res_or_err = client
-            .get(format!(
-                "https://wmsc.lcsc.com/ftps/wm/product/detail?productCode={}",
-                lcscnumber
-            ))
-            .header(reqwest::header::ACCEPT, "application/json")
-            .header(
-                reqwest::header::USER_AGENT,
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:134.0) Gecko/20100101 Firefox/134.0",
-            )
-            .send();
-        if let Ok(res) = res_or_err {
-            if res.status().is_success() {
-                let pictext = res
-                    .text()
-                    .expect("Issue decoding received response from LCSC.");
-                let json: serde_json::Value =
-                    serde_json::from_str(&pictext).expect("Issue parsing search result JSON.");
-
-                // there is a case where we get a fully valid response in an HTML
-                // and JSON sense but it tells us via a code field in the JSON
-                // that no part could be found, in that case we return early with nothing
-                if let Some(code) = json.get("code") {
-                    if code != 200 {
-                        return None;
+        let mut app: Self = match cc.storage {
+            Some(storage) => eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+            None => Default::default(),
+        };
+        app.stock_history = StockHistory::load_default();
+        app.watchlist = Watchlist::load_default();
+        app.load_config();
+        app
+    }
+
+    /// Reads the settings from the config file and `E2K_*` variables, for the command line.
+    /// Commands do not run with default settings when there is no config file.
+    pub fn load_settings() -> Result<Self, String> {
+        let path = config_path().ok_or("Found no config directory, use --config <FILE>")?;
+        let text = read_config(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?
+            .ok_or_else(|| {
+                format!(
+                    "No settings in {}, start the app once or use --config <FILE>",
+                    path.display()
+                )
+            })?;
+        let mut app = Self::default();
+        let (config, overrides) = resolve(&app.config(), Some(&text), |variable| {
+            std::env::var(variable).ok()
+        })
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        eprintln!("Using the settings of {}", path.display());
+        for overridden in &overrides {
+            eprintln!("  overridden by {}", overridden.variable);
+        }
+        app.apply_config(config);
+        Ok(app)
+    }
+
+    /// The settings kept in the config file.
+    fn config(&self) -> Config {
+        let mut profiles = self.profiles.clone();
+        profiles.insert(self.profile.clone(), self.current_profile());
+        Config {
+            exe_path: self.exe_path.clone(),
+            normalize_library: self.normalize_library,
+            git_commit: self.git_commit,
+            git_pull: self.git_pull,
+            git_push: self.git_push,
+            record_scanned_stock: self.record_scanned_stock,
+            profile: self.profile.clone(),
+            profiles,
+        }
+    }
+
+    /// Applies the config file and `E2K_*` variables over the stored state. Without a
+    /// config file the stored state is written to one on the next save.
+    fn load_config(&mut self) {
+        let Some(path) = config_path() else {
+            return;
+        };
+        let loaded = read_config(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                let (config, overrides) = resolve(&self.config(), text.as_deref(), |variable| {
+                    std::env::var(variable).ok()
+                })?;
+                Ok((text, config, overrides))
+            });
+        let (text, config, overrides) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                // a broken file is left alone rather than overwritten on the next save
+                let message = format!("Could not read {}: {}", path.display(), e);
+                log::warn!("{}", message);
+                *self.status_message.lock().unwrap() = Some(message);
+                return;
+            }
+        };
+        self.apply_config(config);
+        self.config_file = Some(path);
+        self.config_overrides = overrides;
+        self.config_text = text.unwrap_or_default();
+    }
+
+    fn apply_config(&mut self, config: Config) {
+        self.exe_path = config.exe_path;
+        self.normalize_library = config.normalize_library;
+        self.git_commit = config.git_commit;
+        self.git_pull = config.git_pull;
+        self.git_push = config.git_push;
+        self.record_scanned_stock = config.record_scanned_stock;
+        self.profiles = config.profiles;
+        self.profiles.entry(config.profile.clone()).or_default();
+        self.load_profile(&config.profile);
+    }
+
+    fn save_config(&mut self) {
+        let Some(path) = &self.config_file else {
+            return;
+        };
+        match self.config().to_file_text(&self.config_overrides) {
+            Ok(text) if text != self.config_text => match write_config(path, &text) {
+                Ok(()) => self.config_text = text,
+                Err(e) => log::warn!("Could not write {}: {}", path.display(), e),
+            },
+            Ok(_) => {}
+            Err(e) => log::warn!("Could not write {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn model_base_variable(&self) -> &str {
+        &self.model_base_variable
+    }
+
+    /// The directory the converter writes the library into, which is what gets locked.
+    pub fn output_dir(&self) -> PathBuf {
+        self.expand(&self.output_path)
+    }
+
+    /// Expands `~` and variables in a path setting, `${KIPRJMOD}` standing for the project
+    /// of the profile if it has one.
+    fn expand(&self, path: &str) -> PathBuf {
+        let project_dir = self
+            .project
+            .as_deref()
+            .and_then(|file| Path::new(file).parent())
+            .map(|dir| dir.to_string_lossy().into_owned());
+        expand_path_with(path, |name| match &project_dir {
+            Some(dir) if name == PROJECT_VARIABLE => Some(dir.clone()),
+            _ => std::env::var(name).ok(),
+        })
+    }
+
+    fn dir_check(&mut self, dir: &Path) -> DirCheck {
+        *self
+            .dir_checks
+            .entry(dir.to_owned())
+            .or_insert_with(|| check_dir(dir))
+    }
+
+    /// Where the converter is found, for the settings.
+    fn exe_check(&mut self) -> Result<String, String> {
+        let exe = self.exe();
+        let found = self
+            .exe_checks
+            .entry(exe.clone())
+            .or_insert_with(|| find_executable(Path::new(&exe)));
+        match found {
+            Some(path) => Ok(format!("found at {}", path.display())),
+            None if Path::new(&exe).components().count() > 1 => Err("does not exist".to_owned()),
+            None => Err("not found in PATH".to_owned()),
+        }
+    }
+
+    /// The converter, which may be given as a path with `~` or variables.
+    fn exe(&self) -> String {
+        self.expand(&self.exe_path).to_string_lossy().into_owned()
+    }
+
+    /// The KiCad project the active profile imports into, if any.
+    fn project(&self) -> Option<Project> {
+        let file = self.project.as_deref()?;
+        Project::open(Path::new(file))
+            .map_err(|e| log::warn!("{}", e))
+            .ok()
+    }
+
+    /// Switches to the profile of a KiCad project, creating one the first time.
+    fn open_project(&mut self, file: &Path) -> Result<(), String> {
+        let project = Project::open(file)?;
+        let profile = project.profile(file);
+        let existing = self
+            .profiles
+            .iter()
+            .find(|(_, other)| other.project == profile.project)
+            .map(|(name, _)| name.clone());
+        match existing {
+            Some(name) => self.switch_profile(&name),
+            None => self.add_profile(&project.name, profile),
+        }
+        Ok(())
+    }
+
+    /// Locks the library for changes from the GUI, which fail right away if another instance
+    /// is writing to it rather than freezing the window.
+    fn lock_library(&self, action: &str) -> Result<LibraryLock, LockError> {
+        lock::try_lock(&self.output_dir(), action)
+    }
+
+    fn search(&mut self) {
+        self.part = self.part.trim().to_owned();
+        // a scanned LCSC label is looked up as it is, so the part shows where it came from
+        let result = lookup_part(&self.part);
+        self.show_lookup(result);
+    }
+
+    /// Shows the result of looking up `self.part`. The quantity of a scanned label is only
+    /// booked as received stock once the part turned out to exist.
+    fn show_lookup(&mut self, result: Result<IndexMap<String, String>, LookupError>) {
+        let label = parse_lcsc_label(&self.part);
+        match result {
+            Ok(tabledata) => {
+                if let Some(label) = label.as_ref().filter(|_| self.record_scanned_stock) {
+                    if let Some(quantity) = label.quantity {
+                        *self
+                            .received_stock
+                            .entry(label.product_code.clone())
+                            .or_insert(0) += quantity;
                     }
                 }
-                let mut imglist = vec![];
-                if let Some(data) = json.get("result") {
-                    if let Some(imagelist) = data.get("productImages") {
-                        if let Some(imagevec) = imagelist.as_array() {
-                            for img in imagevec.iter() {
-                                imglist.push(img.to_string().trim_matches('"').to_owned());
+                // the history gets the part number rather than a scanned label
+                let code = tabledata
+                    .get("Component Code")
+                    .cloned()
+                    .unwrap_or(self.part.clone());
+                self.stock_history.record(&tabledata);
+                self.open_part(tabledata);
+                self.search_good = true;
+                // handle history
+                self.history.push_front(code);
+                self.history.truncate(10);
+            }
+            Err(_) => self.search_good = false,
+        }
+        self.last_scan = label;
+    }
+
+    /// Shows a looked up part, in the tab it is already open in, the current tab if tabs
+    /// are reused, or a new one.
+    fn open_part(&mut self, tabledata: IndexMap<String, String>) {
+        let code = tabledata
+            .get("Component Code")
+            .cloned()
+            .unwrap_or(self.part.clone());
+        if let Some(idx) = self.tabs.iter().position(|tab| tab.code == code) {
+            self.active_tab = idx;
+        } else if self.reuse_tab && self.active_tab < self.tabs.len() {
+            self.tabs[self.active_tab].code = code;
+        } else {
+            self.tabs.push(PartTab {
+                code,
+                ..Default::default()
+            });
+            self.active_tab = self.tabs.len() - 1;
+        }
+        self.tabs[self.active_tab].data = tabledata.clone();
+        self.current_part = tabledata;
+        self.check_library();
+    }
+
+    fn current_profile(&self) -> Profile {
+        Profile {
+            output_path: self.output_path.clone(),
+            symbol_lib: self.symbol_lib.clone(),
+            symbol_lib_dir: self.symbol_lib_dir.clone(),
+            footprint_lib: self.footprint_lib.clone(),
+            model_dir: self.model_dir.clone(),
+            model_base_variable: self.model_base_variable.clone(),
+            datasheet_dir: self.datasheet_dir.clone(),
+            download_datasheet: self.download_datasheet,
+            skip_existing: self.skip_existing,
+            no_footprint: self.no_footprint,
+            no_symbol: self.no_symbol,
+            project: self.project.clone(),
+        }
+    }
+
+    /// Keeps the settings of the active profile in the list of profiles, which is what
+    /// gets exported and switched back to.
+    fn store_profile(&mut self) {
+        self.profiles
+            .insert(self.profile.clone(), self.current_profile());
+    }
+
+    fn switch_profile(&mut self, name: &str) {
+        self.store_profile();
+        self.load_profile(name);
+    }
+
+    /// Makes another profile active. Everything read from the library of the previous one
+    /// is dropped or read again.
+    fn load_profile(&mut self, name: &str) {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            return;
+        };
+        self.profile = name.to_owned();
+        self.output_path = profile.output_path;
+        self.symbol_lib = profile.symbol_lib;
+        self.symbol_lib_dir = profile.symbol_lib_dir;
+        self.footprint_lib = profile.footprint_lib;
+        self.model_dir = profile.model_dir;
+        self.model_base_variable = profile.model_base_variable;
+        self.datasheet_dir = profile.datasheet_dir;
+        self.download_datasheet = profile.download_datasheet;
+        self.skip_existing = profile.skip_existing;
+        self.no_footprint = profile.no_footprint;
+        self.no_symbol = profile.no_symbol;
+        self.project = profile.project;
+
+        self.git_status = None;
+        self.git_checked = 0;
+        self.library_error = None;
+        self.library_entries = scan_library(&self.library_paths());
+        self.audit_report = None;
+        self.sync_result = None;
+        self.verify_result = None;
+        self.check_library();
+    }
+
+    /// Adds a profile under a name not taken yet and makes it active.
+    fn add_profile(&mut self, name: &str, profile: Profile) {
+        self.store_profile();
+        let name = unique_name(name, |name| self.profiles.contains_key(name));
+        self.profiles.insert(name.clone(), profile);
+        self.switch_profile(&name);
+    }
+
+    fn rename_profile(&mut self, name: &str) {
+        self.store_profile();
+        let name = unique_name(name, |name| self.profiles.contains_key(name));
+        if let Some(idx) = self.profiles.get_index_of(&self.profile) {
+            let profile = self.current_profile();
+            self.profiles.shift_remove_index(idx);
+            self.profiles.shift_insert(idx, name.clone(), profile);
+            self.profile = name;
+        }
+    }
+
+    fn delete_profile(&mut self) {
+        self.profiles.shift_remove(&self.profile);
+        // the last profile goes back to the defaults
+        if self.profiles.is_empty() {
+            self.profiles
+                .insert(DEFAULT_PROFILE.to_owned(), Profile::default());
+        }
+        if let Some(next) = self.profiles.keys().next().cloned() {
+            self.load_profile(&next);
+        }
+    }
+
+    /// Looks for the current part, or an equivalent one, in the symbol library.
+    fn check_library(&mut self) {
+        let dir = self.symbol_dir();
+        self.library_index.refresh(&dir);
+        self.library_matches = find_in_library(
+            &self.current_part,
+            &self.library_index.symbols,
+            self.stock_history.details(),
+        );
+    }
+
+    fn select_tab(&mut self, ctx: &egui::Context, idx: usize) {
+        let Some(tab) = self.tabs.get(idx) else {
+            return;
+        };
+        self.active_tab = idx;
+        self.current_part = tab.data.clone();
+        self.part = tab.code.clone();
+        self.search_good = true;
+        self.tab_loading = None;
+        // restored tabs have no details yet, they are looked up in the background
+        if tab.data.is_empty() {
+            let (sender, receiver) = channel();
+            let code = tab.code.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let part = get_part(&code);
+                let _ = sender.send((code, part));
+                ctx.request_repaint();
+            });
+            self.tab_loading = Some(receiver);
+        }
+        self.check_library();
+    }
+
+    /// Fills in the tab whose details were looked up in the background.
+    fn finish_tab_loading(&mut self, code: String, part: Option<IndexMap<String, String>>) {
+        if let Some(part) = &part {
+            self.stock_history.record(part);
+            for tab in self.tabs.iter_mut().filter(|tab| tab.code == code) {
+                tab.data = part.clone();
+            }
+        }
+        if self
+            .tabs
+            .get(self.active_tab)
+            .is_some_and(|tab| tab.code == code)
+        {
+            self.search_good = part.is_some();
+            self.current_part = part.unwrap_or_default();
+            self.check_library();
+        }
+    }
+
+    fn close_tab(&mut self, ctx: &egui::Context, idx: usize) {
+        self.tabs.remove(idx);
+        if self.tabs.is_empty() {
+            self.active_tab = 0;
+            self.current_part.clear();
+            self.library_matches.clear();
+            return;
+        }
+        if self.active_tab > idx || self.active_tab == self.tabs.len() {
+            self.active_tab -= 1;
+        }
+        self.select_tab(ctx, self.active_tab);
+    }
+
+    /// Command line arguments for JLC2KiCadLib to generate a part into `output` with the
+    /// current settings. The part is the first argument.
+    fn converter_args(&self, code: &str, output: &Path, skip_existing: bool) -> Vec<String> {
+        let output = output.to_string_lossy();
+        let symbol_lib_dir = self
+            .expand(&self.symbol_lib_dir)
+            .to_string_lossy()
+            .into_owned();
+        let footprint_lib = self
+            .expand(&self.footprint_lib)
+            .to_string_lossy()
+            .into_owned();
+        let model_dir = self.expand(&self.model_dir).to_string_lossy().into_owned();
+        let mut args = vec![
+            code,
+            "-dir",
+            &output,
+            "-symbol_lib",
+            &self.symbol_lib,
+            "-symbol_lib_dir",
+            &symbol_lib_dir,
+            "-footprint_lib",
+            &footprint_lib,
+            "-model_dir",
+            &model_dir,
+        ];
+        if !self.model_base_variable.is_empty() {
+            args.push("-model_base_variable");
+            args.push(&self.model_base_variable);
+        }
+        if skip_existing {
+            args.push("--skip_existing");
+        }
+        if self.no_footprint {
+            args.push("--no_footprint");
+        }
+        if self.no_symbol {
+            args.push("--no_symbol");
+        }
+        args.into_iter().map(str::to_owned).collect()
+    }
+
+    /// Runs JLC2KiCadLib for a part with the current settings, downloads its datasheet and
+    /// records its provenance. This happens on a separate thread, as with git enabled the
+    /// part is committed after.
+    fn add_to_library(&self, code: &str, datasheet_url: Option<&str>, skip_existing: bool) {
+        let args = self.converter_args(code, &self.output_dir(), skip_existing);
+        let exe = self.exe();
+        let code = code.to_owned();
+        let datasheet_url = datasheet_url.map(str::to_owned);
+        let datasheet = datasheet_url
+            .as_deref()
+            .filter(|_| self.download_datasheet)
+            .map(|url| (self.expand(&self.datasheet_dir), url.to_owned()));
+        let paths = self.library_paths();
+        let library = paths
+            .symbol_dir
+            .join(format!("{}.kicad_sym", self.symbol_lib));
+        let normalize = self.normalize_library;
+        let output = self.output_dir();
+        let project = self.project();
+        let symbol_lib = self.symbol_lib.clone();
+        let repo = self
+            .git_status
+            .as_ref()
+            .map(|status| status.root.clone())
+            .filter(|_| self.git_commit || self.git_pull);
+        let (pull, push) = (self.git_pull, self.git_push && self.git_commit);
+        let verb = if self
+            .library_index
+            .symbols
+            .iter()
+            .any(|symbol| symbol.lcsc_code() == Some(&code))
+        {
+            "Update"
+        } else {
+            "Add"
+        };
+        let part = if self.current_part.get("Component Code") == Some(&code) {
+            Some(&self.current_part)
+        } else {
+            self.stock_history.details().get(&code)
+        };
+        let message = self.git_commit.then(|| commit_message(verb, &code, part));
+        let part = part.cloned();
+        let status_message = self.status_message.clone();
+        let report = move |text: String| {
+            log::info!("{}", text);
+            *status_message.lock().unwrap() = Some(text);
+        };
+        thread::spawn(move || {
+            // held until the part is committed, so nobody pulls or writes in between
+            let _lock = match lock::lock(&output, &format!("adding {}", code), LOCK_TIMEOUT) {
+                Ok(lock) => lock,
+                Err(e) => {
+                    report(format!("Could not add {}: {}", code, e));
+                    return;
+                }
+            };
+            if let (Some(root), true) = (&repo, pull) {
+                if let Err(e) = git::pull(root) {
+                    report(format!("git pull failed: {}", e));
+                }
+            }
+            if let Err(e) = run_converter(&exe, &args) {
+                report(format!("Could not add {}: {}", code, e));
+                return;
+            }
+            // the library can only be normalized once the converter is done with it
+            if normalize {
+                if let Err(e) = normalize_library(&library) {
+                    log::warn!("Could not normalize {}: {}", library.display(), e);
+                }
+            }
+            if let Some(project) = &project {
+                if let Err(e) = project.finish_import(&paths, &symbol_lib) {
+                    report(format!("Could not update the project libraries: {}", e));
+                }
+            }
+            if let Some((dir, url)) = &datasheet {
+                download_datasheet(dir, &code, url);
+            }
+            let entry = scan_library(&paths)
+                .into_iter()
+                .find(|entry| entry.lcsc_code() == Some(&code));
+            let files = entry.as_ref().map(LibraryEntry::files).unwrap_or_default();
+            if let Some(provenance) = entry.and_then(|entry| {
+                provenance::record(
+                    &entry,
+                    &output,
+                    &exe,
+                    &args,
+                    part.as_ref(),
+                    datasheet_url.as_deref(),
+                )
+            }) {
+                if let Err(e) = provenance.save(&output) {
+                    log::warn!("Could not record provenance of {}: {}", code, e);
+                }
+            }
+            if let (Some(root), Some(message)) = (&repo, &message) {
+                let mut changed = files;
+                changed.push(Provenance::path(&output, &code));
+                if let Some(project) = &project {
+                    changed.push(project.dir.join("sym-lib-table"));
+                    changed.push(project.dir.join("fp-lib-table"));
+                }
+                match git::commit(root, &changed, message) {
+                    Ok(true) => {
+                        report(format!("Committed \"{}\"", message));
+                        if push {
+                            if let Err(e) = git::push(root) {
+                                report(format!("git push failed: {}", e));
                             }
-                            return Some(imglist);
                         }
                     }
+                    Ok(false) => {}
+                    Err(e) => report(format!("git commit failed: {}", e)),
+                }
+            }
+        });
+    }
+
+    /// Imports a part for sync with the current settings and the overrides of the manifest.
+    fn part_importer(&self) -> impl FnMut(&str, &PartSpec) -> Result<(), String> + Send + 'static {
+        let exe = self.exe();
+        let template = self.converter_args("", &self.output_dir(), false);
+        let project = self.project();
+        let paths = self.library_paths();
+        let symbol_lib = self.symbol_lib.clone();
+        move |code, spec| {
+            let mut args = template.clone();
+            args[0] = code.to_owned();
+            for (skip, flag) in [
+                (spec.no_footprint, "--no_footprint"),
+                (spec.no_symbol, "--no_symbol"),
+            ] {
+                if skip && !args.iter().any(|arg| arg == flag) {
+                    args.push(flag.to_owned());
                 }
             }
+            run_converter(&exe, &args)?;
+            match &project {
+                Some(project) => project
+                    .finish_import(&paths, &symbol_lib)
+                    .map_err(|e| format!("Could not update the project libraries: {}", e)),
+                None => Ok(()),
+            }
         }
-        // if we fall through to here, we failed getting data somewhere along the way
-        None
-    }
-
-    fn get_part(search_term: &str) -> Option<IndexMap<String, String>> {
-        let term = search_term.trim();
-        let re_jlc = Regex::new(r"/(C\d+)$").unwrap();
-        let re_lcsc = Regex::new(r"_(C\d+)[^/]*\.html$").unwrap();
-        let re_lcscnumber = Regex::new(r"^C(\d+)$").unwrap();
-        let mut lcscnumber = "";
-
-        // case one, we got passed a URL
-        if term.contains("http") {
-            if term.contains("jlcpcb.com") {
-                if let Some(captures) = re_jlc.captures(term) {
-                    lcscnumber = captures.get(1).unwrap().as_str();
-                }
-            } else if term.contains("lcsc.com") {
-                if let Some(captures) = re_lcsc.captures(term) {
-                    lcscnumber = captures.get(1).unwrap().as_str();
-                }
-            }
-        // case two, it's the number directly
-        } else if term.starts_with("C") {
-            lcscnumber = term;
-        }
-
-        // ensure we only make requests if what we have looks like an LCSC number and can work,
-        // also saves us from urlencoding and such because it will only ever be "C" followed by some numbers
-        if re_lcscnumber.is_match(lcscnumber) {
-            let client = reqwest::blocking::Client::new();
-            let res_or_err = client
-                .get(format!("https://cart.jlcpcb.com/shoppingCart/smtGood/getComponentDetail?componentCode={}", lcscnumber))
-                .header(reqwest::header::ACCEPT, "application/json")
-                .send();
-            if let Ok(res) = res_or_err {
-                let res_status = res.status();
-                if res_status.is_success() {
-                    let res_text = res
-                        .text()
-                        .expect("Issue decoding received response from JLCPCB.");
-                    let json: serde_json::Value =
-                        serde_json::from_str(&res_text).expect("Issue parsing search result JSON.");
-                    // only debug: println!("{}", json);
-                    let parameters = indexmap! {
-                        "componentCode" => "Component Code",
-                        "firstTypeNameEn" => "Primary Category",
-                        "secondTypeNameEn" => "Secondary Category",
-                        "componentBrandEn" => "Brand",
-                        "componentName" => "Full Name",
-                        "componentDesignator" => "Designator",
-                        "componentModelEn" => "Model",
-                        "componentSpecificationEn" => "Specification",
-                        "assemblyProcess" => "Assembly Process",
-                        "describe" => "Description",
-                        "matchedPartDetail" => "Details",
-                        "stockCount" => "Stock",
-                        "leastNumber" => "Minimal Quantity",
-                        "leastNumberPrice" => "Minimum Price",
-                    };
+    }
 
-                    // there is a case where we get a fully valid response in an HTML
-                    // and JSON sense but it tells us via a code field in the JSON
-                    // that no part could be found, in that case we exit early
-                    if let Some(code) = json.get("code") {
-                        if code != 200 {
-                            return None;
-                        }
-                    }
+    /// Syncs the library with its `parts.toml`, to run on a thread of its own or from the
+    /// command line. Waits for others writing to the library.
+    pub fn sync_job(&self) -> impl FnOnce() -> Result<SyncReport, String> + Send + 'static {
+        let output = self.output_dir();
+        let paths = self.library_paths();
+        let normalize = self.normalize_library;
+        let importer = self.part_importer();
+        move || {
+            let _lock = lock::lock(&output, "syncing", LOCK_TIMEOUT).map_err(|e| e.to_string())?;
+            manifest::sync(&output, &paths, normalize, importer)
+        }
+    }
 
-                    // if the data section is there as expected, we start taking it apart
-                    if let Some(data) = json.get("data") {
-                        let mut tabledata: IndexMap<String, String> = indexmap! {};
+    fn start_sync(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = channel();
+        let job = self.sync_job();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(job());
+            ctx.request_repaint();
+        });
+        self.sync_result = None;
+        self.sync_running = Some(receiver);
+    }
 
-                        // determine if it is a JLCPCB basic or extended assembly part
-                        if let Some(parttype) = data.get("componentLibraryType") {
-                            if parttype == "base" {
-                                tabledata.insert("Type".to_owned(), "Basic".to_owned());
-                            } else if parttype == "expand" {
-                                tabledata.insert("Type".to_owned(), "Extended".to_owned());
-                            }
-                        }
+    /// Decodes the QR codes of LCSC labels in images and looks up every part found, in the
+    /// background, so a photo or a pile of photos of several bags is processed as a batch.
+    fn start_scan(&mut self, ctx: &egui::Context, images: Vec<DynamicImage>) {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let payloads: Vec<String> = images.iter().flat_map(decode_qr_codes).collect();
+            let _ = sender.send(lookup_all(&payloads));
+            ctx.request_repaint();
+        });
+        self.scan_running = Some(receiver);
+        self.scan_results.clear();
+        self.scan_open = true;
+    }
 
-                        // now pretty-format the parameters that should always be there
-                        for (key, title) in parameters {
-                            if let Some(value) = data.get(key) {
-                                tabledata.insert(
-                                    title.to_owned(),
-                                    value.to_string().trim_matches('"').to_owned(),
-                                );
-                            }
-                        }
+    fn finish_scan(&mut self, results: Vec<RefreshResult>) {
+        for (payload, result) in results {
+            self.part = payload;
+            self.show_lookup(result);
+            let part = match (self.current_part.get("Component Code"), &self.last_scan) {
+                (Some(code), _) if self.search_good => code.to_owned(),
+                (_, Some(label)) => label.product_code.clone(),
+                _ => self.part.clone(),
+            };
+            self.scan_results.push(ScanResult {
+                part,
+                label: self.last_scan.clone(),
+                found: self.search_good,
+            });
+        }
+    }
 
-                        // now the component specific attributes, these are in a nested array within
-                        // the JSON and vary by component
-                        if let Some(attributes) = data.get("attributes") {
-                            if let Some(array) = attributes.as_array() {
-                                for attribute in array {
-                                    if let Some(name) = attribute.get("attribute_name_en") {
-                                        if let Some(value) = attribute.get("attribute_value_name") {
-                                            tabledata.insert(
-                                                name.to_string().trim_matches('"').to_owned(),
-                                                value.to_string().trim_matches('"').to_owned(),
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    pub fn library_paths(&self) -> LibraryPaths {
+        let output = self.output_dir();
+        let footprint_dir = output.join(self.expand(&self.footprint_lib));
+        LibraryPaths {
+            symbol_dir: output.join(self.expand(&self.symbol_lib_dir)),
+            model_dir: footprint_dir.join(self.expand(&self.model_dir)),
+            footprint_dir,
+            datasheet_dir: self.expand(&self.datasheet_dir),
+        }
+    }
 
-                        // here we gather metadata for the image and datasheet URLs
-                        if let Some(imagelist) = data.get("imageList") {
-                            if let Some(imagevec) = imagelist.as_array() {
-                                for (idx, i) in imagevec.iter().enumerate() {
-                                    if let Some(imageurl) = i.get("productBigImage") {
-                                        // this is a f*ed up case where JLC returns API IDs instead of URLs
-                                        if imageurl.is_null() {
-                                            // this does not work right now because of MIME type issues, get from LCSC instead
-                                            // if let Some(imageid) = i.get("productBigImageAccessId")
-                                            // {
-                                            //     let apiurl = format!("https://jlcpcb.com/api/file/downloadByFileSystemAccessId/{}.jpg", imageid.to_string().trim_matches('"').to_owned());
-                                            //     tabledata
-                                            //         .insert(format!("meta_image{}", idx), apiurl);
-                                            // }
-                                            if let Some(lcsc_imglist) =
-                                                MyApp::get_imglist(lcscnumber, &client)
-                                            {
-                                                for (idx, i) in lcsc_imglist.iter().enumerate() {
-                                                    tabledata.insert(
-                                                        format!("meta_image{}", idx),
-                                                        i.to_owned(),
-                                                    );
-                                                }
-                                            }
-                                            break;
-                                        } else {
-                                            tabledata.insert(
-                                                format!("meta_image{}", idx),
-                                                imageurl.to_string().trim_matches('"').to_owned(),
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    fn symbol_dir(&self) -> std::path::PathBuf {
+        self.library_paths().symbol_dir
+    }
 
-                        // This mess is because LCSC can or can not have an offical data sheet link directly to
-                        // the manufacturer, but sometimes the key also exists but is empty. In case it exists
-                        // and is valid we must take it, because then the other one just redirects to the product
-                        // page URL, leaving us without a datasheet that we can actually download. If it does
-                        // not exist the datasheet is actually stored at LCSC and the key in the else branch
-                        // does return a valid PDF. So yeah...
-                        if let Some(officialdatasheeturl) = data.get("dataManualOfficialLink")
-                            && officialdatasheeturl != ""
-                        {
-                            tabledata.insert(
-                                "meta_datasheeturl".to_owned(),
-                                officialdatasheeturl
-                                    .to_string()
-                                    .trim_matches('"')
-                                    .to_owned(),
-                            );
-                        } else if let Some(datasheeturl) = data.get("dataManualUrl") {
-                            tabledata.insert(
-                                "meta_datasheeturl".to_owned(),
-                                datasheeturl.to_string().trim_matches('"').to_owned(),
-                            );
-                        }
-                        return Some(tabledata);
-                    }
+    fn rename_library_entry(&mut self, idx: usize, kind: RenameKind, new: &str) {
+        let paths = self.library_paths();
+        let Some(entry) = self.library_entries.get(idx) else {
+            return;
+        };
+        let _lock = match self.lock_library("renaming") {
+            Ok(lock) => lock,
+            Err(e) => {
+                self.library_error = Some(format!("Could not rename: {}", e));
+                return;
+            }
+        };
+        let result = match kind {
+            RenameKind::Symbol => match &entry.symbol {
+                Some(symbol) => rename_symbol(&symbol.library, &symbol.name, new),
+                None => Ok(()),
+            },
+            RenameKind::Footprint => match &entry.footprint {
+                Some(footprint) => rename_footprint(&paths, footprint, new),
+                None => Ok(()),
+            },
+            RenameKind::Model => rename_model(&paths, &entry.models, new),
+        };
+        self.library_error = result.err().map(|e| format!("Could not rename: {}", e));
+        self.library_entries = scan_library(&paths);
+    }
+
+    /// Searches the parts looked up so far and the symbols of the library with a filter like
+    /// "capacitors ≥10µF, ≥16V", sorted by the value meeting the first condition.
+    fn find_parts(&mut self) -> Result<Vec<FoundPart>, String> {
+        let filter = PartFilter::parse(&self.find_filter)?;
+        if filter.is_empty() {
+            return Ok(vec![]);
+        }
+        let details = self.stock_history.details();
+        let mut found = vec![];
+        for (code, part) in details {
+            if filter.matches(part) {
+                found.push((
+                    filter.first_match(part),
+                    FoundPart {
+                        code: Some(code.clone()),
+                        name: part.get("Model").cloned().unwrap_or_default(),
+                        origin: "History".to_owned(),
+                        value: None,
+                    },
+                ));
+            }
+        }
+        let dir = self.symbol_dir();
+        self.library_index.refresh(&dir);
+        for symbol in &self.library_index.symbols {
+            // symbols carry few attributes, so we add what we know about the part from lookups
+            let mut properties = symbol.properties.clone();
+            if let Some(part) = symbol.lcsc_code().and_then(|code| details.get(code)) {
+                for (key, value) in part {
+                    properties.entry(key.clone()).or_insert(value.clone());
+                }
+            }
+            if filter.matches(&properties) {
+                found.push((
+                    filter.first_match(&properties),
+                    FoundPart {
+                        code: symbol.lcsc_code().map(str::to_owned),
+                        origin: format!(
+                            "Library {}",
+                            symbol
+                                .library
+                                .file_stem()
+                                .map(|stem| stem.to_string_lossy())
+                                .unwrap_or_default()
+                        ),
+                        name: symbol.name.clone(),
+                        value: None,
+                    },
+                ));
+            }
+        }
+        found.sort_by(|(a, _), (b, _)| {
+            let value = |q: &Option<Quantity>| q.as_ref().map_or(f64::MAX, |q| q.value);
+            value(a).total_cmp(&value(b))
+        });
+        Ok(found
+            .into_iter()
+            .map(|(quantity, mut part)| {
+                part.value = quantity.map(|quantity| quantity.to_string());
+                part
+            })
+            .collect())
+    }
+
+    /// Looks up all watched parts in the background, the results are picked up in `update`.
+    fn start_watch_refresh(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = channel();
+        let codes = self.watchlist.codes();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(lookup_all(&codes));
+            ctx.request_repaint();
+        });
+        self.watch_refresh = Some(receiver);
+    }
+
+    fn start_audit(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = channel();
+        let paths = self.library_paths();
+        let model_base_variable = self.model_base_variable.clone();
+        let online = self.audit_online;
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(audit(&paths, &model_base_variable, online));
+            ctx.request_repaint();
+        });
+        self.audit_error = None;
+        self.audit_running = Some(receiver);
+    }
+
+    /// Generates every part of the library, or only the part `only`, again into a staging
+    /// directory, in the background, and compares the result with the library part by part.
+    fn start_regeneration(&mut self, ctx: &egui::Context, only: Option<&str>) {
+        let staging = match tempfile::Builder::new()
+            .prefix("easyedatokicadlib-staging")
+            .tempdir()
+        {
+            Ok(staging) => staging,
+            Err(e) => {
+                self.regenerate_error = Some(format!("Could not create staging directory: {}", e));
+                return;
+            }
+        };
+        let live = self.library_paths();
+        let staged = staged_paths(staging.path(), &live, &self.output_dir());
+        let mut jobs = vec![];
+        for entry in scan_library(&live) {
+            if let Some(code) = entry.lcsc_code().map(str::to_owned) {
+                if only.is_none_or(|only| only == code)
+                    && !jobs.iter().any(|(other, _, _)| *other == code)
+                {
+                    let args = self.converter_args(&code, staging.path(), false);
+                    jobs.push((code, args, entry));
+                }
+            }
+        }
+        self.regenerate_pending = jobs.len();
+        let (sender, receiver) = channel();
+        let exe = self.exe();
+        let symbol_lib = self.symbol_lib.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (code, args, entry) in jobs {
+                let result = run_converter(&exe, &args)
+                    .and_then(|()| diff_part(&code, Some(&entry), &staged, &live, &symbol_lib))
+                    .map(|diff| {
+                        let texts = diff
+                            .symbol
+                            .iter()
+                            .chain(&diff.footprint)
+                            .map(|change| TextDiff::new(change.old.as_deref(), &change.new))
+                            .collect();
+                        (diff, texts)
+                    });
+                // the window was closed
+                if sender.send((code, result)).is_err() {
+                    break;
                 }
+                ctx.request_repaint();
+            }
+        });
+        self.regenerate_results.clear();
+        self.regenerate_error = None;
+        self.regenerate_staging = Some(staging);
+        self.regenerate_running = Some(receiver);
+        self.regenerate_open = true;
+    }
+
+    fn finish_watch_refresh(&mut self, results: Vec<RefreshResult>) {
+        let now = jiff::Timestamp::now().as_second();
+        for (_, result) in &results {
+            if let Ok(part) = result {
+                self.stock_history.record(part);
             }
         }
-        // if we fall through to here, we failed getting data somewhere along the way
-        None
+        for alert in self.watchlist.apply(&results, now) {
+            self.alerts
+                .push(format!("{} {}", format_date(now, true), alert));
+        }
+        if let Err(e) = self.watchlist.save() {
+            log::warn!("Could not save watchlist: {}", e);
+        }
     }
 }
 
 impl eframe::App for MyApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.store_profile();
+        self.save_config();
+        // lookups are written with the app state rather than one by one
+        if let Err(e) = self.stock_history.save() {
+            log::warn!("Could not save stock history: {}", e);
+        }
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
@@ -322,23 +1231,174 @@ impl eframe::App for MyApp {
         // For inspiration and more examples, go to https://emilk.github.io/egui
         let is_web = cfg!(target_arch = "wasm32");
 
-        // on startup the current_part IndexMap is empty even if a part is set, so we populate it
-        if !self.is_init && self.current_part.is_empty() && !self.part.is_empty() {
-            if let Some(tabledata) = Self::get_part(self.part.as_str()) {
-                self.current_part = tabledata;
-                self.search_good = true;
-            } else {
-                self.search_good = false;
+        // on startup only the part numbers of the tabs are known, so we populate the active one,
+        // state from before tabs existed only has the last searched part
+        if !self.is_init {
+            if !self.tabs.is_empty() {
+                self.select_tab(ctx, self.active_tab.min(self.tabs.len() - 1));
+            } else if !self.part.is_empty() {
+                if let Some(tabledata) = get_part(self.part.as_str()) {
+                    self.stock_history.record(&tabledata);
+                    self.open_part(tabledata);
+                    self.search_good = true;
+                } else {
+                    self.search_good = false;
+                }
             }
             self.is_init = true
         }
 
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            // The top panel is often a good place for a menu bar:
+        // periodic refresh of the watchlist, the lookups run on a separate thread
+        let now = jiff::Timestamp::now().as_second();
+        if self.watch_refresh.is_none() && self.watchlist.is_due(now) {
+            self.start_watch_refresh(ctx);
+        }
+        if let Some(receiver) = &self.watch_refresh {
+            match receiver.try_recv() {
+                Ok(results) => {
+                    self.watch_refresh = None;
+                    self.finish_watch_refresh(results);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    // the next attempt waits for the next interval instead of retrying at once
+                    self.watch_refresh = None;
+                    self.watchlist.last_refresh = Some(now);
+                    let message = "Refreshing the watchlist failed".to_owned();
+                    log::warn!("{}", message);
+                    *self.status_message.lock().unwrap() = Some(message);
+                }
+            }
+        }
+        if self.watchlist.refresh_minutes > 0 && !self.watchlist.parts.is_empty() {
+            ctx.request_repaint_after(Duration::from_secs(60));
+        }
 
-            egui::MenuBar::new().ui(ui, |ui| {
+        // status of the git repository the library may be in, also checked in the background
+        if self.git_refresh.is_none() && now - self.git_checked >= 30 {
+            self.git_checked = now;
+            let (sender, receiver) = channel();
+            let dir = self.output_dir();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let _ = sender.send(repo_status(&dir));
+                ctx.request_repaint();
+            });
+            self.git_refresh = Some(receiver);
+        }
+        if let Some(receiver) = &self.git_refresh {
+            if let Ok(status) = receiver.try_recv() {
+                self.git_refresh = None;
+                self.git_status = status;
+            }
+        }
+        if self.git_status.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(30));
+        }
+
+        // images of LCSC labels can be dropped onto the window or opened via the menu
+        let mut label_images: Vec<DynamicImage> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| match (&file.path, &file.bytes) {
+                    (Some(path), _) => image::open(path).ok(),
+                    (None, Some(bytes)) => image::load_from_memory(bytes).ok(),
+                    _ => None,
+                })
+                .collect()
+        });
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            // The top panel is often a good place for a menu bar:
+
+            egui::MenuBar::new().ui(ui, |ui| {
                 // NOTE: no File->Quit on web pages!
                 ui.menu_button("File", |ui| {
+                    if !is_web && ui.button("Open Label Image…").clicked() {
+                        if let Some(paths) = rfd::FileDialog::new()
+                            .add_filter("Images", &["png", "jpg", "jpeg"])
+                            .pick_files()
+                        {
+                            label_images.extend(paths.iter().filter_map(|p| image::open(p).ok()));
+                        }
+                    }
+                    if !is_web
+                        && ui
+                            .button("Open KiCad Project…")
+                            .on_hover_text("Import into libraries of the project itself, so it can be handed over without anyone's global library.")
+                            .clicked()
+                    {
+                        if let Some(file) = rfd::FileDialog::new()
+                            .add_filter("KiCad project", &["kicad_pro"])
+                            .pick_file()
+                        {
+                            if let Err(e) = self.open_project(&file) {
+                                *self.status_message.lock().unwrap() = Some(e);
+                            }
+                        }
+                    }
+                    if ui.button("Library Browser").clicked() {
+                        self.library_entries = scan_library(&self.library_paths());
+                        self.library_open = true;
+                    }
+                    if !is_web && ui.button("Diff Library Files…").clicked() {
+                        let filter = ["kicad_sym", "kicad_mod"];
+                        if let Some(old) = rfd::FileDialog::new()
+                            .set_title("Old file")
+                            .add_filter("KiCad library files", &filter)
+                            .pick_file()
+                        {
+                            if let Some(new) = rfd::FileDialog::new()
+                                .set_title("New file")
+                                .add_filter("KiCad library files", &filter)
+                                .pick_file()
+                            {
+                                match (read_to_string(&old), read_to_string(&new)) {
+                                    (Ok(old_text), Ok(new_text)) => {
+                                        let diff = TextDiff::new(Some(&old_text), &new_text);
+                                        self.file_diff = Some((old, new, diff));
+                                    }
+                                    (Err(e), _) | (_, Err(e)) => {
+                                        log::warn!("Could not read file: {}", e)
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if ui
+                        .button("Sync Library")
+                        .on_hover_text(format!("Import the parts listed in {} of the output directory that are missing.", MANIFEST_FILE))
+                        .clicked()
+                    {
+                        self.sync_open = true;
+                        if self.sync_running.is_none() {
+                            self.start_sync(ctx);
+                        }
+                    }
+                    if ui
+                        .button("Verify Library")
+                        .on_hover_text(format!("Compare the library with the hashes in {} without changing anything.", LOCK_FILE))
+                        .clicked()
+                    {
+                        self.sync_open = true;
+                        self.verify_result = Some(manifest::verify(&self.output_dir(), &self.library_paths()));
+                    }
+                    if ui.button("Audit Library").clicked() {
+                        self.audit_open = true;
+                        if self.audit_report.is_none() && self.audit_running.is_none() {
+                            self.start_audit(ctx);
+                        }
+                    }
+                    if ui.button("Find Parts").clicked() {
+                        self.find_open = true;
+                    }
+                    if ui.button("Compare Parts").clicked() {
+                        self.compare_open = true;
+                    }
+                    if ui.button("Watchlist").clicked() {
+                        self.watchlist_open = true;
+                    }
                     if ui.button("Settings").clicked() {
                         self.settings_open = true;
                     }
@@ -349,9 +1409,82 @@ impl eframe::App for MyApp {
                 ui.add_space(16.0);
 
                 egui::widgets::global_theme_preference_buttons(ui);
+
+                // settings from before profiles existed become the first profile
+                if self.profiles.is_empty() {
+                    self.store_profile();
+                }
+                ui.separator();
+                let mut switch_to = None;
+                egui::ComboBox::from_id_salt("profile")
+                    .selected_text(&self.profile)
+                    .show_ui(ui, |ui| {
+                        for name in self.profiles.keys() {
+                            if ui.selectable_label(*name == self.profile, name).clicked() {
+                                switch_to = Some(name.clone());
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text(format!("Library profile, writing to {}", self.output_path));
+                if let Some(name) = switch_to.filter(|name| *name != self.profile) {
+                    self.switch_profile(&name);
+                }
+
+                if let Some(status) = &self.git_status {
+                    ui.separator();
+                    let mut hover = status.root.display().to_string();
+                    if let Some(upstream) = &status.upstream {
+                        hover.push_str(&format!("\ntracking {}", upstream));
+                    }
+                    ui.label(format!("git: {}", status.summary()))
+                        .on_hover_text(hover);
+                }
+                if let Some(message) = self.status_message.lock().unwrap().as_ref() {
+                    ui.weak(message);
+                }
             });
         });
 
+        if !label_images.is_empty() {
+            self.start_scan(ctx, label_images);
+        }
+        if let Some(receiver) = &self.tab_loading {
+            match receiver.try_recv() {
+                Ok((code, part)) => {
+                    self.tab_loading = None;
+                    self.finish_tab_loading(code, part);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.tab_loading = None,
+            }
+        }
+        if let (Some(receiver), Some((_, result))) =
+            (&self.alternatives_running, &mut self.alternatives)
+        {
+            match receiver.try_recv() {
+                Ok(found) => {
+                    *result = Some(found);
+                    self.alternatives_running = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    *result = Some(Err(LookupError::Unavailable));
+                    self.alternatives_running = None;
+                }
+            }
+        }
+        if let Some(receiver) = &self.scan_running {
+            match receiver.try_recv() {
+                Ok(results) => {
+                    self.scan_running = None;
+                    self.finish_scan(results);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.scan_running = None,
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             if is_web {
@@ -361,10 +1494,11 @@ impl eframe::App for MyApp {
 
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
-                    ui.label("LCSC number or part URL: ");
+                    ui.label("LCSC number or part URL: ")
+                        .on_hover_text("You can also drop photos of LCSC labels onto the window.");
                     // ui.add(TextEdit::singleline(&mut self.part).desired_width(800.0));
 
-                    ui.add(
+                    let searchbox = ui.add(
                         DropDownBox::from_iter(
                             &self.history,
                             "searchbox",
@@ -376,93 +1510,129 @@ impl eframe::App for MyApp {
                         .filter_by_input(false),
                     );
 
-                    if ui.button("Search").clicked() {
-                        self.part = self.part.trim().to_owned();
-                        if let Some(tabledata) = Self::get_part(self.part.as_str()) {
-                            self.current_part = tabledata;
-                            self.search_good = true;
-                            // handle history
-                            self.history.push_front(self.part.clone());
-                            self.history.truncate(10);
-                        } else {
-                            self.search_good = false;
-                        }
+                    // barcode scanners finish their input with Enter, so we search on that as well
+                    let enter_pressed =
+                        searchbox.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Search").clicked() || enter_pressed {
+                        self.search();
                     }
+                    ui.checkbox(&mut self.reuse_tab, "Replace current tab")
+                        .on_hover_text("Show search results in the current tab instead of a new one.");
                 });
+                if !self.tabs.is_empty() {
+                    let mut select = None;
+                    let mut close = None;
+                    ui.horizontal_wrapped(|ui| {
+                        for (idx, tab) in self.tabs.iter().enumerate() {
+                            let title = match tab.data.get("Model") {
+                                Some(name) => format!("{} {}", tab.code, name),
+                                None => tab.code.clone(),
+                            };
+                            if ui
+                                .selectable_label(idx == self.active_tab, title)
+                                .clicked()
+                            {
+                                select = Some(idx);
+                            }
+                            if ui.small_button("✖").on_hover_text("Close tab").clicked() {
+                                close = Some(idx);
+                            }
+                            ui.add_space(8.0);
+                        }
+                    });
+                    if let Some(idx) = select {
+                        self.select_tab(ctx, idx);
+                    }
+                    if let Some(idx) = close {
+                        self.close_tab(ctx, idx);
+                    }
+                }
                 ui.horizontal(|ui| {
-                    if self.search_good {
+                    if self.tab_loading.is_some() {
+                        ui.spinner();
+                        ui.label(format!("Looking up {}…", self.part));
+                    } else if self.tabs.is_empty() && self.search_good {
+                        ui.label("Search for a part to open it.");
+                    } else if self.search_good {
                         ui.label(format!(
                             "Current Part: {}",
                             self.current_part
                                 .get("Component Code")
                                 .unwrap_or(&"".to_owned())
                         ));
+                        if let Some(source) = self.current_part.get("meta_source") {
+                            ui.weak(format!("(from {})", source));
+                        }
+                        if let Some(label) = &self.last_scan {
+                            ui.label(format!(
+                                "Scanned label: {} × {} pcs",
+                                label.manufacturer_part.as_deref().unwrap_or("unknown part"),
+                                label
+                                    .quantity
+                                    .map(|qty| qty.to_string())
+                                    .unwrap_or("?".to_owned())
+                            ));
+                        }
+                        if let Some(received) = self
+                            .current_part
+                            .get("Component Code")
+                            .and_then(|code| self.received_stock.get(code))
+                        {
+                            ui.label(format!("Received: {} pcs", received));
+                        }
                         if ui.button("Add to Library").clicked() {
-                            if let Some(curr_part) = self.current_part.get("Component Code") {
-                                let mut args = vec![
-                                    curr_part,
-                                    "-dir",
-                                    &self.output_path,
-                                    "-symbol_lib",
-                                    &self.symbol_lib,
-                                    "-symbol_lib_dir",
-                                    &self.symbol_lib_dir,
-                                    "-footprint_lib",
-                                    &self.footprint_lib,
-                                    "-model_dir",
-                                    &self.model_dir,
-                                ];
-                                if !self.model_base_variable.is_empty() {
-                                    args.push("-model_base_variable");
-                                    args.push(&self.model_base_variable);
-                                }
-                                if self.skip_existing {
-                                    args.push("--skip_existing");
-                                }
-                                if self.no_footprint {
-                                    args.push("--no_footprint");
-                                }
-                                if self.no_symbol {
-                                    args.push("--no_symbol");
-                                }
-                                let _ = Exec::cmd(&self.exe_path).args(&args).popen();
-                                if self.download_datasheet {
-                                    let dlpath = Path::new(&self.datasheet_dir);
-                                    if !dlpath.is_dir() {
-                                        let _ = create_dir_all(dlpath);
-                                    }
-                                    if let Some(url) = self.current_part.get("meta_datasheeturl") {
-                                        // the datasheet url points to an integrated parts view frame with an embedded pdf viewer
-                                        // we need to modify it for the download of the actual file
-                                        // https://datasheet.lcsc.com/lcsc/2206010216_UNI-ROYAL-Uniroyal-Elec-0402WGF1001TCE_C11702.pdf
-                                        // https://wmsc.lcsc.com/wmsc/upload/file/pdf/v2/lcsc/2206010216_UNI-ROYAL-Uniroyal-Elec-0402WGF1001TCE_C11702.pdf
-                                        let pdf_url = url.replace("https://www.lcsc.com/datasheet/lcsc_datasheet_", "https://wmsc.lcsc.com/wmsc/upload/file/pdf/v2/lcsc/");
-                                        println!("PDF-URL: {}", pdf_url); // Debug log
-                                        let filename = pdf_url.rsplit('/').next().unwrap_or(&format!("{}.pdf", curr_part)).to_string();
-                                        let dest_path = dlpath.join(filename);
-                                        let client = reqwest::blocking::Client::new();
-                                        if let Ok(response) = client
-                                            .get(&pdf_url)
-                                            .header(
-                                                reqwest::header::USER_AGENT,
-                                                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:134.0) Gecko/20100101 Firefox/134.0",
-                                            )
-                                            .send()
-                                        {
-                                            let content_type = response
-                                                .headers()
-                                                .get(reqwest::header::CONTENT_TYPE)
-                                                .map(|v| v.to_str().unwrap_or("unknown"))
-                                                .unwrap_or("unknown");
-                                            println!("Content-Type: {}", content_type); // Debug log
-                                            if response.status().is_success() && content_type.contains("application/pdf") {
-                                                if let Ok(bytes) = response.bytes() {
-                                                    let _ = File::create(&dest_path).and_then(|mut file| file.write_all(&bytes));
-                                                }
-                                            }
-                                        }
-                                    }
+                            if let Some(code) = self.current_part.get("Component Code") {
+                                self.add_to_library(
+                                    code,
+                                    self.current_part.get("meta_datasheeturl").map(String::as_str),
+                                    self.skip_existing,
+                                );
+                            }
+                        }
+                        if ui
+                            .button("Compare")
+                            .on_hover_text("Add the part to the side-by-side comparison.")
+                            .clicked()
+                        {
+                            let code = self.current_part.get("Component Code");
+                            if !self
+                                .compare_parts
+                                .iter()
+                                .any(|part| part.get("Component Code") == code)
+                            {
+                                self.compare_parts.push(self.current_part.clone());
+                            }
+                            self.compare_open = true;
+                        }
+                        // suggestions only make sense if there is a reason to look for a replacement
+                        let out_of_stock = self.current_part.get("Stock").is_some_and(|s| s == "0");
+                        let extended = self.current_part.get("Type").is_some_and(|t| t == "Extended");
+                        if (out_of_stock || extended) && ui.button("Find alternatives").clicked() {
+                            if let Some(code) = self.current_part.get("Component Code") {
+                                let (sender, receiver) = channel();
+                                let part = self.current_part.clone();
+                                let ctx = ctx.clone();
+                                thread::spawn(move || {
+                                    let _ = sender.send(find_alternatives(&part));
+                                    ctx.request_repaint();
+                                });
+                                self.alternatives = Some((code.clone(), None));
+                                self.alternatives_running = Some(receiver);
+                            }
+                        }
+                        if let Some(code) = self.current_part.get("Component Code") {
+                            if self.watchlist.contains(code) {
+                                if ui.button("Unwatch").clicked() {
+                                    self.watchlist.remove(code);
+                                    let _ = self.watchlist.save();
                                 }
+                            } else if ui
+                                .button("Watch")
+                                .on_hover_text("Get alerted when stock runs low or the part changes.")
+                                .clicked()
+                            {
+                                self.watchlist.add(code, 100);
+                                let _ = self.watchlist.save();
                             }
                         }
                         // in the rare case the temp dir cannot be created or isn't a UTF8 path,
@@ -486,7 +1656,7 @@ impl eframe::App for MyApp {
                                             "-model_dir",
                                             "packages3d",
                                         ];
-                                        let _ = Exec::cmd(&self.exe_path).args(&args).popen();
+                                        let _ = Exec::cmd(self.exe()).args(&args).popen();
 
                                         // now copy the generated footprint to the clipboard
                                         let glob = glob(
@@ -518,6 +1688,39 @@ impl eframe::App for MyApp {
                         ui.label("No such part found. Check part number or URL!");
                     }
                 });
+                // warn before adding something the library already has
+                let mut show_part = None;
+                for library_match in &self.library_matches {
+                    let symbol = &library_match.symbol;
+                    ui.horizontal(|ui| {
+                        let warning = if library_match.exact {
+                            "⚠ Already in the library:"
+                        } else {
+                            "⚠ Equivalent part in the library:"
+                        };
+                        ui.colored_label(ui.visuals().warn_fg_color, warning);
+                        let library = symbol
+                            .library
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy())
+                            .unwrap_or_default();
+                        ui.hyperlink_to(
+                            format!("{}:{}", library, symbol.name),
+                            format!("file://{}", symbol.library.display()),
+                        );
+                        if !library_match.exact {
+                            if let Some(code) = symbol.lcsc_code() {
+                                if ui.button(format!("Show {}", code)).clicked() {
+                                    show_part = Some(code.to_owned());
+                                }
+                            }
+                        }
+                    });
+                }
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
             });
 
             ui.separator();
@@ -578,6 +1781,67 @@ impl eframe::App for MyApp {
 
                 ui.separator();
 
+                // price ladder and order cost calculator
+                if let Some(prices) = PriceTable::from_part(&self.current_part) {
+                    ui.horizontal_top(|ui| {
+                        egui::Grid::new("price_breaks").striped(true).show(ui, |ui| {
+                            ui.strong("Quantity");
+                            ui.strong("Unit Price");
+                            ui.end_row();
+                            for price_break in &prices.breaks {
+                                ui.label(format!("{}+", price_break.quantity));
+                                ui.label(format!("${:.4}", price_break.unit_price));
+                                ui.end_row();
+                            }
+                        });
+                        ui.add_space(32.0);
+                        ui.vertical(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Quantity needed:");
+                                ui.add(egui::DragValue::new(&mut self.calc_quantity).range(1..=MAX_QUANTITY));
+                            });
+                            let quote = prices.quote(self.calc_quantity);
+                            ui.label(format!(
+                                "Order quantity: {} (minimum {}, multiples of {})",
+                                quote.order_quantity, prices.minimum_order, prices.order_multiple
+                            ));
+                            if let Some(reel_quantity) = prices.reel_quantity {
+                                ui.label(format!(
+                                    "Packaging: {} full reel(s) of {} + {} pcs cut tape",
+                                    quote.full_reels, reel_quantity, quote.cut_tape
+                                ));
+                            }
+                            ui.label(format!("Unit price: ${:.4}", quote.unit_price));
+                            ui.strong(format!("Extended price: ${:.2}", quote.extended_price));
+                        });
+                    });
+
+                    ui.separator();
+                }
+
+                // stock and price development, once we have seen the part more than once
+                let snapshots = self
+                    .current_part
+                    .get("Component Code")
+                    .map(|code| self.stock_history.snapshots(code))
+                    .unwrap_or_default();
+                if snapshots.len() > 1 {
+                    egui::CollapsingHeader::new(format!(
+                        "Stock & Price History ({} lookups since {})",
+                        snapshots.len(),
+                        format_date(snapshots[0].timestamp, false)
+                    ))
+                    .id_salt("stock_history")
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            history_plot(ui, "Stock", snapshots, |s| s.stock.map(|stock| stock as f64));
+                            history_plot(ui, "Unit Price ($)", snapshots, |s| s.unit_price);
+                        });
+                    });
+
+                    ui.separator();
+                }
+
                 ui.horizontal(|ui| {
                     for url in imagevec {
                         let img = ui
@@ -611,6 +1875,1082 @@ impl eframe::App for MyApp {
                 egui::warn_if_debug_build(ui);
             });
 
+            // results of scanned label images
+            if self.scan_open {
+                let mut show_part = None;
+                Window::new("Scanned Labels")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            if self.scan_running.is_some() {
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label("Reading labels…");
+                                });
+                            } else if self.scan_results.is_empty() {
+                                ui.label("No QR code found in the image.");
+                            }
+                            for result in &self.scan_results {
+                                ui.horizontal(|ui| {
+                                    ui.label(&result.part);
+                                    if let Some(label) = &result.label {
+                                        ui.label(format!(
+                                            "{} × {} pcs",
+                                            label.manufacturer_part.as_deref().unwrap_or("unknown part"),
+                                            label
+                                                .quantity
+                                                .map(|qty| qty.to_string())
+                                                .unwrap_or("?".to_owned())
+                                        ));
+                                        if let Some(order) = &label.order_number {
+                                            ui.weak(format!("order {}", order));
+                                        }
+                                    }
+                                    if result.found {
+                                        if ui.button("Show").clicked() {
+                                            show_part = Some(result.part.clone());
+                                        }
+                                    } else {
+                                        ui.label("No such part found.");
+                                    }
+                                });
+                            }
+                            if ui.button("Close").clicked() {
+                                self.scan_open = false;
+                            }
+                        });
+                    });
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+            }
+
+            // everything already generated into the output directory
+            if self.library_open {
+                let mut show_part = None;
+                let mut show_provenance = None;
+                let mut regenerate = None;
+                let mut regenerate_all = false;
+                let mut refresh = false;
+                Window::new("Library Browser")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Search:");
+                                ui.add(TextEdit::singleline(&mut self.library_query).desired_width(300.0));
+                                if ui.button("Refresh").clicked() {
+                                    refresh = true;
+                                }
+                                if ui
+                                    .add_enabled(self.regenerate_running.is_none(), egui::Button::new("Regenerate All…"))
+                                    .on_hover_text("Generate every part again into a staging directory and review the changes before they replace the library.")
+                                    .clicked()
+                                {
+                                    regenerate_all = true;
+                                }
+                                if ui
+                                    .button("Normalize")
+                                    .on_hover_text("Sort the symbol libraries by name and write them in canonical format.")
+                                    .clicked()
+                                {
+                                    self.library_error = self
+                                        .lock_library("normalizing")
+                                        .map_err(|e| e.to_string())
+                                        .and_then(|_lock| {
+                                            normalize_libraries(&self.library_paths())
+                                                .map_err(|e| e.to_string())
+                                        })
+                                        .err()
+                                        .map(|e| format!("Could not normalize: {}", e));
+                                    refresh = true;
+                                }
+                            });
+                            if let Some(error) = &self.library_error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                            let file_link = |ui: &mut egui::Ui, path: &Path| {
+                                let name = path
+                                    .file_name()
+                                    .map(|name| name.to_string_lossy())
+                                    .unwrap_or_default();
+                                ui.hyperlink_to(name, format!("file://{}", path.display()))
+                            };
+                            egui::ScrollArea::vertical().max_height(600.0).show(ui, |ui| {
+                                egui::Grid::new("library_entries")
+                                    .striped(true)
+                                    .num_columns(6)
+                                    .show(ui, |ui| {
+                                        ui.strong("Symbol");
+                                        ui.strong("LCSC");
+                                        ui.strong("Footprint");
+                                        ui.strong("3D Models");
+                                        ui.strong("Datasheet");
+                                        ui.label("");
+                                        ui.end_row();
+                                        for (idx, entry) in self.library_entries.iter().enumerate() {
+                                            if !entry.matches(&self.library_query) {
+                                                continue;
+                                            }
+                                            match &entry.symbol {
+                                                Some(symbol) => ui
+                                                    .hyperlink_to(
+                                                        &symbol.name,
+                                                        format!("file://{}", symbol.library.display()),
+                                                    )
+                                                    .on_hover_text(symbol.library.display().to_string()),
+                                                None => ui.weak("no symbol"),
+                                            };
+                                            ui.label(entry.lcsc_code().unwrap_or_default());
+                                            match &entry.footprint {
+                                                Some(footprint) => {
+                                                    ui.hyperlink_to(
+                                                        &footprint.name,
+                                                        format!("file://{}", footprint.path.display()),
+                                                    );
+                                                }
+                                                None => {
+                                                    ui.weak("–");
+                                                }
+                                            }
+                                            ui.vertical(|ui| {
+                                                for model in &entry.models {
+                                                    file_link(ui, model);
+                                                }
+                                            });
+                                            match &entry.datasheet {
+                                                Some(datasheet) => file_link(ui, datasheet),
+                                                None => ui.weak("–"),
+                                            };
+                                            ui.horizontal(|ui| {
+                                                if let Some(code) = entry.lcsc_code() {
+                                                    if ui.button("Show").clicked() {
+                                                        show_part = Some(code.to_owned());
+                                                    }
+                                                    if ui
+                                                        .add_enabled(self.regenerate_running.is_none(), egui::Button::new("Regenerate…"))
+                                                        .on_hover_text("Generate symbol, footprint and model again with the current settings and review the changes.")
+                                                        .clicked()
+                                                    {
+                                                        regenerate = Some(idx);
+                                                    }
+                                                    if ui
+                                                        .button("Provenance")
+                                                        .on_hover_text("Where the part came from: lookup data, converter, settings and file hashes at import.")
+                                                        .clicked()
+                                                    {
+                                                        show_provenance = Some(code.to_owned());
+                                                    }
+                                                }
+                                                if ui.button("Rename…").clicked() {
+                                                    let kind = if entry.symbol.is_some() {
+                                                        RenameKind::Symbol
+                                                    } else if entry.footprint.is_some() {
+                                                        RenameKind::Footprint
+                                                    } else {
+                                                        RenameKind::Model
+                                                    };
+                                                    self.library_rename = Some((idx, kind, String::new()));
+                                                }
+                                                if ui.button("Delete…").clicked() {
+                                                    self.library_delete = Some(idx);
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                            if ui.button("Close").clicked() {
+                                self.library_open = false;
+                            }
+                        });
+                    });
+
+                // rename dialog, the entry decides what can be renamed
+                let mut rename = None;
+                if let Some((idx, kind, new_name)) = &mut self.library_rename {
+                    let mut close = false;
+                    if let Some(entry) = self.library_entries.get(*idx) {
+                        Window::new(format!("Rename {}", entry.title()))
+                            .auto_sized()
+                            .interactable(true)
+                            .show(ctx, |ui| {
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        if entry.symbol.is_some() {
+                                            ui.radio_value(kind, RenameKind::Symbol, "Symbol");
+                                        }
+                                        if entry.footprint.is_some() {
+                                            ui.radio_value(kind, RenameKind::Footprint, "Footprint");
+                                        }
+                                        if !entry.models.is_empty() {
+                                            ui.radio_value(kind, RenameKind::Model, "3D Model");
+                                        }
+                                    });
+                                    ui.label(match kind {
+                                        RenameKind::Symbol => "The symbol is renamed in its library.",
+                                        RenameKind::Footprint => {
+                                            "All symbols using the footprint are updated, even from other parts."
+                                        }
+                                        RenameKind::Model => {
+                                            "All footprints using the model are updated, even from other parts."
+                                        }
+                                    });
+                                    ui.add(TextEdit::singleline(new_name).hint_text("new name"));
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Rename").clicked() {
+                                            rename = Some((*idx, *kind, new_name.trim().to_owned()));
+                                            close = true;
+                                        }
+                                        if ui.button("Cancel").clicked() {
+                                            close = true;
+                                        }
+                                    });
+                                });
+                            });
+                    } else {
+                        close = true;
+                    }
+                    if close {
+                        self.library_rename = None;
+                    }
+                }
+                if let Some((idx, kind, new_name)) = rename {
+                    self.rename_library_entry(idx, kind, &new_name);
+                }
+
+                // deletion has to be confirmed, listing everything that goes away
+                if let Some(idx) = self.library_delete {
+                    let mut close = false;
+                    let mut delete = false;
+                    if let Some(entry) = self.library_entries.get(idx) {
+                        Window::new(format!("Delete {}", entry.title()))
+                            .auto_sized()
+                            .interactable(true)
+                            .show(ctx, |ui| {
+                                ui.vertical(|ui| {
+                                    ui.label("This deletes:");
+                                    if let Some(symbol) = &entry.symbol {
+                                        ui.label(format!("• symbol {} from {}", symbol.name, symbol.library.display()));
+                                    }
+                                    if let Some(footprint) = &entry.footprint {
+                                        ui.label(format!("• footprint {}, unless other symbols still use it", footprint.path.display()));
+                                    }
+                                    for model in &entry.models {
+                                        ui.label(format!("• 3D model {}, unless still in use", model.display()));
+                                    }
+                                    if let Some(datasheet) = &entry.datasheet {
+                                        ui.label(format!("• datasheet {}", datasheet.display()));
+                                    }
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Delete").clicked() {
+                                            delete = true;
+                                            close = true;
+                                        }
+                                        if ui.button("Cancel").clicked() {
+                                            close = true;
+                                        }
+                                    });
+                                });
+                            });
+                        if delete {
+                            self.library_error = self
+                                .lock_library("deleting")
+                                .map_err(|e| e.to_string())
+                                .and_then(|_lock| {
+                                    delete_entry(entry, &self.library_entries)
+                                        .and_then(|()| match entry.lcsc_code() {
+                                            Some(code) => Provenance::remove(&self.output_dir(), code),
+                                            None => Ok(()),
+                                        })
+                                        .map_err(|e| e.to_string())
+                                })
+                                .err()
+                                .map(|e| format!("Could not delete: {}", e));
+                            refresh = true;
+                        }
+                    } else {
+                        close = true;
+                    }
+                    if close {
+                        self.library_delete = None;
+                    }
+                }
+
+                // names given in the library are kept and the changes reviewed, like for all parts
+                if let Some(code) = regenerate
+                    .and_then(|idx| self.library_entries.get(idx))
+                    .and_then(|entry| entry.lcsc_code())
+                    .map(str::to_owned)
+                {
+                    self.start_regeneration(ctx, Some(&code));
+                }
+                if regenerate_all {
+                    self.start_regeneration(ctx, None);
+                }
+                if refresh {
+                    self.library_entries = scan_library(&self.library_paths());
+                }
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+                if let Some(code) = show_provenance {
+                    let provenance =
+                        Provenance::load(&self.output_dir(), &code).map_err(|e| match e.kind() {
+                            std::io::ErrorKind::NotFound => {
+                                "No provenance was recorded, the part was added by an older version or by hand.".to_owned()
+                            }
+                            _ => format!("Could not read the provenance: {}", e),
+                        });
+                    self.provenance = Some((code, provenance));
+                }
+            }
+
+            // where a part of the library came from, from its sidecar file
+            if let Some((code, provenance)) = &self.provenance {
+                let mut close = false;
+                Window::new(format!("Provenance of {}", code))
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            match provenance {
+                                Ok(provenance) => {
+                                    egui::Grid::new("provenance").num_columns(2).show(ui, |ui| {
+                                        ui.label("Imported");
+                                        ui.label(format!(
+                                            "{} by {} on {}",
+                                            format_date(provenance.imported, true),
+                                            provenance.user,
+                                            provenance.host
+                                        ));
+                                        ui.end_row();
+                                        ui.label("Converter");
+                                        ui.label(format!(
+                                            "{} {}",
+                                            provenance.converter,
+                                            provenance.converter_version.as_deref().unwrap_or("(unknown version)")
+                                        ));
+                                        ui.end_row();
+                                        ui.label("Arguments");
+                                        ui.monospace(provenance.converter_args.join(" "));
+                                        ui.end_row();
+                                        ui.label("App version");
+                                        ui.label(&provenance.app_version);
+                                        ui.end_row();
+                                        ui.label("Datasheet");
+                                        match &provenance.datasheet_url {
+                                            Some(url) => ui.hyperlink(url),
+                                            None => ui.weak("–"),
+                                        };
+                                        ui.end_row();
+                                    });
+                                    ui.collapsing(format!("{} files", provenance.files.len()), |ui| {
+                                        egui::Grid::new("provenance_files").striped(true).show(ui, |ui| {
+                                            for (path, hash) in &provenance.files {
+                                                ui.label(path);
+                                                ui.monospace(&hash[..hash.len().min(16)]).on_hover_text(hash);
+                                                ui.end_row();
+                                            }
+                                        });
+                                    });
+                                    ui.collapsing("Lookup data", |ui| {
+                                        egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                                            egui::Grid::new("provenance_part").striped(true).show(ui, |ui| {
+                                                for (key, value) in &provenance.part {
+                                                    ui.label(key);
+                                                    ui.label(value);
+                                                    ui.end_row();
+                                                }
+                                            });
+                                        });
+                                    });
+                                }
+                                Err(e) => {
+                                    ui.label(e);
+                                }
+                            }
+                            if ui.button("Close").clicked() {
+                                close = true;
+                            }
+                        });
+                    });
+                if close {
+                    self.provenance = None;
+                }
+            }
+
+            // importing the parts of the manifest
+            if let Some(receiver) = &self.sync_running {
+                if let Ok(result) = receiver.try_recv() {
+                    self.sync_running = None;
+                    self.sync_result = Some(result);
+                    self.library_entries = scan_library(&self.library_paths());
+                }
+            }
+            if self.sync_open {
+                let mut rerun = false;
+                let mut verify = false;
+                let mut add_unlisted = None;
+                Window::new(format!("Library Manifest ({})", MANIFEST_FILE))
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            match &self.sync_result {
+                                _ if self.sync_running.is_some() => {
+                                    ui.horizontal(|ui| {
+                                        ui.spinner();
+                                        ui.label(format!("Importing the parts of {}…", MANIFEST_FILE));
+                                    });
+                                }
+                                Some(Ok(report)) => {
+                                    ui.label(report.to_string());
+                                    if !report.unlisted.is_empty()
+                                        && ui
+                                            .button(format!("Add to {}", MANIFEST_FILE))
+                                            .on_hover_text("List the parts that are only in the library in the manifest.")
+                                            .clicked()
+                                    {
+                                        add_unlisted = Some(report.unlisted.clone());
+                                    }
+                                }
+                                Some(Err(e)) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, e);
+                                }
+                                None => {}
+                            }
+                            match &self.verify_result {
+                                Some(Ok(drift)) if drift.is_empty() => {
+                                    ui.label(format!("The library matches {}.", LOCK_FILE));
+                                }
+                                Some(Ok(drift)) => {
+                                    for drift in drift {
+                                        ui.colored_label(ui.visuals().warn_fg_color, drift.to_string());
+                                    }
+                                }
+                                Some(Err(e)) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, e);
+                                }
+                                None => {}
+                            }
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add_enabled(self.sync_running.is_none(), egui::Button::new("Sync Again"))
+                                    .clicked()
+                                {
+                                    rerun = true;
+                                }
+                                if ui
+                                    .add_enabled(self.sync_running.is_none(), egui::Button::new("Verify"))
+                                    .on_hover_text(format!("Compare the library with the hashes in {} without changing anything.", LOCK_FILE))
+                                    .clicked()
+                                {
+                                    verify = true;
+                                }
+                                if ui.button("Close").clicked() {
+                                    self.sync_open = false;
+                                }
+                            });
+                        });
+                    });
+                if let Some(codes) = add_unlisted {
+                    if let Err(e) = append_to_manifest(&self.output_dir(), &codes) {
+                        self.sync_result = Some(Err(format!("Could not update {}: {}", MANIFEST_FILE, e)));
+                    } else {
+                        rerun = true;
+                    }
+                }
+                if rerun {
+                    self.verify_result = None;
+                    self.start_sync(ctx);
+                }
+                if verify {
+                    self.verify_result = Some(manifest::verify(&self.output_dir(), &self.library_paths()));
+                }
+            }
+
+            // dangling links in the library and what to do about them
+            if let Some(receiver) = &self.audit_running {
+                if let Ok(report) = receiver.try_recv() {
+                    self.audit_running = None;
+                    self.audit_report = Some(report);
+                }
+            }
+            if self.audit_open {
+                let mut rerun = false;
+                let mut apply_fixes = false;
+                Window::new("Audit Library")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.audit_online, "Check datasheet links online");
+                                if self.audit_running.is_some() {
+                                    ui.spinner();
+                                } else if ui.button("Run Again").clicked() {
+                                    rerun = true;
+                                }
+                            });
+                            if let Some(error) = &self.audit_error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                            if let Some(report) = &mut self.audit_report {
+                                if report.findings.is_empty() {
+                                    ui.label("No problems found.");
+                                }
+                                egui::ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                                    egui::Grid::new("audit_findings")
+                                        .striped(true)
+                                        .num_columns(3)
+                                        .show(ui, |ui| {
+                                            for finding in &report.findings {
+                                                let text = finding.severity.to_string();
+                                                if finding.fixed {
+                                                    ui.weak("fixed");
+                                                } else {
+                                                    match finding.severity {
+                                                        Severity::Error => ui.colored_label(ui.visuals().error_fg_color, text),
+                                                        Severity::Warning => ui.colored_label(ui.visuals().warn_fg_color, text),
+                                                        Severity::Info => ui.label(text),
+                                                    };
+                                                }
+                                                ui.label(format!("{}: {}", finding.subject, finding.problem));
+                                                match (&finding.fix, &finding.suggestion) {
+                                                    (Some(fix), _) => ui.label(format!("Fix: {}", fix)),
+                                                    (None, Some(suggestion)) => ui.weak(suggestion),
+                                                    (None, None) => ui.label(""),
+                                                };
+                                                ui.end_row();
+                                            }
+                                        });
+                                });
+                                ui.label(format!(
+                                    "{} errors, {} warnings, {} notes",
+                                    report.count(Severity::Error),
+                                    report.count(Severity::Warning),
+                                    report.count(Severity::Info)
+                                ));
+                                ui.horizontal(|ui| {
+                                    let fixable = report.fixable();
+                                    if ui
+                                        .add_enabled(fixable > 0, egui::Button::new(format!("Apply {} Safe Fixes", fixable)))
+                                        .on_hover_text("Relink symbols, footprints and datasheets to files that exist under a slightly different name or path.")
+                                        .clicked()
+                                    {
+                                        apply_fixes = true;
+                                    }
+                                    if ui.button("Copy Report").clicked() {
+                                        if let Ok(mut clipboard) = Clipboard::new() {
+                                            let _ = clipboard.set_text(report.to_string());
+                                        }
+                                    }
+                                });
+                            }
+                            if ui.button("Close").clicked() {
+                                self.audit_open = false;
+                            }
+                        });
+                    });
+                if apply_fixes {
+                    let result = match (self.lock_library("fixing"), &mut self.audit_report) {
+                        (Ok(_lock), Some(report)) => report.apply_fixes().map(|_| ()).map_err(|e| e.to_string()),
+                        (Err(e), _) => Err(e.to_string()),
+                        (Ok(_), None) => Ok(()),
+                    };
+                    if let Err(e) = result {
+                        self.audit_error = Some(format!("Could not apply fix: {}", e));
+                    }
+                    self.library_entries = scan_library(&self.library_paths());
+                }
+                if rerun {
+                    self.start_audit(ctx);
+                }
+            }
+
+            // review of a regeneration of the whole library, part by part
+            if let Some(receiver) = &self.regenerate_running {
+                for result in receiver.try_iter() {
+                    self.regenerate_results.push(result);
+                    self.regenerate_pending -= 1;
+                }
+                if self.regenerate_pending == 0 {
+                    self.regenerate_running = None;
+                }
+            }
+            if self.regenerate_open {
+                let mut decisions = vec![];
+                let mut close = false;
+                Window::new("Regenerate Library")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            if self.regenerate_running.is_some() {
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label(format!("{} parts left to generate…", self.regenerate_pending));
+                                });
+                            }
+                            if let Some(error) = &self.regenerate_error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
+                            let unchanged = self
+                                .regenerate_results
+                                .iter()
+                                .filter(|(_, result)| result.as_ref().is_ok_and(|(diff, _)| diff.is_empty()))
+                                .count();
+                            if unchanged > 0 {
+                                ui.label(format!("{} parts are unchanged.", unchanged));
+                            }
+                            ui.checkbox(&mut self.diff_as_text, "Show changes as text lines");
+                            egui::ScrollArea::vertical().max_height(600.0).show(ui, |ui| {
+                                for (idx, (code, result)) in self.regenerate_results.iter().enumerate() {
+                                    match result {
+                                        Err(e) => {
+                                            ui.horizontal(|ui| {
+                                                ui.strong(code);
+                                                ui.colored_label(ui.visuals().error_fg_color, e);
+                                            });
+                                        }
+                                        Ok((diff, _)) if diff.is_empty() => {}
+                                        Ok((diff, texts)) => {
+                                            ui.horizontal(|ui| {
+                                                ui.strong(code);
+                                                ui.label(diff.summary());
+                                                if ui.button("Accept").clicked() {
+                                                    decisions.push((idx, true));
+                                                }
+                                                if ui.button("Reject").clicked() {
+                                                    decisions.push((idx, false));
+                                                }
+                                            });
+                                            ui.indent(code, |ui| {
+                                                for (change, text) in diff.symbol.iter().chain(&diff.footprint).zip(texts) {
+                                                    egui::CollapsingHeader::new(change.target.display().to_string())
+                                                        .id_salt((code, &change.target))
+                                                        .show(ui, |ui| {
+                                                            show_text_diff(ui, (code, &change.target), text, self.diff_as_text);
+                                                        });
+                                                }
+                                                for (_, model) in &diff.models {
+                                                    ui.label(format!("3D model {}", model.display()));
+                                                }
+                                            });
+                                        }
+                                    }
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button("Accept All").clicked() {
+                                    decisions.extend((0..self.regenerate_results.len()).map(|idx| (idx, true)));
+                                }
+                                if ui.button("Close").clicked() {
+                                    close = true;
+                                }
+                            });
+                        });
+                    });
+                // later entries first, so the indices stay valid
+                decisions.sort_by(|a, b| b.0.cmp(&a.0));
+                let _lock = if decisions.iter().any(|(_, accepted)| *accepted) {
+                    match self.lock_library("regenerating") {
+                        Ok(lock) => Some(lock),
+                        Err(e) => {
+                            self.regenerate_error = Some(format!("Could not update: {}", e));
+                            decisions.clear();
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                for (idx, accepted) in decisions {
+                    let (code, result) = self.regenerate_results.remove(idx);
+                    if let (true, Ok((diff, _))) = (accepted, &result) {
+                        let mut result = accept(diff);
+                        if let (Ok(()), Some(symbol), true) = (&result, &diff.symbol, self.normalize_library) {
+                            result = normalize_library(&symbol.target).map(|_| ());
+                        }
+                        if let (Ok(()), Some(project)) = (&result, self.project()) {
+                            result = project.finish_import(&self.library_paths(), &self.symbol_lib);
+                        }
+                        if let Err(e) = result {
+                            self.regenerate_error = Some(format!("Could not update {}: {}", code, e));
+                        }
+                    }
+                }
+                if close {
+                    // dropping the receiver stops the background generation
+                    self.regenerate_open = false;
+                    self.regenerate_running = None;
+                    self.regenerate_staging = None;
+                    self.regenerate_results.clear();
+                    self.library_entries = scan_library(&self.library_paths());
+                }
+            }
+
+            // structural diff of two library files picked by the user
+            if let Some((old_path, new_path, diff)) = &self.file_diff {
+                let mut close = false;
+                Window::new("Diff Library Files")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            ui.label(format!("− {}", old_path.display()));
+                            ui.label(format!("+ {}", new_path.display()));
+                            ui.checkbox(&mut self.diff_as_text, "Show changes as text lines");
+                            egui::ScrollArea::vertical().max_height(600.0).show(ui, |ui| {
+                                show_text_diff(ui, "file_diff", diff, self.diff_as_text);
+                            });
+                            if ui.button("Close").clicked() {
+                                close = true;
+                            }
+                        });
+                    });
+                if close {
+                    self.file_diff = None;
+                }
+            }
+
+            // numeric search through known parts
+            if self.find_open {
+                let mut show_part = None;
+                Window::new("Find Parts")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            ui.label("Search the parts looked up so far and the symbol library:");
+                            ui.horizontal(|ui| {
+                                let filterbox = ui.add(
+                                    TextEdit::singleline(&mut self.find_filter)
+                                        .hint_text("capacitors ≥10µF, ≥16V")
+                                        .desired_width(400.0),
+                                );
+                                let enter_pressed = filterbox.lost_focus()
+                                    && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                if ui.button("Find").clicked() || enter_pressed {
+                                    self.find_results = Some(self.find_parts());
+                                }
+                            });
+                            match &self.find_results {
+                                Some(Ok(parts)) if parts.is_empty() => {
+                                    ui.label("No matching parts.");
+                                }
+                                Some(Ok(parts)) => {
+                                    egui::ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                                        egui::Grid::new("found_parts")
+                                            .striped(true)
+                                            .num_columns(5)
+                                            .show(ui, |ui| {
+                                                ui.strong("Part");
+                                                ui.strong("Name");
+                                                ui.strong("Value");
+                                                ui.strong("Found in");
+                                                ui.label("");
+                                                ui.end_row();
+                                                for part in parts {
+                                                    ui.label(part.code.as_deref().unwrap_or("?"));
+                                                    ui.label(&part.name);
+                                                    ui.label(part.value.as_deref().unwrap_or_default());
+                                                    ui.label(&part.origin);
+                                                    if let Some(code) = &part.code {
+                                                        if ui.button("Show").clicked() {
+                                                            show_part = Some(code.clone());
+                                                        }
+                                                    } else {
+                                                        ui.label("");
+                                                    }
+                                                    ui.end_row();
+                                                }
+                                            });
+                                    });
+                                }
+                                Some(Err(e)) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, e);
+                                }
+                                None => {}
+                            }
+                            if ui.button("Close").clicked() {
+                                self.find_open = false;
+                            }
+                        });
+                    });
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+            }
+
+            // side-by-side comparison
+            if self.compare_open {
+                let mut show_part = None;
+                let mut remove_part = None;
+                Window::new("Compare Parts")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            let rows = compare_rows(&self.compare_parts);
+                            if self.compare_parts.is_empty() {
+                                ui.label("Use the Compare button next to a part to add it here.");
+                            } else {
+                                egui::ScrollArea::both().max_height(600.0).show(ui, |ui| {
+                                    egui::Grid::new("compare")
+                                        .striped(true)
+                                        .num_columns(self.compare_parts.len() + 1)
+                                        .show(ui, |ui| {
+                                            ui.label("");
+                                            for (idx, part) in self.compare_parts.iter().enumerate() {
+                                                ui.horizontal(|ui| {
+                                                    if ui.small_button("Show").clicked() {
+                                                        show_part = part.get("Component Code").cloned();
+                                                    }
+                                                    if ui.small_button("Remove").clicked() {
+                                                        remove_part = Some(idx);
+                                                    }
+                                                });
+                                            }
+                                            ui.end_row();
+                                            for row in &rows {
+                                                // differing attributes are what the comparison is about
+                                                let color = if row.differs {
+                                                    ui.visuals().warn_fg_color
+                                                } else {
+                                                    ui.visuals().text_color()
+                                                };
+                                                ui.strong(&row.key);
+                                                for value in &row.values {
+                                                    ui.colored_label(color, value.as_deref().unwrap_or("–"));
+                                                }
+                                                ui.end_row();
+                                            }
+                                        });
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button("Copy as Markdown").clicked() {
+                                    if let Ok(mut clipboard) = Clipboard::new() {
+                                        let _ = clipboard.set_text(to_markdown(&rows));
+                                    }
+                                }
+                                if !is_web && ui.button("Export…").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("CSV", &["csv"])
+                                        .add_filter("Markdown", &["md"])
+                                        .set_file_name("comparison.csv")
+                                        .save_file()
+                                    {
+                                        let contents = match path.extension().and_then(|e| e.to_str()) {
+                                            Some("md") => to_markdown(&rows),
+                                            _ => to_csv(&rows),
+                                        };
+                                        if let Err(e) = std::fs::write(&path, contents) {
+                                            log::warn!("Could not export comparison: {}", e);
+                                        }
+                                    }
+                                }
+                                if ui.button("Clear").clicked() {
+                                    self.compare_parts.clear();
+                                }
+                                if ui.button("Close").clicked() {
+                                    self.compare_open = false;
+                                }
+                            });
+                        });
+                    });
+                if let Some(idx) = remove_part {
+                    self.compare_parts.remove(idx);
+                }
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+            }
+
+            // suggested replacements for the current part
+            if let Some((code, result)) = &self.alternatives {
+                let mut show_part = None;
+                let mut close = false;
+                Window::new(format!("Alternatives for {}", code))
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            match result {
+                                None => {
+                                    ui.horizontal(|ui| {
+                                        ui.spinner();
+                                        ui.label("Searching…");
+                                    });
+                                }
+                                Some(Ok(candidates)) if candidates.is_empty() => {
+                                    ui.label("No parts in stock with the same package and key attributes found.");
+                                }
+                                Some(Ok(candidates)) => {
+                                    egui::ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                                        egui::Grid::new("alternatives")
+                                            .striped(true)
+                                            .num_columns(7)
+                                            .show(ui, |ui| {
+                                                ui.strong("Part");
+                                                ui.strong("Model");
+                                                ui.strong("Brand");
+                                                ui.strong("Type");
+                                                ui.strong("Stock");
+                                                ui.strong("Unit Price");
+                                                ui.label("");
+                                                ui.end_row();
+                                                for candidate in candidates {
+                                                    ui.label(&candidate.code);
+                                                    ui.label(&candidate.model);
+                                                    ui.label(&candidate.brand);
+                                                    ui.label(candidate.part_type.as_deref().unwrap_or("?"));
+                                                    ui.label(candidate.stock.to_string());
+                                                    ui.label(
+                                                        candidate
+                                                            .unit_price
+                                                            .map(|price| format!("${}", price))
+                                                            .unwrap_or("?".to_owned()),
+                                                    );
+                                                    if ui.button("Show").clicked() {
+                                                        show_part = Some(candidate.code.clone());
+                                                    }
+                                                    ui.end_row();
+                                                }
+                                            });
+                                    });
+                                }
+                                Some(Err(e)) => {
+                                    ui.label(format!("Could not search for alternatives: {}", e));
+                                }
+                            }
+                            if ui.button("Close").clicked() {
+                                close = true;
+                            }
+                        });
+                    });
+                if close {
+                    // dropping the receiver leaves a running search to finish unheard
+                    self.alternatives = None;
+                    self.alternatives_running = None;
+                }
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+            }
+
+            // alerts raised by the watchlist refresh
+            if !self.alerts.is_empty() {
+                Window::new("Watchlist Alerts")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            for alert in &self.alerts {
+                                ui.label(alert);
+                            }
+                            if ui.button("Dismiss").clicked() {
+                                self.alerts.clear();
+                            }
+                        });
+                    });
+            }
+
+            // watched parts
+            if self.watchlist_open {
+                let mut show_part = None;
+                let mut remove_part = None;
+                let mut changed = false;
+                Window::new("Watchlist")
+                    .auto_sized()
+                    .interactable(true)
+                    .show(ctx, |ui| {
+                        ui.vertical(|ui| {
+                            if self.watchlist.parts.is_empty() {
+                                ui.label("No parts watched yet, use the Watch button next to a part.");
+                            }
+                            egui::Grid::new("watchlist")
+                                .striped(true)
+                                .num_columns(7)
+                                .show(ui, |ui| {
+                                    ui.strong("Part");
+                                    ui.strong("Alert below");
+                                    ui.strong("Stock");
+                                    ui.strong("Type");
+                                    ui.strong("Last checked");
+                                    ui.label("");
+                                    ui.label("");
+                                    ui.end_row();
+                                    for part in &mut self.watchlist.parts {
+                                        ui.label(&part.code);
+                                        changed |= ui
+                                            .add(egui::DragValue::new(&mut part.threshold))
+                                            .changed();
+                                        if part.missing {
+                                            ui.colored_label(ui.visuals().error_fg_color, "not listed");
+                                        } else {
+                                            ui.label(
+                                                part.last_stock
+                                                    .map(|stock| stock.to_string())
+                                                    .unwrap_or("?".to_owned()),
+                                            );
+                                        }
+                                        ui.label(part.last_type.as_deref().unwrap_or("?"));
+                                        ui.label(
+                                            part.last_checked
+                                                .map(|time| format_date(time, true))
+                                                .unwrap_or("never".to_owned()),
+                                        );
+                                        if ui.button("Show").clicked() {
+                                            show_part = Some(part.code.clone());
+                                        }
+                                        if ui.button("Remove").clicked() {
+                                            remove_part = Some(part.code.clone());
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                            ui.horizontal(|ui| {
+                                ui.label("Refresh every (minutes, 0 = never):");
+                                changed |= ui
+                                    .add(egui::DragValue::new(&mut self.watchlist.refresh_minutes))
+                                    .changed();
+                            });
+                            ui.horizontal(|ui| {
+                                if self.watch_refresh.is_some() {
+                                    ui.spinner();
+                                    ui.label("Refreshing…");
+                                } else if ui.button("Refresh now").clicked() {
+                                    self.start_watch_refresh(ctx);
+                                }
+                                if ui.button("Close").clicked() {
+                                    self.watchlist_open = false;
+                                }
+                            });
+                        });
+                    });
+                if let Some(code) = remove_part {
+                    self.watchlist.remove(&code);
+                    changed = true;
+                }
+                if changed {
+                    let _ = self.watchlist.save();
+                }
+                if let Some(part) = show_part {
+                    self.part = part;
+                    self.search();
+                }
+            }
+
             //settings window
             if self.settings_open {
                 Window::new("Settings")
@@ -619,45 +2959,152 @@ impl eframe::App for MyApp {
                     .show(ctx, |ui| {
                         ui.vertical(|ui| {
                             ui.heading("Settings");
+                            if let Some(path) = &self.config_file {
+                                ui.label(format!("Kept in {}", path.display()));
+                            }
+                            if !self.config_overrides.is_empty() {
+                                let variables: Vec<&str> = self
+                                    .config_overrides
+                                    .iter()
+                                    .map(|o| o.variable.as_str())
+                                    .collect();
+                                ui.label(format!(
+                                    "Set by the environment, not saved: {}",
+                                    variables.join(", ")
+                                ));
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Profile:");
+                                ui.add(
+                                    TextEdit::singleline(&mut self.profile_name)
+                                        .hint_text(self.profile.as_str())
+                                        .desired_width(200.0),
+                                );
+                                if ui
+                                    .add_enabled(!self.profile_name.trim().is_empty(), egui::Button::new("Rename"))
+                                    .clicked()
+                                {
+                                    let name = std::mem::take(&mut self.profile_name);
+                                    self.rename_profile(&name);
+                                }
+                                if ui.button("Duplicate").clicked() {
+                                    let (name, profile) = (self.profile.clone(), self.current_profile());
+                                    self.add_profile(&name, profile);
+                                }
+                                if ui
+                                    .add_enabled(self.profiles.len() > 1, egui::Button::new("Delete"))
+                                    .clicked()
+                                {
+                                    self.delete_profile();
+                                }
+                                if ui.button("Import…").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Profile", &["toml"])
+                                        .pick_file()
+                                    {
+                                        match read_to_string(&path)
+                                            .map_err(|e| e.to_string())
+                                            .and_then(|text| Profile::import(&text))
+                                        {
+                                            Ok((name, profile)) => {
+                                                self.profile_error = None;
+                                                self.add_profile(&name, profile);
+                                            }
+                                            Err(e) => {
+                                                self.profile_error = Some(format!("Could not import {}: {}", path.display(), e));
+                                            }
+                                        }
+                                    }
+                                }
+                                if ui.button("Export…").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Profile", &["toml"])
+                                        .set_file_name(format!("{}.toml", self.profile))
+                                        .save_file()
+                                    {
+                                        self.profile_error = self
+                                            .current_profile()
+                                            .export(&self.profile)
+                                            .and_then(|text| std::fs::write(&path, text).map_err(|e| e.to_string()))
+                                            .err()
+                                            .map(|e| format!("Could not export to {}: {}", path.display(), e));
+                                    }
+                                }
+                            });
+                            if let Some(error) = &self.profile_error {
+                                ui.colored_label(ui.visuals().error_fg_color, error);
+                            }
                             ui.checkbox(&mut self.download_datasheet, "Download datasheet");
                             ui.checkbox(&mut self.skip_existing, "Skip existing components");
                             ui.checkbox(&mut self.no_footprint, "Skip footprint generation");
                             ui.checkbox(&mut self.no_symbol, "Skip symbol generation");
-                            ui.label("Path of JLC2KiCadLib executable:");
-                            ui.add(TextEdit::singleline(&mut self.exe_path).desired_width(800.0));
-                            ui.label("Output directory for the generated library (absolute path):");
-                            ui.add(
-                                TextEdit::singleline(&mut self.output_path).desired_width(800.0),
+                            ui.checkbox(&mut self.normalize_library, "Keep the symbol library sorted and canonically formatted")
+                                .on_hover_text("Rewrites the symbol library after each import, so it gives small diffs in git.");
+                            ui.checkbox(
+                                &mut self.record_scanned_stock,
+                                "Record quantity of scanned LCSC labels as received stock",
                             );
+                            ui.label("Git, if the output directory is in a repository:");
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.git_pull, "Pull before adding a part");
+                                ui.checkbox(&mut self.git_commit, "Commit added parts");
+                                ui.add_enabled(
+                                    self.git_commit,
+                                    egui::Checkbox::new(&mut self.git_push, "Push after committing"),
+                                );
+                            });
+                            let paths = self.library_paths();
+                            let output = self.output_dir();
+                            ui.label("Path of JLC2KiCadLib executable (~ and $VARIABLES are expanded):");
+                            let exe = self.exe_check();
+                            ui.horizontal(|ui| {
+                                ui.add(TextEdit::singleline(&mut self.exe_path).desired_width(640.0));
+                                if !is_web && ui.button("Browse…").clicked() {
+                                    if let Some(file) = rfd::FileDialog::new().pick_file() {
+                                        self.exe_path = file.to_string_lossy().into_owned();
+                                    }
+                                }
+                                show_check(ui, exe);
+                            });
+                            ui.label("Output directory for the generated library:");
+                            let check = self.dir_check(&output);
+                            dir_setting(ui, &mut self.output_path, &output, None, check);
                             ui.label(
                                 "Name of the symbol directory (relative to output directory):",
                             );
-                            ui.add(
-                                TextEdit::singleline(&mut self.symbol_lib_dir).desired_width(800.0),
-                            );
+                            let check = self.dir_check(&paths.symbol_dir);
+                            dir_setting(ui, &mut self.symbol_lib_dir, &paths.symbol_dir, Some(&output), check);
                             ui.label(
                                 "Name of the footprint library (relative to output directory):",
                             );
-                            ui.add(
-                                TextEdit::singleline(&mut self.footprint_lib).desired_width(800.0),
-                            );
+                            let check = self.dir_check(&paths.footprint_dir);
+                            dir_setting(ui, &mut self.footprint_lib, &paths.footprint_dir, Some(&output), check);
                             ui.label("Name of the symbol library:");
                             ui.add(TextEdit::singleline(&mut self.symbol_lib).desired_width(800.0));
                             ui.label(
                                 "Name of the 3D model directory (relative to footprint directory):",
                             );
-                            ui.add(TextEdit::singleline(&mut self.model_dir).desired_width(800.0));
+                            let check = self.dir_check(&paths.model_dir);
+                            dir_setting(ui, &mut self.model_dir, &paths.model_dir, Some(&paths.footprint_dir), check);
                             ui.label("Base path variable for 3D Models (start with $):");
-                            ui.add(
-                                TextEdit::singleline(&mut self.model_base_variable)
-                                    .desired_width(800.0),
-                            );
-                            ui.label("Output directory for downloaded datasheets (absolute path):");
-                            ui.add(
-                                TextEdit::singleline(&mut self.datasheet_dir).desired_width(800.0),
-                            );
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    TextEdit::singleline(&mut self.model_base_variable)
+                                        .desired_width(640.0),
+                                );
+                                if !self.model_base_variable.is_empty()
+                                    && !self.model_base_variable.starts_with('$')
+                                {
+                                    show_check(ui, Err("must start with $".to_owned()));
+                                }
+                            });
+                            ui.label("Output directory for downloaded datasheets:");
+                            let check = self.dir_check(&paths.datasheet_dir);
+                            dir_setting(ui, &mut self.datasheet_dir, &paths.datasheet_dir, None, check);
                             if ui.button("Close").clicked() {
                                 self.settings_open = false;
+                                self.dir_checks.clear();
+                                self.exe_checks.clear();
                             }
                         });
                     });
@@ -666,6 +3113,197 @@ impl eframe::App for MyApp {
     }
 }
 
+/// Shows next to a setting whether it can be used, in red if not.
+fn show_check(ui: &mut egui::Ui, check: Result<String, String>) {
+    match check {
+        Ok(note) => ui.weak(note),
+        Err(problem) => ui.colored_label(ui.visuals().error_fg_color, problem),
+    };
+}
+
+/// Edits a directory setting with a folder picker. Picked folders inside `base` are
+/// stored relative to it, like the setting defaults.
+fn dir_setting(
+    ui: &mut egui::Ui,
+    value: &mut String,
+    dir: &Path,
+    base: Option<&Path>,
+    check: DirCheck,
+) {
+    ui.horizontal(|ui| {
+        ui.add(TextEdit::singleline(value).desired_width(640.0));
+        if !cfg!(target_arch = "wasm32") && ui.button("Browse…").clicked() {
+            let mut dialog = rfd::FileDialog::new();
+            if let Some(start) = dir.ancestors().find(|dir| dir.is_dir()) {
+                dialog = dialog.set_directory(start);
+            }
+            if let Some(picked) = dialog.pick_folder() {
+                let picked = base
+                    .and_then(|base| picked.strip_prefix(base).ok())
+                    .unwrap_or(&picked);
+                *value = picked.to_string_lossy().into_owned();
+            }
+        }
+        let text = format!("{} {}", dir.display(), check);
+        show_check(ui, if check.is_ok() { Ok(text) } else { Err(text) });
+    });
+}
+
+/// The details of a restored tab, `None` if the part was not found.
+type TabLookup = (String, Option<IndexMap<String, String>>);
+
+/// Result of searching for replacements of a part.
+type Alternatives = Result<Vec<Candidate>, LookupError>;
+
+/// A part regenerated for review, with the diffs of its symbol and footprint files.
+type Regenerated = (String, Result<(PartDiff, Vec<TextDiff>), String>);
+
+/// The line and node diffs of two texts, computed once rather than on every frame.
+struct TextDiff {
+    lines: Vec<LineChange>,
+    /// `None` when there is no old text to compare nodes with
+    nodes: Option<Result<Vec<NodeChange>, ParseError>>,
+}
+
+impl TextDiff {
+    fn new(old: Option<&str>, new: &str) -> Self {
+        Self {
+            lines: diff_lines(old.unwrap_or_default(), new),
+            nodes: old.map(|old| diff_sexpr(old, new)),
+        }
+    }
+}
+
+/// Shows a diff as lines, or node by node unless `as_text` or there is nothing to compare.
+fn show_text_diff(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash + Copy,
+    diff: &TextDiff,
+    as_text: bool,
+) {
+    match &diff.nodes {
+        Some(nodes) if !as_text => show_sexpr_diff(ui, id, nodes, &diff.lines),
+        _ => show_diff(ui, &diff.lines),
+    }
+}
+
+/// Shows a line diff, leaving out the unchanged parts.
+fn show_diff(ui: &mut egui::Ui, changes: &[LineChange]) {
+    let added = egui::Color32::from_rgb(0x2e, 0xa0, 0x43);
+    for change in with_context(changes, 3) {
+        let (text, color) = match change {
+            None => ("…".to_owned(), ui.visuals().weak_text_color()),
+            Some(LineChange::Same(line)) => (format!("  {}", line), ui.visuals().text_color()),
+            Some(LineChange::Removed(line)) => (format!("- {}", line), ui.visuals().error_fg_color),
+            Some(LineChange::Added(line)) => (format!("+ {}", line), added),
+        };
+        ui.label(egui::RichText::new(text).monospace().color(color));
+    }
+}
+
+/// Shows what changed between two KiCad files node by node, grouped into pins, pads,
+/// properties and graphics. Falls back to the line diff for text that does not parse.
+fn show_sexpr_diff(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash + Copy,
+    nodes: &Result<Vec<NodeChange>, ParseError>,
+    lines: &[LineChange],
+) {
+    let changes = match nodes {
+        Ok(changes) => changes,
+        Err(e) => {
+            ui.weak(format!(
+                "Cannot compare structurally ({}), showing lines:",
+                e
+            ));
+            show_diff(ui, lines);
+            return;
+        }
+    };
+    if changes.is_empty() {
+        ui.label("No changes besides formatting and order.");
+        return;
+    }
+    let added = egui::Color32::from_rgb(0x2e, 0xa0, 0x43);
+    for group in changes.chunk_by(|a, b| a.category == b.category) {
+        let category = group[0].category;
+        egui::CollapsingHeader::new(format!("{} ({})", category, group.len()))
+            .id_salt((id, category.to_string()))
+            .default_open(true)
+            .show(ui, |ui| {
+                egui::Grid::new((id, category.to_string(), "changes"))
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for change in group {
+                            let (marker, color) = match change.kind {
+                                ChangeKind::Added => ("+", added),
+                                ChangeKind::Removed => ("−", ui.visuals().error_fg_color),
+                                ChangeKind::Changed => ("~", ui.visuals().warn_fg_color),
+                            };
+                            ui.colored_label(color, marker);
+                            ui.label(&change.path);
+                            ui.vertical(|ui| {
+                                if let Some(old) = &change.old {
+                                    ui.label(
+                                        egui::RichText::new(old)
+                                            .monospace()
+                                            .color(ui.visuals().error_fg_color),
+                                    );
+                                }
+                                if let Some(new) = &change.new {
+                                    ui.label(egui::RichText::new(new).monospace().color(added));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
+
+/// Downloads the datasheet of a part into `dir`.
+fn download_datasheet(dir: &Path, code: &str, url: &str) {
+    if !dir.is_dir() {
+        let _ = create_dir_all(dir);
+    }
+    // the datasheet url points to an integrated parts view frame with an embedded pdf viewer
+    // we need to modify it for the download of the actual file
+    // https://datasheet.lcsc.com/lcsc/2206010216_UNI-ROYAL-Uniroyal-Elec-0402WGF1001TCE_C11702.pdf
+    // https://wmsc.lcsc.com/wmsc/upload/file/pdf/v2/lcsc/2206010216_UNI-ROYAL-Uniroyal-Elec-0402WGF1001TCE_C11702.pdf
+    let pdf_url = url.replace(
+        "https://www.lcsc.com/datasheet/lcsc_datasheet_",
+        "https://wmsc.lcsc.com/wmsc/upload/file/pdf/v2/lcsc/",
+    );
+    println!("PDF-URL: {}", pdf_url); // Debug log
+    let filename = pdf_url
+        .rsplit('/')
+        .next()
+        .unwrap_or(&format!("{}.pdf", code))
+        .to_string();
+    let dest_path = dir.join(filename);
+    let client = reqwest::blocking::Client::new();
+    if let Ok(response) = client
+        .get(&pdf_url)
+        .header(
+            reqwest::header::USER_AGENT,
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:134.0) Gecko/20100101 Firefox/134.0",
+        )
+        .send()
+    {
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap_or("unknown"))
+            .unwrap_or("unknown");
+        println!("Content-Type: {}", content_type); // Debug log
+        if response.status().is_success() && content_type.contains("application/pdf") {
+            if let Ok(bytes) = response.bytes() {
+                let _ = File::create(&dest_path).and_then(|mut file| file.write_all(&bytes));
+            }
+        }
+    }
+}
+
 pub fn is_hover_rect(ui: &egui::Ui, rect: egui::Rect) -> bool {
     let pointer_pos = ui.input(|i| i.pointer.hover_pos());
     let Some(pos) = pointer_pos else {
@@ -675,6 +3313,33 @@ pub fn is_hover_rect(ui: &egui::Ui, rect: egui::Rect) -> bool {
     rect.contains(pos)
 }
 
+/// Plots one value of the stock history over time, both plots share their time axis.
+fn history_plot(
+    ui: &mut egui::Ui,
+    title: &str,
+    snapshots: &[Snapshot],
+    value: impl Fn(&Snapshot) -> Option<f64>,
+) {
+    let points: Vec<[f64; 2]> = snapshots
+        .iter()
+        .filter_map(|s| value(s).map(|v| [s.timestamp as f64, v]))
+        .collect();
+    ui.vertical(|ui| {
+        ui.label(title);
+        Plot::new(title)
+            .width(400.0)
+            .height(160.0)
+            .link_axis("stock_history", [true, false])
+            .x_axis_formatter(|mark, _range| format_date(mark.value as i64, false))
+            .label_formatter(|_name, point| {
+                format!("{}\n{}", format_date(point.x as i64, true), point.y)
+            })
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(title, PlotPoints::from(points)));
+            });
+    });
+}
+
 fn powered_by(ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
         ui.spacing_mut().item_spacing.x = 0.0;
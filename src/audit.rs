@@ -0,0 +1,620 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use regex::Regex;
+
+use crate::{
+    library::read_symbols,
+    library_manager::{
+        Footprint, LibraryEntry, LibraryPaths, files_with_extension, footprint_name,
+        read_footprint, scan_library, set_model_path_in, set_symbol_property_in, update_file,
+    },
+    paths::expand_path,
+    project::{PROJECT_VARIABLE, project_dir_of},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        })
+    }
+}
+
+/// A change that repairs a finding without guessing, so it can be applied automatically.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    /// point the Footprint property of a symbol to another footprint
+    Footprint {
+        library: PathBuf,
+        symbol: String,
+        footprint: String,
+    },
+    /// replace a model path of a footprint
+    Model {
+        footprint: PathBuf,
+        old: String,
+        new: String,
+    },
+    /// point the Datasheet property of a symbol to another file or link
+    Datasheet {
+        library: PathBuf,
+        symbol: String,
+        datasheet: String,
+    },
+}
+
+impl Fix {
+    fn apply(&self) -> io::Result<()> {
+        match self {
+            Fix::Footprint {
+                library,
+                symbol,
+                footprint,
+            } => update_file(library, |text| {
+                set_symbol_property_in(text, symbol, "Footprint", footprint)
+            }),
+            Fix::Model {
+                footprint,
+                old,
+                new,
+            } => update_file(footprint, |text| Ok(set_model_path_in(text, old, new))),
+            Fix::Datasheet {
+                library,
+                symbol,
+                datasheet,
+            } => update_file(library, |text| {
+                set_symbol_property_in(text, symbol, "Datasheet", datasheet)
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fix::Footprint { footprint, .. } => {
+                write!(f, "point the symbol to \"{}\"", footprint)
+            }
+            Fix::Model { new, .. } => write!(f, "point the model to \"{}\"", new),
+            Fix::Datasheet { datasheet, .. } => {
+                write!(f, "point the datasheet to \"{}\"", datasheet)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// what the finding is about, e.g. `default_lib/0402WGF1001TCE`
+    pub subject: String,
+    pub problem: String,
+    /// what the user can do about it, if it cannot be fixed automatically
+    pub suggestion: Option<String>,
+    pub fix: Option<Fix>,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditReport {
+    pub findings: Vec<Finding>,
+}
+
+impl AuditReport {
+    fn push(&mut self, severity: Severity, subject: &str, problem: String) -> &mut Finding {
+        self.findings.push(Finding {
+            severity,
+            subject: subject.to_owned(),
+            problem,
+            suggestion: None,
+            fix: None,
+            fixed: false,
+        });
+        self.findings.last_mut().unwrap()
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity && !finding.fixed)
+            .count()
+    }
+
+    /// Whether any error or warning is left that was not fixed.
+    pub fn has_problems(&self) -> bool {
+        self.count(Severity::Error) + self.count(Severity::Warning) > 0
+    }
+
+    pub fn fixable(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.fix.is_some() && !finding.fixed)
+            .count()
+    }
+
+    /// Applies all safe fixes, returning how many were applied.
+    pub fn apply_fixes(&mut self) -> io::Result<usize> {
+        let mut applied = 0;
+        for finding in &mut self.findings {
+            if let (Some(fix), false) = (&finding.fix, finding.fixed) {
+                fix.apply()?;
+                finding.fixed = true;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(
+                f,
+                "{:<8}{}: {}",
+                finding.severity, finding.subject, finding.problem
+            )?;
+            if let Some(fix) = &finding.fix {
+                let state = if finding.fixed { "applied" } else { "safe" };
+                writeln!(f, "        fix ({}): {}", state, fix)?;
+            } else if let Some(suggestion) = &finding.suggestion {
+                writeln!(f, "        suggestion: {}", suggestion)?;
+            }
+        }
+        write!(
+            f,
+            "{} errors, {} warnings, {} notes",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info)
+        )
+    }
+}
+
+/// Resolves a model path the way KiCad does. The configured base variable stands for the
+/// footprint directory, other variables come from the environment. Returns the name of
+/// the variable as error if it is unknown here, like `KIPRJMOD` outside of a project.
+pub fn resolve_model_path(
+    path: &str,
+    footprint_dir: &Path,
+    model_base_variable: &str,
+) -> Result<PathBuf, String> {
+    let re_variable = Regex::new(r"^\$(?:\{(\w+)\}|\((\w+)\)|(\w+))[/\\]?(.*)$").unwrap();
+    let Some(captures) = re_variable.captures(path) else {
+        let path = expand_path(path);
+        return Ok(if path.is_relative() {
+            footprint_dir.join(path)
+        } else {
+            path
+        });
+    };
+    let name = (1..=3).find_map(|idx| captures.get(idx)).unwrap().as_str();
+    let rest = &captures[4];
+    let base = model_base_variable
+        .trim_start_matches('$')
+        .trim_matches(['{', '}', '(', ')']);
+    if !base.is_empty() && name == base {
+        return Ok(footprint_dir.join(rest));
+    }
+    match std::env::var_os(name) {
+        Some(value) => Ok(PathBuf::from(value).join(rest)),
+        // KiCad sets it to the project the library is in
+        None if name == PROJECT_VARIABLE => project_dir_of(footprint_dir)
+            .map(|dir| dir.join(rest))
+            .ok_or(name.to_owned()),
+        None => Err(name.to_owned()),
+    }
+}
+
+/// The path to write into a footprint for a model file, as JLC2KiCadLib would.
+fn model_path(model: &Path, paths: &LibraryPaths, model_base_variable: &str) -> String {
+    let base = model_base_variable
+        .trim_start_matches('$')
+        .trim_matches(['{', '}', '(', ')']);
+    match model.strip_prefix(&paths.footprint_dir) {
+        Ok(relative) if !base.is_empty() => format!(
+            "${{{}}}/{}",
+            base,
+            relative.to_string_lossy().replace('\\', "/")
+        ),
+        _ => model.to_string_lossy().into_owned(),
+    }
+}
+
+/// Checks whether a datasheet link can be downloaded.
+fn check_url(url: &str) -> Result<(), String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let request = |method| {
+        client
+            .request(method, url)
+            .header(
+                reqwest::header::USER_AGENT,
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:134.0) Gecko/20100101 Firefox/134.0",
+            )
+            .send()
+            .map_err(|e| e.to_string())
+    };
+    let mut response = request(reqwest::Method::HEAD)?;
+    // not every server answers HEAD requests
+    if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED
+        || response.status() == reqwest::StatusCode::FORBIDDEN
+    {
+        response = request(reqwest::Method::GET)?;
+    }
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(response.status().to_string())
+    }
+}
+
+fn comparable(name: &str) -> String {
+    name.to_lowercase().replace(['_', '-', ' '], "")
+}
+
+fn unreadable_files(report: &mut AuditReport, paths: &LibraryPaths) {
+    for library in files_with_extension(&paths.symbol_dir, &["kicad_sym"]) {
+        if let Err(e) = read_symbols(&library) {
+            report
+                .push(
+                    Severity::Error,
+                    &library.display().to_string(),
+                    format!("library cannot be read: {}", e),
+                )
+                .suggestion = Some("repair the file or restore it from a backup".to_owned());
+        }
+    }
+    for footprint in files_with_extension(&paths.footprint_dir, &["kicad_mod"]) {
+        if let Err(e) = read_footprint(&footprint) {
+            report
+                .push(
+                    Severity::Error,
+                    &footprint.display().to_string(),
+                    format!("footprint cannot be read: {}", e),
+                )
+                .suggestion = Some("delete it and regenerate the parts using it".to_owned());
+        }
+    }
+}
+
+fn check_symbols(
+    report: &mut AuditReport,
+    entries: &[LibraryEntry],
+    footprints: &[&Footprint],
+    paths: &LibraryPaths,
+    check_urls: bool,
+) {
+    let regenerate = |code: Option<&str>| match code {
+        Some(code) => format!("regenerate {} from the Library Browser", code),
+        None => "regenerate the part from the Library Browser".to_owned(),
+    };
+    for entry in entries {
+        let Some(symbol) = &entry.symbol else {
+            continue;
+        };
+        let library = symbol
+            .library
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let subject = format!("{}/{}", library, symbol.name);
+
+        match footprint_name(symbol) {
+            None => {
+                report
+                    .push(
+                        Severity::Info,
+                        &subject,
+                        "symbol has no footprint".to_owned(),
+                    )
+                    .suggestion = Some("set the Footprint property in KiCad".to_owned());
+            }
+            Some(name) if entry.footprint.is_none() => {
+                let candidates: Vec<&&Footprint> = footprints
+                    .iter()
+                    .filter(|footprint| comparable(&footprint.name) == comparable(name))
+                    .collect();
+                let finding = report.push(
+                    Severity::Error,
+                    &subject,
+                    format!("footprint \"{}\" does not exist", name),
+                );
+                match candidates[..] {
+                    [footprint] => {
+                        let property = &symbol.properties["Footprint"];
+                        let prefix = &property[..property.len() - name.len()];
+                        finding.fix = Some(Fix::Footprint {
+                            library: symbol.library.clone(),
+                            symbol: symbol.name.clone(),
+                            footprint: format!("{}{}", prefix, footprint.name),
+                        });
+                    }
+                    _ => finding.suggestion = Some(regenerate(symbol.lcsc_code())),
+                }
+            }
+            Some(_) => {}
+        }
+
+        let local_fix = |datasheet: &Path| Fix::Datasheet {
+            library: symbol.library.clone(),
+            symbol: symbol.name.clone(),
+            datasheet: datasheet.to_string_lossy().into_owned(),
+        };
+        match symbol.properties.get("Datasheet").map(String::as_str) {
+            None | Some("~") => {
+                if let Some(datasheet) = &entry.datasheet {
+                    report
+                        .push(
+                            Severity::Info,
+                            &subject,
+                            "datasheet was downloaded but is not linked".to_owned(),
+                        )
+                        .fix = Some(local_fix(datasheet));
+                }
+            }
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                if !check_urls {
+                    continue;
+                }
+                if let Err(e) = check_url(url) {
+                    let finding = report.push(
+                        Severity::Warning,
+                        &subject,
+                        format!("datasheet link {} is broken: {}", url, e),
+                    );
+                    match &entry.datasheet {
+                        Some(datasheet) => finding.fix = Some(local_fix(datasheet)),
+                        None => {
+                            finding.suggestion = Some(format!(
+                                "{} to download the datasheet again",
+                                regenerate(symbol.lcsc_code())
+                            ))
+                        }
+                    }
+                }
+            }
+            Some(link) => {
+                let path = expand_path(link.strip_prefix("file://").unwrap_or(link));
+                let path = if path.is_relative() {
+                    paths.symbol_dir.join(path)
+                } else {
+                    path
+                };
+                if !path.exists() {
+                    let finding = report.push(
+                        Severity::Warning,
+                        &subject,
+                        format!("datasheet {} does not exist", link),
+                    );
+                    match &entry.datasheet {
+                        Some(datasheet) => finding.fix = Some(local_fix(datasheet)),
+                        None => {
+                            finding.suggestion = Some(format!(
+                                "{} to download the datasheet again",
+                                regenerate(symbol.lcsc_code())
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn check_models(
+    report: &mut AuditReport,
+    footprints: &[&Footprint],
+    paths: &LibraryPaths,
+    model_base_variable: &str,
+) {
+    let models = files_with_extension(&paths.model_dir, &["wrl", "step", "stp"]);
+    for footprint in footprints {
+        for model in &footprint.models {
+            let subject = format!("footprint {}", footprint.name);
+            match resolve_model_path(model, &paths.footprint_dir, model_base_variable) {
+                Err(variable) => {
+                    report
+                        .push(
+                            Severity::Info,
+                            &subject,
+                            format!(
+                                "model {} uses ${{{}}}, which is unknown here",
+                                model, variable
+                            ),
+                        )
+                        .suggestion = Some(
+                        "set the base path variable in the settings, or check it from KiCad"
+                            .to_owned(),
+                    );
+                }
+                Ok(resolved) if !resolved.exists() => {
+                    let finding = report.push(
+                        Severity::Warning,
+                        &subject,
+                        format!("model {} does not exist", model),
+                    );
+                    let name = model.rsplit(['/', '\\']).next().unwrap_or(model);
+                    let found = models.iter().find(|path| {
+                        path.file_name()
+                            .is_some_and(|file| file.to_string_lossy().eq_ignore_ascii_case(name))
+                    });
+                    match found {
+                        Some(found) => {
+                            finding.fix = Some(Fix::Model {
+                                footprint: footprint.path.clone(),
+                                old: model.clone(),
+                                new: model_path(found, paths, model_base_variable),
+                            })
+                        }
+                        None => finding.suggestion = Some(
+                            "regenerate the parts using this footprint from the Library Browser"
+                                .to_owned(),
+                        ),
+                    }
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+fn check_orphans(report: &mut AuditReport, entries: &[LibraryEntry]) {
+    for entry in entries.iter().filter(|entry| entry.symbol.is_none()) {
+        let (what, subject) = match &entry.footprint {
+            Some(footprint) => ("footprint", footprint.name.clone()),
+            None if !entry.models.is_empty() => ("model", entry.title()),
+            None => ("datasheet", entry.title()),
+        };
+        report
+            .push(
+                Severity::Info,
+                &subject,
+                format!("{} is not used by any symbol", what),
+            )
+            .suggestion = Some("delete it from the Library Browser".to_owned());
+    }
+}
+
+/// Checks the whole library for dangling links: symbols pointing to missing footprints,
+/// footprints pointing to missing models and broken datasheet links. Datasheet links on
+/// the web are only checked if `check_urls` is set, as that takes a while.
+pub fn audit(paths: &LibraryPaths, model_base_variable: &str, check_urls: bool) -> AuditReport {
+    let mut report = AuditReport::default();
+    unreadable_files(&mut report, paths);
+    let entries = scan_library(paths);
+    let mut footprints: Vec<&Footprint> = vec![];
+    for footprint in entries.iter().filter_map(|entry| entry.footprint.as_ref()) {
+        if !footprints.iter().any(|other| other.path == footprint.path) {
+            footprints.push(footprint);
+        }
+    }
+    check_symbols(&mut report, &entries, &footprints, paths, check_urls);
+    check_models(&mut report, &footprints, paths, model_base_variable);
+    check_orphans(&mut report, &entries);
+    report.findings.sort_by_key(|finding| finding.severity);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, write};
+
+    use super::*;
+
+    const LIBRARY: &str = r#"(kicad_symbol_lib (version 20210201) (generator TousstNicolas/JLC2KiCad_lib)
+  (symbol "0402WGF1001TCE" (in_bom yes) (on_board yes)
+    (property "Footprint" "footprint:r0402" (id 2) (at 0 -7.62 0))
+    (property "Datasheet" "" (id 3) (at 0 -10.16 0))
+    (property "LCSC" "C11702" (id 5) (at 0 -12.7 0))
+  )
+  (symbol "CL05A106MQ5NUNC"
+    (property "Footprint" "footprint:C0402" (id 2) (at 0 -7.62 0))
+    (property "Datasheet" "~/missing.pdf" (id 3) (at 0 -10.16 0))
+    (property "LCSC" "C15525" (id 5) (at 0 -12.7 0))
+  )
+)
+"#;
+
+    const FOOTPRINT: &str = r#"(module R0402 (layer F.Cu) (tedit 5DD50112)
+  (model "${EASYEDA}/3d/R0402.wrl"
+    (offset (xyz 0 0 0)) (scale (xyz 1 1 1)) (rotate (xyz 0 0 0)))
+)
+"#;
+
+    #[test]
+    fn finds_and_fixes_dangling_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = LibraryPaths {
+            symbol_dir: dir.path().join("symbol"),
+            footprint_dir: dir.path().join("footprint"),
+            model_dir: dir.path().join("footprint/packages3d"),
+            datasheet_dir: dir.path().join("datasheets"),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        create_dir_all(&paths.model_dir).unwrap();
+        create_dir_all(&paths.datasheet_dir).unwrap();
+        let library = paths.symbol_dir.join("default_lib.kicad_sym");
+        write(&library, LIBRARY).unwrap();
+        write(paths.footprint_dir.join("R0402.kicad_mod"), FOOTPRINT).unwrap();
+        write(paths.model_dir.join("R0402.wrl"), "").unwrap();
+        let datasheet = paths
+            .datasheet_dir
+            .join("UNI-ROYAL-0402WGF1001TCE_C11702.pdf");
+        write(&datasheet, "").unwrap();
+
+        let mut report = audit(&paths, "$EASYEDA", false);
+        let problems: Vec<(Severity, &str)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.severity, finding.problem.as_str()))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (Severity::Error, "footprint \"r0402\" does not exist"),
+                (Severity::Error, "footprint \"C0402\" does not exist"),
+                (Severity::Warning, "datasheet ~/missing.pdf does not exist"),
+                (
+                    Severity::Warning,
+                    "model ${EASYEDA}/3d/R0402.wrl does not exist"
+                ),
+                (Severity::Info, "datasheet was downloaded but is not linked"),
+                (Severity::Info, "footprint is not used by any symbol"),
+            ]
+        );
+        assert_eq!(report.fixable(), 3);
+        assert!(
+            report.findings[1]
+                .suggestion
+                .as_ref()
+                .unwrap()
+                .contains("C15525")
+        );
+        assert_eq!(report.apply_fixes().unwrap(), 3);
+        assert_eq!(report.count(Severity::Error), 1);
+        assert_eq!(report.count(Severity::Warning), 1);
+
+        let text = read_to_string(&library).unwrap();
+        assert!(text.contains(r#"(property "Footprint" "footprint:R0402""#));
+        assert!(text.contains(&format!(
+            "(property \"Datasheet\" \"{}\"",
+            datasheet.display()
+        )));
+        let footprint = read_to_string(paths.footprint_dir.join("R0402.kicad_mod")).unwrap();
+        assert!(footprint.contains(r#"(model "${EASYEDA}/packages3d/R0402.wrl""#));
+        assert_eq!(audit(&paths, "$EASYEDA", false).fixable(), 0);
+    }
+
+    #[test]
+    fn resolves_model_paths() {
+        let dir = Path::new("/libs/footprint");
+        assert_eq!(
+            resolve_model_path("${KIPRJMOD}/packages3d/R0402.wrl", dir, "$KIPRJMOD"),
+            Ok(PathBuf::from("/libs/footprint/packages3d/R0402.wrl"))
+        );
+        assert_eq!(
+            resolve_model_path("$(E2K_UNKNOWN_VARIABLE)/R0402.wrl", dir, "$KIPRJMOD"),
+            Err("E2K_UNKNOWN_VARIABLE".to_owned())
+        );
+        assert_eq!(
+            resolve_model_path("packages3d/R0402.wrl", dir, ""),
+            Ok(PathBuf::from("/libs/footprint/packages3d/R0402.wrl"))
+        );
+    }
+}
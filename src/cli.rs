@@ -0,0 +1,288 @@
+//! Headless commands, e.g. for running from cron or CI.
+
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    MyApp,
+    audit::audit,
+    config::{portable_path, set_config_path},
+    library_manager::normalize_libraries,
+    lock::{LibraryLock, lock},
+    manifest::{LOCK_FILE, verify},
+    part_id::parse_part_id,
+    stock_history::{StockHistory, format_date},
+    watchlist::{Watchlist, lookup_all},
+};
+
+const USAGE: &str = "\
+Usage: easyeda_to_kicad_lib_ui [--config <FILE> | --portable] [COMMAND]
+
+Without a command the graphical user interface is started.
+
+Options:
+  --config <FILE>           read and write the settings in FILE instead of
+                            ~/.config/EasyEDA_to_KiCAD_Lib_UI/config.toml (also E2K_CONFIG)
+  --portable                keep the settings in easyeda_to_kicad_lib_ui.toml next to the executable,
+                            which is also used without this option once it exists
+
+Any setting of the config file can be overridden with an E2K_ variable, e.g.
+E2K_OUTPUT_PATH=/ci/library or E2K_GIT_COMMIT=false, without being written back.
+
+Commands:
+  watch                     refresh the watchlist and print alerts (exit code 1 if there are any)
+  watch list                show the watched parts
+  watch add <PART> [MIN]    watch a part, alerting when stock drops below MIN (default 100)
+  watch remove <PART>       stop watching a part
+  audit [--fix] [--online]  check the library for dangling links (exit code 1 if there are any),
+                            --fix applies the safe fixes, --online also checks datasheet links
+  normalize                 sort and canonically format the symbol libraries, e.g. before a commit
+  sync                      import the parts of parts.toml missing from the library and write parts.lock
+                            (exit code 1 if a part failed or the library has parts not in parts.toml)
+  verify                    compare the library with the hashes of parts.lock without writing anything
+                            (exit code 1 on any difference, e.g. for CI)
+  help                      show this help";
+
+/// Runs the command given on the command line, if any. Returns the exit code of the
+/// command, or `None` if the GUI should be started instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let mut args = args.to_vec();
+    while let Some(option) = args.get(1) {
+        match option.as_str() {
+            "--config" => {
+                let Some(path) = args.get(2) else {
+                    eprintln!("--config needs a file\n\n{}", USAGE);
+                    return Some(2);
+                };
+                set_config_path(PathBuf::from(path));
+                args.drain(1..3);
+            }
+            "--portable" => {
+                if let Some(path) = portable_path() {
+                    set_config_path(path);
+                }
+                args.remove(1);
+            }
+            _ => break,
+        }
+    }
+    let command = args.get(1)?;
+    // macOS passes a process serial number when started from the Finder
+    if command.starts_with("-psn_") {
+        return None;
+    }
+    let rest = &args[2..];
+    Some(match command.as_str() {
+        "watch" => watch(rest),
+        "audit" => audit_library(rest),
+        "normalize" => {
+            let Some(app) = load_settings() else {
+                return Some(2);
+            };
+            let Some(_lock) = lock_library(&app, "normalizing") else {
+                return Some(2);
+            };
+            match normalize_libraries(&app.library_paths()) {
+                Ok(changed) => {
+                    println!("{} libraries normalized", changed);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Could not normalize: {}", e);
+                    2
+                }
+            }
+        }
+        "sync" => {
+            let Some(app) = load_settings() else {
+                return Some(2);
+            };
+            match app.sync_job()() {
+                Ok(report) => {
+                    println!("{}", report);
+                    if report.has_problems() { 1 } else { 0 }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        "verify" => {
+            let Some(app) = load_settings() else {
+                return Some(2);
+            };
+            match verify(&app.output_dir(), &app.library_paths()) {
+                Ok(drift) => {
+                    for drift in &drift {
+                        println!("{}", drift);
+                    }
+                    if drift.is_empty() {
+                        println!("The library matches {}", LOCK_FILE);
+                        0
+                    } else {
+                        1
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => {
+            eprintln!("Unknown command: {}\n\n{}", command, USAGE);
+            2
+        }
+    })
+}
+
+fn watch(args: &[String]) -> i32 {
+    let mut watchlist = Watchlist::load_default();
+    match args.first().map(String::as_str) {
+        None => {
+            let mut history = StockHistory::load_default();
+            let now = jiff::Timestamp::now().as_second();
+            let results = lookup_all(&watchlist.codes());
+            for (code, result) in &results {
+                match result {
+                    Ok(part) => {
+                        history.record(part);
+                        println!(
+                            "{}: {} in stock",
+                            code,
+                            part.get("Stock").map(String::as_str).unwrap_or("?")
+                        );
+                    }
+                    Err(e) => println!("{}: {}", code, e),
+                }
+            }
+            if let Err(e) = history.save() {
+                eprintln!("Could not save stock history: {}", e);
+            }
+            let alerts = watchlist.apply(&results, now);
+            if let Err(e) = watchlist.save() {
+                eprintln!("Could not save watchlist: {}", e);
+                return 2;
+            }
+            for alert in &alerts {
+                println!("ALERT {}", alert);
+            }
+            if alerts.is_empty() { 0 } else { 1 }
+        }
+        Some("list") => {
+            for part in &watchlist.parts {
+                println!(
+                    "{}\tmin {}\tstock {}\t{}\tchecked {}",
+                    part.code,
+                    part.threshold,
+                    part.last_stock
+                        .map(|stock| stock.to_string())
+                        .unwrap_or("?".to_owned()),
+                    part.last_type.as_deref().unwrap_or("?"),
+                    part.last_checked
+                        .map(|time| format_date(time, true))
+                        .unwrap_or("never".to_owned()),
+                );
+            }
+            0
+        }
+        Some("add") => {
+            let Some(code) = args.get(1) else {
+                eprintln!("{}", USAGE);
+                return 2;
+            };
+            let Some(part_id) = parse_part_id(code) else {
+                eprintln!("Not a valid LCSC number or part URL: {}", code);
+                return 2;
+            };
+            let threshold = match args.get(2).map(|min| min.parse()) {
+                None => 100,
+                Some(Ok(min)) => min,
+                Some(Err(_)) => {
+                    eprintln!("Not a valid minimum stock: {}", args[2]);
+                    return 2;
+                }
+            };
+            watchlist.add(&part_id.lcsc_number, threshold);
+            save_watchlist(&mut watchlist)
+        }
+        Some("remove") => {
+            let Some(code) = args.get(1) else {
+                eprintln!("{}", USAGE);
+                return 2;
+            };
+            // invalid entries from before `watch add` checked them can be removed too
+            let code = parse_part_id(code)
+                .map(|part_id| part_id.lcsc_number)
+                .unwrap_or(code.to_uppercase());
+            watchlist.remove(&code);
+            save_watchlist(&mut watchlist)
+        }
+        Some(other) => {
+            eprintln!("Unknown watch command: {}\n\n{}", other, USAGE);
+            2
+        }
+    }
+}
+
+fn load_settings() -> Option<MyApp> {
+    MyApp::load_settings()
+        .inspect_err(|e| eprintln!("{}", e))
+        .ok()
+}
+
+/// Waits a while for another instance writing to the library, as commands may run unattended.
+fn lock_library(app: &MyApp, action: &str) -> Option<LibraryLock> {
+    match lock(&app.output_dir(), action, Duration::from_secs(60)) {
+        Ok(lock) => Some(lock),
+        Err(e) => {
+            eprintln!("Gave up {}: {}", action, e);
+            None
+        }
+    }
+}
+
+fn save_watchlist(watchlist: &mut Watchlist) -> i32 {
+    match watchlist.save() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Could not save watchlist: {}", e);
+            2
+        }
+    }
+}
+
+fn audit_library(args: &[String]) -> i32 {
+    let mut fix = false;
+    let mut online = false;
+    for arg in args {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            "--online" => online = true,
+            _ => {
+                eprintln!("Unknown option: {}\n\n{}", arg, USAGE);
+                return 2;
+            }
+        }
+    }
+    let Some(app) = load_settings() else {
+        return 2;
+    };
+    let mut report = audit(&app.library_paths(), app.model_base_variable(), online);
+    if fix {
+        let Some(_lock) = lock_library(&app, "fixing") else {
+            return 2;
+        };
+        if let Err(e) = report.apply_fixes() {
+            eprintln!("Could not apply fix: {}", e);
+            println!("{}", report);
+            return 2;
+        }
+    }
+    println!("{}", report);
+    if report.has_problems() { 1 } else { 0 }
+}
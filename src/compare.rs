@@ -0,0 +1,158 @@
+use indexmap::IndexMap;
+
+/// Rows that come first in a comparison, as these are what usually decides between parts.
+const LEADING_KEYS: [&str; 7] = [
+    "Component Code",
+    "Model",
+    "Brand",
+    "Type",
+    "Specification",
+    "Stock",
+    "Minimum Price",
+];
+
+/// One attribute of all compared parts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareRow {
+    pub key: String,
+    /// one value per part, `None` if a part does not have this attribute
+    pub values: Vec<Option<String>>,
+    pub differs: bool,
+}
+
+/// Lines up the attributes of several looked up parts by key, leaving out the `meta_` entries.
+pub fn compare_rows(parts: &[IndexMap<String, String>]) -> Vec<CompareRow> {
+    let mut keys: Vec<&str> = LEADING_KEYS
+        .iter()
+        .copied()
+        .filter(|key| parts.iter().any(|part| part.contains_key(*key)))
+        .collect();
+    for part in parts {
+        for key in part.keys() {
+            if !key.starts_with("meta_") && !keys.contains(&key.as_str()) {
+                keys.push(key);
+            }
+        }
+    }
+    keys.into_iter()
+        .map(|key| {
+            let values: Vec<Option<String>> =
+                parts.iter().map(|part| part.get(key).cloned()).collect();
+            let differs = values.iter().any(|value| *value != values[0]);
+            CompareRow {
+                key: key.to_owned(),
+                values,
+                differs,
+            }
+        })
+        .collect()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+pub fn to_csv(rows: &[CompareRow]) -> String {
+    let mut csv = String::new();
+    for row in rows {
+        let mut fields = vec![csv_field(&row.key)];
+        fields.extend(
+            row.values
+                .iter()
+                .map(|value| csv_field(value.as_deref().unwrap_or_default())),
+        );
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Renders the comparison as a Markdown table, differing rows are set in bold.
+pub fn to_markdown(rows: &[CompareRow]) -> String {
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+    let mut markdown = String::new();
+    let Some(header) = rows.first() else {
+        return markdown;
+    };
+    // the first row holds the part codes, which make for a nice table header
+    markdown.push_str(&format!("| {} |", cell(&header.key)));
+    for value in &header.values {
+        markdown.push_str(&format!(
+            " {} |",
+            cell(value.as_deref().unwrap_or_default())
+        ));
+    }
+    markdown.push_str("\n|---|");
+    markdown.push_str(&"---|".repeat(header.values.len()));
+    markdown.push('\n');
+    for row in &rows[1..] {
+        let emphasis = if row.differs { "**" } else { "" };
+        markdown.push_str(&format!("| {}{}{} |", emphasis, cell(&row.key), emphasis));
+        for value in &row.values {
+            markdown.push_str(&format!(
+                " {} |",
+                value.as_deref().map(cell).unwrap_or("–".to_owned())
+            ));
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    #[test]
+    fn lines_up_parts() {
+        let parts = vec![
+            indexmap! {
+                "Component Code".to_owned() => "C6186".to_owned(),
+                "Output Voltage".to_owned() => "3.3V".to_owned(),
+                "Specification".to_owned() => "SOT-223".to_owned(),
+                "meta_image0".to_owned() => "https://example.com".to_owned(),
+            },
+            indexmap! {
+                "Component Code".to_owned() => "C347222".to_owned(),
+                "Specification".to_owned() => "SOT-223".to_owned(),
+                "Dropout Voltage".to_owned() => "1.1V, at 800mA".to_owned(),
+            },
+        ];
+        let rows = compare_rows(&parts);
+        let keys: Vec<&str> = rows.iter().map(|row| row.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "Component Code",
+                "Specification",
+                "Output Voltage",
+                "Dropout Voltage"
+            ]
+        );
+        assert!(rows[0].differs);
+        assert!(!rows[1].differs);
+        assert_eq!(
+            rows[3].values,
+            vec![None, Some("1.1V, at 800mA".to_owned())]
+        );
+
+        assert_eq!(
+            to_csv(&rows),
+            "Component Code,C6186,C347222\r\nSpecification,SOT-223,SOT-223\r\n\
+             Output Voltage,3.3V,\r\nDropout Voltage,,\"1.1V, at 800mA\"\r\n"
+        );
+        assert_eq!(
+            to_markdown(&rows),
+            "| Component Code | C6186 | C347222 |\n|---|---|---|\n\
+             | Specification | SOT-223 | SOT-223 |\n\
+             | **Output Voltage** | 3.3V | – |\n\
+             | **Dropout Voltage** | – | 1.1V, at 800mA |\n"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    env,
+    fs::{create_dir_all, read_to_string},
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use indexmap::IndexMap;
+use toml::{Table, Value};
+
+use crate::{
+    lock::write_atomic,
+    profile::{DEFAULT_PROFILE, Profile},
+};
+
+/// Prefix of environment variables overriding settings, e.g. `E2K_OUTPUT_PATH`.
+pub const ENV_PREFIX: &str = "E2K_";
+/// Name of the config file in the config directory.
+const CONFIG_FILE: &str = "config.toml";
+/// Name of the config file next to the executable, which makes the app portable.
+pub const PORTABLE_FILE: &str = "easyeda_to_kicad_lib_ui.toml";
+
+/// Set from `--config` or `--portable` on the command line.
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// The settings kept in the config file, which can be versioned or provisioned. Window
+/// state like open tabs stays in the app state.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub exe_path: String,
+    pub normalize_library: bool,
+    pub git_commit: bool,
+    pub git_pull: bool,
+    pub git_push: bool,
+    pub record_scanned_stock: bool,
+    /// the active profile
+    pub profile: String,
+    pub profiles: IndexMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            exe_path: "JLC2KiCadLib".to_owned(),
+            normalize_library: true,
+            git_commit: false,
+            git_pull: false,
+            git_push: false,
+            record_scanned_stock: false,
+            profile: DEFAULT_PROFILE.to_owned(),
+            profiles: IndexMap::new(),
+        }
+    }
+}
+
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+/// The config file next to the executable.
+pub fn portable_path() -> Option<PathBuf> {
+    Some(env::current_exe().ok()?.parent()?.join(PORTABLE_FILE))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let absolute = |dir: String| Some(PathBuf::from(dir)).filter(|dir| dir.is_absolute());
+    env::var("XDG_CONFIG_HOME")
+        .ok()
+        .and_then(absolute)
+        .or_else(|| env::var("APPDATA").ok().and_then(absolute))
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })
+}
+
+/// Where the config is read from and written to: the path given on the command line or
+/// in `E2K_CONFIG`, a config file next to the executable, or the XDG config directory.
+pub fn config_path() -> Option<PathBuf> {
+    CONFIG_PATH
+        .get()
+        .cloned()
+        .or_else(|| env::var_os(format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from))
+        .or_else(|| portable_path().filter(|path| path.is_file()))
+        .or_else(|| Some(config_dir()?.join(crate::APP_ID).join(CONFIG_FILE)))
+}
+
+/// A setting overridden from the environment, with the value it had before, which is what
+/// gets written back to the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub variable: String,
+    path: Vec<String>,
+    previous: Option<Value>,
+}
+
+fn lookup<'a>(table: &'a Table, path: &[String]) -> Option<&'a Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn insert(table: &mut Table, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        let Value::Table(inner) = entry else {
+            return;
+        };
+        table = inner;
+    }
+    table.insert(last.clone(), value);
+}
+
+fn to_table(config: &Config) -> Result<Table, String> {
+    Table::try_from(config).map_err(|e| e.to_string())
+}
+
+/// The settings of `base`, which come from the app state, changed by the config file and
+/// then by `E2K_*` variables of `env`.
+pub fn resolve(
+    base: &Config,
+    file: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(Config, Vec<Override>), String> {
+    let mut table = to_table(base)?;
+    if let Some(text) = file {
+        // the file decides per top level setting, so profiles deleted there stay deleted
+        table.extend(text.parse::<Table>().map_err(|e| e.to_string())?);
+    }
+    let profile_keys: Vec<String> = to_table(&Config {
+        profiles: IndexMap::from([(String::new(), Profile::default())]),
+        ..Default::default()
+    })?
+    .get("profiles")
+    .and_then(Value::as_table)
+    .and_then(|profiles| profiles.get(""))
+    .and_then(Value::as_table)
+    .map(|profile| profile.keys().cloned().collect())
+    .unwrap_or_default();
+
+    let mut overrides = vec![];
+    let top_level: Vec<String> = table
+        .keys()
+        .filter(|key| *key != "profiles")
+        .cloned()
+        .collect();
+    // top level first, as E2K_PROFILE picks the profile the others apply to
+    for key in top_level.iter().chain(&profile_keys) {
+        let variable = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+        let Some(value) = env(&variable) else {
+            continue;
+        };
+        let path = if top_level.contains(key) {
+            vec![key.clone()]
+        } else {
+            let profile = table
+                .get("profile")
+                .and_then(Value::as_str)
+                .unwrap_or(DEFAULT_PROFILE)
+                .to_owned();
+            vec!["profiles".to_owned(), profile, key.clone()]
+        };
+        let previous = lookup(&table, &path).cloned();
+        let value = match (&previous, key.as_str()) {
+            (Some(Value::Boolean(_)), _)
+            | (None, "download_datasheet" | "skip_existing" | "no_footprint" | "no_symbol") => {
+                match value.to_lowercase().as_str() {
+                    "1" | "true" | "yes" | "on" => Value::Boolean(true),
+                    "0" | "false" | "no" | "off" | "" => Value::Boolean(false),
+                    _ => {
+                        return Err(format!(
+                            "{} must be true or false, not \"{}\"",
+                            variable, value
+                        ));
+                    }
+                }
+            }
+            _ => Value::String(value),
+        };
+        insert(&mut table, &path, value);
+        overrides.push(Override {
+            variable,
+            path,
+            previous,
+        });
+    }
+    let config = Config::deserialize_table(table)?;
+    Ok((config, overrides))
+}
+
+impl Config {
+    fn deserialize_table(table: Table) -> Result<Self, String> {
+        Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    /// The text of the config file, with overridden settings as they were before.
+    pub fn to_file_text(&self, overrides: &[Override]) -> Result<String, String> {
+        let mut table = to_table(self)?;
+        for Override { path, previous, .. } in overrides {
+            match previous {
+                Some(previous) => insert(&mut table, path, previous.clone()),
+                None => {
+                    if let Some((last, parents)) = path.split_last() {
+                        let mut parent = Some(&mut table);
+                        for key in parents {
+                            parent = parent
+                                .and_then(|table| table.get_mut(key))
+                                .and_then(Value::as_table_mut);
+                        }
+                        if let Some(parent) = parent {
+                            parent.remove(last);
+                        }
+                    }
+                }
+            }
+        }
+        toml::to_string_pretty(&table).map_err(|e| e.to_string())
+    }
+}
+
+/// Reads the config file, `None` if there is none yet.
+pub fn read_config(path: &Path) -> io::Result<Option<String>> {
+    match read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_config(path: &Path, text: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        create_dir_all(dir)?;
+    }
+    write_atomic(path, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_settings() {
+        let base = Config {
+            profiles: IndexMap::from([
+                (DEFAULT_PROFILE.to_owned(), Profile::default()),
+                ("ACME".to_owned(), Profile::default()),
+            ]),
+            ..Default::default()
+        };
+        let file = "exe_path = \"/opt/jlc/JLC2KiCadLib\"\nprofile = \"ACME\"\n\n\
+                    [profiles.ACME]\noutput_path = \"~/acme\"\n";
+        let env = |variable: &str| match variable {
+            "E2K_OUTPUT_PATH" => Some("/ci/library".to_owned()),
+            "E2K_NO_FOOTPRINT" => Some("1".to_owned()),
+            "E2K_GIT_COMMIT" => Some("yes".to_owned()),
+            _ => None,
+        };
+        let (config, overrides) = resolve(&base, Some(file), env).unwrap();
+        assert_eq!(config.exe_path, "/opt/jlc/JLC2KiCadLib");
+        assert!(config.git_commit);
+        // the file lists only one profile, the others are gone
+        assert_eq!(config.profiles.keys().collect::<Vec<_>>(), vec!["ACME"]);
+        let acme = &config.profiles["ACME"];
+        assert_eq!(acme.output_path, "/ci/library");
+        assert!(acme.no_footprint);
+        assert_eq!(acme.symbol_lib, "default_lib");
+        assert_eq!(overrides.len(), 3);
+
+        // overridden settings are written back as they were
+        let text = config.to_file_text(&overrides).unwrap();
+        let (written, _) = resolve(&Config::default(), Some(&text), |_| None).unwrap();
+        assert!(!written.git_commit);
+        assert_eq!(written.profiles["ACME"].output_path, "~/acme");
+        assert!(!written.profiles["ACME"].no_footprint);
+        assert_eq!(written.exe_path, "/opt/jlc/JLC2KiCadLib");
+
+        assert!(
+            resolve(&base, None, |variable| (variable == "E2K_GIT_PUSH")
+                .then(|| "maybe".to_owned()))
+            .is_err()
+        );
+        assert!(resolve(&base, Some("profile = ["), |_| None).is_err());
+    }
+}
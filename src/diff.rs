@@ -0,0 +1,148 @@
+/// Largest table of the line diff, in cells. Beyond it the differing middle of the texts is
+/// shown as removed and added as a whole instead of aligning it line by line.
+const MAX_CELLS: usize = 4_000_000;
+
+/// One line of a diff between two texts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineChange {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Line diff by longest common subsequence of what lies between the common start and end
+/// of the texts. The table is quadratic, so when that middle has more than [`MAX_CELLS`]
+/// line pairs it is not aligned but replaced as a whole.
+pub fn diff_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let same = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|line| LineChange::Same(line.to_string()))
+            .collect::<Vec<_>>()
+    };
+    let mut changes = same(&old[..prefix]);
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_CELLS {
+        changes.extend(
+            old_middle
+                .iter()
+                .map(|line| LineChange::Removed(line.to_string())),
+        );
+        changes.extend(
+            new_middle
+                .iter()
+                .map(|line| LineChange::Added(line.to_string())),
+        );
+    } else {
+        changes.extend(align(old_middle, new_middle));
+    }
+    changes.extend(same(&old[old.len() - suffix..]));
+    changes
+}
+
+fn align(old: &[&str], new: &[&str]) -> Vec<LineChange> {
+    // common[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut changes = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.push(LineChange::Same(old[i].to_owned()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            // removals first, so a changed line reads as - then +
+            changes.push(LineChange::Removed(old[i].to_owned()));
+            i += 1;
+        } else {
+            changes.push(LineChange::Added(new[j].to_owned()));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Leaves out unchanged lines further than `context` lines away from a change, `None`
+/// marks where lines were left out.
+pub fn with_context(changes: &[LineChange], context: usize) -> Vec<Option<&LineChange>> {
+    // index of the next change at or after each line
+    let mut next = vec![None; changes.len()];
+    let mut following = None;
+    for (idx, change) in changes.iter().enumerate().rev() {
+        if !matches!(change, LineChange::Same(_)) {
+            following = Some(idx);
+        }
+        next[idx] = following;
+    }
+    let mut shown = vec![];
+    let mut previous: Option<usize> = None;
+    for (idx, change) in changes.iter().enumerate() {
+        if !matches!(change, LineChange::Same(_)) {
+            previous = Some(idx);
+        }
+        let near = previous.is_some_and(|other| idx - other <= context)
+            || next[idx].is_some_and(|other| other - idx <= context);
+        if near {
+            shown.push(Some(change));
+        } else if shown.last() != Some(&None) {
+            shown.push(None);
+        }
+    }
+    shown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_lines() {
+        let changes = diff_lines("(a\n  (b 1)\n  (c 2))", "(a\n  (b 1)\n  (c 3)\n  (d 4))");
+        assert_eq!(
+            changes,
+            vec![
+                LineChange::Same("(a".into()),
+                LineChange::Same("  (b 1)".into()),
+                LineChange::Removed("  (c 2))".into()),
+                LineChange::Added("  (c 3)".into()),
+                LineChange::Added("  (d 4))".into()),
+            ]
+        );
+        let shown = with_context(&changes, 1);
+        assert_eq!(shown[0], None);
+        assert_eq!(shown[1], Some(&LineChange::Same("  (b 1)".into())));
+        assert_eq!(shown.len(), 5);
+        assert!(
+            diff_lines("x\ny", "x\ny")
+                .iter()
+                .all(|change| matches!(change, LineChange::Same(_)))
+        );
+
+        // too many differing lines to align, they are replaced as a whole
+        let old: String = (0..3000).map(|n| format!("(a {})\n", n)).collect();
+        let new: String = (0..3000).map(|n| format!("(b {})\n", n)).collect();
+        let changes = diff_lines(&format!("(x\n{}", old), &format!("(x\n{}", new));
+        assert_eq!(changes.len(), 6001);
+        assert_eq!(changes[0], LineChange::Same("(x".into()));
+        assert_eq!(changes[3001], LineChange::Added("(b 0)".into()));
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use subprocess::{Exec, Redirection};
+
+/// Where the repository of a shared library stands, from `git status`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoStatus {
+    pub root: PathBuf,
+    pub branch: String,
+    /// changed and untracked files
+    pub changed: usize,
+    /// commits not pushed and not pulled yet, if there is an upstream branch
+    pub ahead: u32,
+    pub behind: u32,
+    pub upstream: Option<String>,
+}
+
+impl RepoStatus {
+    /// Short summary for the status area, e.g. "main ↑1 ↓2, 3 changed".
+    pub fn summary(&self) -> String {
+        let mut summary = self.branch.clone();
+        if self.ahead > 0 {
+            summary.push_str(&format!(" ↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            summary.push_str(&format!(" ↓{}", self.behind));
+        }
+        if self.changed > 0 {
+            summary.push_str(&format!(", {} changed", self.changed));
+        }
+        summary
+    }
+}
+
+/// Runs git in `dir`, returning its output or, if it failed, its error message.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Exec::cmd("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Merge)
+        .capture()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if output.success() {
+        Ok(output.stdout_str())
+    } else {
+        Err(output.stdout_str().trim().to_owned())
+    }
+}
+
+/// The root of the git repository `dir` is in, if any.
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+    if !dir.is_dir() {
+        return None;
+    }
+    git(dir, &["rev-parse", "--show-toplevel"])
+        .ok()
+        .map(|root| PathBuf::from(root.trim()))
+}
+
+/// Reads the output of `git status --porcelain=v2 --branch`.
+pub fn parse_status(root: &Path, output: &str) -> RepoStatus {
+    let mut status = RepoStatus {
+        root: root.to_owned(),
+        ..Default::default()
+    };
+    for line in output.lines() {
+        if let Some(branch) = line.strip_prefix("# branch.head ") {
+            status.branch = branch.to_owned();
+        } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+            status.upstream = Some(upstream.to_owned());
+        } else if let Some(counts) = line.strip_prefix("# branch.ab ") {
+            for count in counts.split_whitespace() {
+                if let Some(ahead) = count.strip_prefix('+') {
+                    status.ahead = ahead.parse().unwrap_or_default();
+                } else if let Some(behind) = count.strip_prefix('-') {
+                    status.behind = behind.parse().unwrap_or_default();
+                }
+            }
+        } else if !line.starts_with('#') && !line.is_empty() {
+            status.changed += 1;
+        }
+    }
+    status
+}
+
+/// The status of the repository `dir` is in, `None` if it is not in one.
+pub fn repo_status(dir: &Path) -> Option<RepoStatus> {
+    let root = repo_root(dir)?;
+    let output = git(&root, &["status", "--porcelain=v2", "--branch"]).ok()?;
+    Some(parse_status(&root, &output))
+}
+
+/// Commit message for a part added to the library, e.g. "Add C11702 0402WGF1001TCE (UNI-ROYAL)".
+pub fn commit_message(verb: &str, code: &str, part: Option<&IndexMap<String, String>>) -> String {
+    let mut message = format!("{} {}", verb, code);
+    if let Some(model) = part.and_then(|part| part.get("Model")) {
+        message.push_str(&format!(" {}", model));
+    }
+    // LCSC brands come with the full name in parentheses, e.g. "UNI-ROYAL(Uniroyal Elec)"
+    if let Some(brand) = part
+        .and_then(|part| part.get("Brand"))
+        .and_then(|brand| brand.split('(').next())
+        .map(str::trim)
+        .filter(|brand| !brand.is_empty())
+    {
+        message.push_str(&format!(" ({})", brand));
+    }
+    message
+}
+
+pub fn pull(root: &Path) -> Result<(), String> {
+    git(root, &["pull", "--ff-only"]).map(|_| ())
+}
+
+pub fn push(root: &Path) -> Result<(), String> {
+    git(root, &["push"]).map(|_| ())
+}
+
+/// Stages and commits just `paths`, leaving alone whatever else is staged or changed in
+/// the repository. Returns `false` if they have no changes, e.g. because the part was
+/// already in the library.
+pub fn commit(root: &Path, paths: &[PathBuf], message: &str) -> Result<bool, String> {
+    let paths: Vec<String> = paths
+        .iter()
+        .filter(|path| path.exists() && path.starts_with(root))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if paths.is_empty() {
+        return Ok(false);
+    }
+    let git_paths = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.extend(paths.iter().map(String::as_str));
+        git(root, &args)
+    };
+    git_paths(&["add", "--"])?;
+    // exits with 1 if one of the paths is staged
+    if git_paths(&["diff", "--cached", "--quiet", "--"]).is_ok() {
+        return Ok(false);
+    }
+    git_paths(&["commit", "-m", message, "--"])?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    #[test]
+    fn reads_status() {
+        let output = "# branch.oid 5d3a1e2\n# branch.head main\n# branch.upstream origin/main\n\
+                      # branch.ab +1 -2\n1 .M N... 100644 100644 100644 3f2a 3f2a symbol/default_lib.kicad_sym\n\
+                      ? footprint/R0402.kicad_mod\n";
+        let status = parse_status(Path::new("/libs"), output);
+        assert_eq!(status.branch, "main");
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!((status.ahead, status.behind, status.changed), (1, 2, 2));
+        assert_eq!(status.summary(), "main ↑1 ↓2, 2 changed");
+    }
+
+    #[test]
+    fn writes_commit_messages() {
+        let part = indexmap! {
+            "Model".to_owned() => "0402WGF1001TCE".to_owned(),
+            "Brand".to_owned() => "UNI-ROYAL(Uniroyal Elec)".to_owned(),
+        };
+        assert_eq!(
+            commit_message("Add", "C11702", Some(&part)),
+            "Add C11702 0402WGF1001TCE (UNI-ROYAL)"
+        );
+        assert_eq!(commit_message("Update", "C11702", None), "Update C11702");
+    }
+
+    #[test]
+    fn commits_only_the_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &["config", "user.name", "Test"],
+            &["config", "user.email", "test@example.com"],
+        ] {
+            git(&root, args).unwrap();
+        }
+        let part = root.join("R0402.kicad_mod");
+        let staged = root.join("notes.txt");
+        let edited = root.join("C0402.kicad_mod");
+        for path in [&part, &staged, &edited] {
+            std::fs::write(path, "x").unwrap();
+        }
+        git(&root, &["add", "notes.txt"]).unwrap();
+
+        assert!(commit(&root, std::slice::from_ref(&part), "Add C11702").unwrap());
+        assert_eq!(
+            git(&root, &["show", "--name-only", "--format="]).unwrap(),
+            "R0402.kicad_mod\n"
+        );
+        // what the user staged or changed stays as it was
+        assert_eq!(
+            git(&root, &["status", "--porcelain"]).unwrap(),
+            "A  notes.txt\n?? C0402.kicad_mod\n"
+        );
+        assert!(!commit(&root, &[part], "Add C11702").unwrap());
+    }
+}
@@ -0,0 +1,103 @@
+/// The contents of the QR code LCSC prints on its reels and bags.
+///
+/// A typical payload looks like
+/// `{pbn:PICK2209150046,on:SO2209151379,pc:C11702,pm:0402WGF1001TCE,qty:100,mc:,cc:1,pdi:67033862,hp:0,wc:JS}`.
+/// Only the fields we actually use are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LcscLabel {
+    /// LCSC product code, e.g. `C11702`
+    pub product_code: String,
+    /// manufacturer part number, e.g. `0402WGF1001TCE`
+    pub manufacturer_part: Option<String>,
+    /// quantity contained in this bag or reel
+    pub quantity: Option<u64>,
+    /// LCSC order number
+    pub order_number: Option<String>,
+}
+
+/// Parses the payload of an LCSC packaging QR code.
+///
+/// Barcode scanners act as keyboards, so depending on the keyboard layout the braces
+/// might get lost or the input might have surrounding whitespace, which is why the
+/// braces are optional. The `pc:` field is mandatory and has to be a valid LCSC number.
+pub fn parse_lcsc_label(input: &str) -> Option<LcscLabel> {
+    let term = input.trim();
+    let term = term.strip_prefix('{').unwrap_or(term);
+    let term = term.strip_suffix('}').unwrap_or(term);
+
+    let mut product_code = None;
+    let mut manufacturer_part = None;
+    let mut quantity = None;
+    let mut order_number = None;
+
+    for field in term.split(',') {
+        // every field of the payload must be a key:value pair, anything else is not a label
+        let (key, value) = field.split_once(':')?;
+        let value = value.trim();
+        let non_empty = || (!value.is_empty()).then(|| value.to_owned());
+        match key.trim().to_lowercase().as_str() {
+            "pc" => product_code = non_empty(),
+            "pm" => manufacturer_part = non_empty(),
+            "qty" => quantity = value.parse().ok(),
+            "on" => order_number = non_empty(),
+            _ => {}
+        }
+    }
+
+    let product_code = product_code?.to_uppercase();
+    let is_lcsc_number = product_code.len() > 1
+        && product_code.starts_with('C')
+        && product_code[1..].chars().all(|c| c.is_ascii_digit());
+    if !is_lcsc_number {
+        return None;
+    }
+
+    Some(LcscLabel {
+        product_code,
+        manufacturer_part,
+        quantity,
+        order_number,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_label_payloads() {
+        let label = parse_lcsc_label(
+            "{pbn:PICK2209150046,on:SO2209151379,pc:C11702,pm:0402WGF1001TCE,qty:100,mc:,cc:1,pdi:67033862,hp:0,wc:JS}",
+        )
+        .unwrap();
+        assert_eq!(label.product_code, "C11702");
+        assert_eq!(label.manufacturer_part.as_deref(), Some("0402WGF1001TCE"));
+        assert_eq!(label.quantity, Some(100));
+        assert_eq!(label.order_number.as_deref(), Some("SO2209151379"));
+
+        // scanners with a mismatched keyboard layout may swallow the braces
+        let label =
+            parse_lcsc_label(" pbn:PICK1,on:SO1,pc:c8734,pm:STM32F103C8T6,qty:5\n").unwrap();
+        assert_eq!(label.product_code, "C8734");
+        assert_eq!(label.quantity, Some(5));
+
+        // missing optional fields are fine
+        let label = parse_lcsc_label("{pc:C11702}").unwrap();
+        assert_eq!(label.manufacturer_part, None);
+        assert_eq!(label.quantity, None);
+    }
+
+    #[test]
+    fn rejects_other_input() {
+        for input in [
+            "",
+            "C11702",
+            "https://jlcpcb.com/partdetail/C11702",
+            "{pbn:PICK1,on:SO1,pm:0402WGF1001TCE,qty:100}",
+            "{pc:11702,qty:100}",
+            "{pc:C11702,garbage}",
+        ] {
+            assert_eq!(parse_lcsc_label(input), None, "input: {}", input);
+        }
+    }
+}
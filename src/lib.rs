@@ -1,5 +1,34 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![allow(clippy::collapsible_if)]
 
+mod alternatives;
 mod app;
+mod audit;
+pub mod cli;
+mod compare;
+mod config;
+mod diff;
+mod git;
+mod lcsc_qr;
+mod library;
+mod library_manager;
+mod lock;
+mod lookup;
+mod manifest;
+mod part_id;
+mod paths;
+mod pricing;
+mod profile;
+mod project;
+mod provenance;
+mod qr_decode;
+mod quantity;
+mod regenerate;
+mod sexpr;
+mod sexpr_diff;
+mod stock_history;
+mod watchlist;
 pub use app::MyApp;
+
+/// Used for the app state and everything else we keep in the local data directory.
+pub const APP_ID: &str = "EasyEDA_to_KiCAD_Lib_UI";
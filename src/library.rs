@@ -0,0 +1,260 @@
+use std::{
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    alternatives::key_attributes,
+    quantity::same_value,
+    sexpr::{self, ParseError},
+};
+
+/// A symbol of one of our generated `.kicad_sym` libraries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibrarySymbol {
+    pub name: String,
+    pub library: PathBuf,
+    /// the symbol properties like Value, Footprint, Datasheet or LCSC
+    pub properties: IndexMap<String, String>,
+}
+
+impl LibrarySymbol {
+    /// The LCSC number this symbol was generated from, JLC2KiCadLib stores it as property.
+    pub fn lcsc_code(&self) -> Option<&str> {
+        ["LCSC", "LCSC Part", "LCSC Part #", "JLCPCB Part #"]
+            .iter()
+            .find_map(|key| self.properties.get(*key))
+            .map(String::as_str)
+            .or_else(|| {
+                // fall back to any property that looks like an LCSC number
+                self.properties.values().map(String::as_str).find(|value| {
+                    value.len() > 1
+                        && value.starts_with('C')
+                        && value[1..].chars().all(|c| c.is_ascii_digit())
+                })
+            })
+    }
+}
+
+/// Reads the symbols of a library from its text.
+pub fn symbols_from_str(text: &str, library: &Path) -> Result<Vec<LibrarySymbol>, ParseError> {
+    let root = sexpr::parse(text)?;
+    Ok(root
+        .children("symbol")
+        .filter_map(|symbol| {
+            let properties = symbol
+                .children("property")
+                .filter_map(|property| Some((property.arg(1)?, property.arg(2)?)))
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect();
+            Some(LibrarySymbol {
+                name: symbol.arg(1)?.to_owned(),
+                library: library.to_owned(),
+                properties,
+            })
+        })
+        .collect())
+}
+
+pub fn read_symbols(path: &Path) -> io::Result<Vec<LibrarySymbol>> {
+    symbols_from_str(&read_to_string(path)?, path)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads all symbol libraries in a directory, skipping anything that cannot be read.
+pub fn read_library(dir: &Path) -> Vec<LibrarySymbol> {
+    let Ok(entries) = dir.read_dir() else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "kicad_sym"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .flat_map(|path| match read_symbols(path) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                log::warn!("Could not read library {}: {}", path.display(), e);
+                vec![]
+            }
+        })
+        .collect()
+}
+
+/// The symbols of a library directory, re-read only when one of its files changed.
+#[derive(Debug, Default)]
+pub struct LibraryIndex {
+    dir: PathBuf,
+    modified: Vec<(PathBuf, Option<SystemTime>)>,
+    pub symbols: Vec<LibrarySymbol>,
+}
+
+impl LibraryIndex {
+    fn library_files(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let Ok(entries) = dir.read_dir() else {
+            return vec![];
+        };
+        let mut files: Vec<(PathBuf, Option<SystemTime>)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext == "kicad_sym")
+            })
+            .map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                (entry.path(), modified)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Makes sure the index reflects the current state of `dir`.
+    pub fn refresh(&mut self, dir: &Path) {
+        let files = Self::library_files(dir);
+        if self.dir != dir || self.modified != files {
+            self.symbols = read_library(dir);
+            self.dir = dir.to_owned();
+            self.modified = files;
+        }
+    }
+}
+
+/// A symbol of the library that is the same as, or can replace, a looked up part.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryMatch {
+    pub symbol: LibrarySymbol,
+    /// same LCSC number, otherwise same package and key attributes
+    pub exact: bool,
+}
+
+/// Looks for `part` in the library. Symbols only carry few properties, so to find
+/// equivalent parts we also use what `known` parts (by LCSC number) tell us about them.
+pub fn find_in_library(
+    part: &IndexMap<String, String>,
+    symbols: &[LibrarySymbol],
+    known: &IndexMap<String, IndexMap<String, String>>,
+) -> Vec<LibraryMatch> {
+    let Some(code) = part.get("Component Code") else {
+        return vec![];
+    };
+    let keys = key_attributes(part);
+    let package = part.get("Specification");
+    let mut matches: Vec<LibraryMatch> = symbols
+        .iter()
+        .filter_map(|symbol| {
+            let symbol_code = symbol.lcsc_code();
+            if symbol_code == Some(code.as_str()) {
+                return Some(LibraryMatch {
+                    symbol: symbol.clone(),
+                    exact: true,
+                });
+            }
+            // without value or package there is nothing to call equivalent
+            let package = package?;
+            if keys.is_empty() {
+                return None;
+            }
+            let mut attributes = symbol.properties.clone();
+            if let Some(details) = symbol_code.and_then(|code| known.get(code)) {
+                for (key, value) in details {
+                    attributes.entry(key.clone()).or_insert(value.clone());
+                }
+            }
+            let equivalent = attributes
+                .get("Specification")
+                .is_some_and(|other| same_value(other, package))
+                && keys.iter().all(|(name, value)| {
+                    attributes
+                        .get(name)
+                        .is_some_and(|other| same_value(other, value))
+                });
+            equivalent.then(|| LibraryMatch {
+                symbol: symbol.clone(),
+                exact: false,
+            })
+        })
+        .collect();
+    // exact matches first
+    matches.sort_by_key(|m| !m.exact);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    #[test]
+    fn reads_symbol_properties() {
+        let text = r#"(kicad_symbol_lib (version 20210201) (generator TousstNicolas/JLC2KiCad_lib)
+  (symbol "0402WGF1001TCE" (pin_names hide) (in_bom yes) (on_board yes)
+    (property "Reference" "R" (id 0) (at 0 5.08 0))
+    (property "Value" "0402WGF1001TCE" (id 1) (at 0 -5.08 0))
+    (property "Footprint" "footprint:R0402" (id 2) (at 0 -7.62 0))
+    (property "Datasheet" "" (id 3) (at 0 -10.16 0))
+    (property "LCSC" "C11702" (id 5) (at 0 -12.7 0))
+    (symbol "0402WGF1001TCE_0_1" (rectangle (start -1.02 2.54) (end 1.02 -2.54)))
+  )
+  (symbol "CL05A106MQ5NUNC" (property "Value" "CL05A106MQ5NUNC" (id 1)) (property "Part" "C15525" (id 5)))
+)"#;
+        let symbols = symbols_from_str(text, Path::new("default_lib.kicad_sym")).unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "0402WGF1001TCE");
+        assert_eq!(symbols[0].properties.len(), 4);
+        assert_eq!(symbols[0].lcsc_code(), Some("C11702"));
+        assert_eq!(symbols[1].lcsc_code(), Some("C15525"));
+    }
+
+    #[test]
+    fn finds_exact_and_equivalent_parts() {
+        let symbol = |name: &str, code: &str| LibrarySymbol {
+            name: name.to_owned(),
+            library: PathBuf::from("default_lib.kicad_sym"),
+            properties: indexmap! {
+                "Value".to_owned() => name.to_owned(),
+                "LCSC".to_owned() => code.to_owned(),
+            },
+        };
+        let resistor = |code: &str, resistance: &str, package: &str| -> IndexMap<String, String> {
+            indexmap! {
+                "Component Code".to_owned() => code.to_owned(),
+                "Specification".to_owned() => package.to_owned(),
+                "Resistance".to_owned() => resistance.to_owned(),
+                "Tolerance".to_owned() => "±1%".to_owned(),
+            }
+        };
+        let symbols = vec![
+            symbol("0402WGF1001TCE", "C11702"),
+            symbol("RC0402FR-071KL", "C106235"),
+            symbol("RC0603FR-071KL", "C22548"),
+            symbol("Unknown", "C1"),
+        ];
+        let known = indexmap! {
+            "C106235".to_owned() => resistor("C106235", "1kΩ", "0402"),
+            "C22548".to_owned() => resistor("C22548", "1kΩ", "0603"),
+        };
+
+        let matches = find_in_library(&resistor("C11702", "1kΩ", "0402"), &symbols, &known);
+        assert_eq!(matches.len(), 2);
+        assert!(matches[0].exact);
+        assert_eq!(matches[1].symbol.name, "RC0402FR-071KL");
+        assert!(!matches[1].exact);
+
+        let matches = find_in_library(&resistor("C25744", "1000Ω", "0402"), &symbols, &known);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].symbol.name, "RC0402FR-071KL");
+
+        assert!(find_in_library(&resistor("C25744", "10kΩ", "0402"), &symbols, &known).is_empty());
+    }
+}
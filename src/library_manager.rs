@@ -0,0 +1,823 @@
+use std::{
+    fs::{read_to_string, remove_file, rename},
+    io,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+
+use crate::{
+    library::{LibrarySymbol, read_symbols},
+    lock::write_atomic,
+    sexpr::{self, Sexpr},
+};
+
+/// Where the parts of our library end up, derived from the settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryPaths {
+    pub symbol_dir: PathBuf,
+    pub footprint_dir: PathBuf,
+    pub model_dir: PathBuf,
+    pub datasheet_dir: PathBuf,
+}
+
+/// A footprint file and the 3D model files it references.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footprint {
+    pub name: String,
+    pub path: PathBuf,
+    /// paths of the referenced models as written in the file
+    pub models: Vec<String>,
+}
+
+/// Everything in the library that belongs to one part: its symbol, the footprint the symbol
+/// points to, the models of that footprint and the datasheet. Files nothing points to get
+/// entries of their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryEntry {
+    pub symbol: Option<LibrarySymbol>,
+    pub footprint: Option<Footprint>,
+    pub models: Vec<PathBuf>,
+    pub datasheet: Option<PathBuf>,
+}
+
+impl LibraryEntry {
+    pub fn lcsc_code(&self) -> Option<&str> {
+        self.symbol.as_ref().and_then(LibrarySymbol::lcsc_code)
+    }
+
+    /// The files of the part, the symbol by its library which it shares with other parts.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.symbol
+            .iter()
+            .map(|symbol| symbol.library.clone())
+            .chain(
+                self.footprint
+                    .iter()
+                    .map(|footprint| footprint.path.clone()),
+            )
+            .chain(self.models.iter().cloned())
+            .chain(self.datasheet.iter().cloned())
+            .collect()
+    }
+
+    pub fn title(&self) -> String {
+        if let Some(symbol) = &self.symbol {
+            symbol.name.clone()
+        } else if let Some(footprint) = &self.footprint {
+            footprint.name.clone()
+        } else {
+            self.models
+                .iter()
+                .chain(&self.datasheet)
+                .next()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Case insensitive search through names, LCSC number and symbol properties.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
+        }
+        let symbol_texts = self
+            .symbol
+            .iter()
+            .flat_map(|symbol| std::iter::once(&symbol.name).chain(symbol.properties.values()));
+        let footprint = self.footprint.iter().map(|footprint| &footprint.name);
+        symbol_texts
+            .chain(footprint)
+            .any(|text| text.to_lowercase().contains(&query))
+            || self
+                .models
+                .iter()
+                .chain(&self.datasheet)
+                .any(|path| path.to_string_lossy().to_lowercase().contains(&query))
+    }
+}
+
+pub fn files_with_extension(dir: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let Ok(entries) = dir.read_dir() else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn read_footprint(path: &Path) -> io::Result<Footprint> {
+    let text = read_to_string(path)?;
+    let root = sexpr::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let models = root
+        .children("model")
+        .filter_map(|model| model.arg(1))
+        .map(str::to_owned)
+        .collect();
+    Ok(Footprint {
+        name: root
+            .arg(1)
+            .map(str::to_owned)
+            .or_else(|| stem(path))
+            .unwrap_or_default(),
+        path: path.to_owned(),
+        models,
+    })
+}
+
+/// File stem of a model path as written in a footprint, which may use either separator.
+pub fn model_stem(path: &str) -> Option<String> {
+    let name = path.rsplit(['/', '\\']).next()?;
+    stem(Path::new(name))
+}
+
+fn stem(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
+
+/// The footprint name a symbol points to, without the library prefix.
+pub fn footprint_name(symbol: &LibrarySymbol) -> Option<&str> {
+    let footprint = symbol.properties.get("Footprint")?;
+    Some(footprint.rsplit(':').next().unwrap_or(footprint))
+}
+
+/// Reads the whole library and groups the files that belong together.
+pub fn scan_library(paths: &LibraryPaths) -> Vec<LibraryEntry> {
+    let symbols: Vec<LibrarySymbol> = files_with_extension(&paths.symbol_dir, &["kicad_sym"])
+        .iter()
+        .flat_map(|path| read_symbols(path).unwrap_or_default())
+        .collect();
+    let footprints: Vec<Footprint> = files_with_extension(&paths.footprint_dir, &["kicad_mod"])
+        .iter()
+        .filter_map(|path| read_footprint(path).ok())
+        .collect();
+    let models = files_with_extension(&paths.model_dir, &["wrl", "step", "stp"]);
+    let datasheets = files_with_extension(&paths.datasheet_dir, &["pdf"]);
+
+    // models are matched by file stem, as there usually is a .wrl and a .step of each
+    let models_of = |footprint: &Footprint| -> Vec<PathBuf> {
+        models
+            .iter()
+            .filter(|model| {
+                footprint
+                    .models
+                    .iter()
+                    .any(|referenced| model_stem(referenced) == stem(model))
+            })
+            .cloned()
+            .collect()
+    };
+    // datasheets are named after the LCSC number, e.g. 2206010216_UNI-ROYAL-..._C11702.pdf
+    let datasheet_of = |code: &str| {
+        datasheets
+            .iter()
+            .find(|path| {
+                stem(path).is_some_and(|stem| stem == code || stem.ends_with(&format!("_{}", code)))
+            })
+            .cloned()
+    };
+
+    let mut entries = vec![];
+    for symbol in symbols {
+        let footprint = footprint_name(&symbol)
+            .and_then(|name| footprints.iter().find(|footprint| footprint.name == name))
+            .cloned();
+        entries.push(LibraryEntry {
+            models: footprint.as_ref().map(models_of).unwrap_or_default(),
+            datasheet: symbol.lcsc_code().and_then(datasheet_of),
+            footprint,
+            symbol: Some(symbol),
+        });
+    }
+    for footprint in &footprints {
+        if !entries.iter().any(|entry| {
+            entry
+                .footprint
+                .as_ref()
+                .is_some_and(|f| f.path == footprint.path)
+        }) {
+            entries.push(LibraryEntry {
+                models: models_of(footprint),
+                footprint: Some(footprint.clone()),
+                ..Default::default()
+            });
+        }
+    }
+    for model in &models {
+        if !entries.iter().any(|entry| entry.models.contains(model)) {
+            // the .wrl and .step of a model share an entry
+            match entries.iter_mut().find(|entry| {
+                entry.symbol.is_none()
+                    && entry.footprint.is_none()
+                    && entry.models.iter().any(|other| stem(other) == stem(model))
+            }) {
+                Some(entry) => entry.models.push(model.clone()),
+                None => entries.push(LibraryEntry {
+                    models: vec![model.clone()],
+                    ..Default::default()
+                }),
+            }
+        }
+    }
+    for datasheet in datasheets {
+        if !entries
+            .iter()
+            .any(|entry| entry.datasheet.as_ref() == Some(&datasheet))
+        {
+            entries.push(LibraryEntry {
+                datasheet: Some(datasheet),
+                ..Default::default()
+            });
+        }
+    }
+    entries
+}
+
+/// Escapes a string the way KiCad files do, without the surrounding quotes.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+fn invalid_name(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("\"{}\" is not a valid name", name),
+    )
+}
+
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name.contains(['/', '\\', ':', '"']) {
+        return Err(invalid_name(name));
+    }
+    Ok(())
+}
+
+/// Removes a symbol from the text of a `.kicad_sym` library, leaving the rest untouched.
+pub fn remove_symbol(text: &str, name: &str) -> io::Result<String> {
+    let children =
+        sexpr::child_spans(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let Some((span, _)) = children
+        .iter()
+        .find(|(_, child)| child.head() == Some("symbol") && child.arg(1) == Some(name))
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no symbol \"{}\"", name),
+        ));
+    };
+    // take the indentation before and the line break after the symbol with it
+    let start = text[..span.start]
+        .rfind(|c: char| c != ' ' && c != '\t')
+        .filter(|&idx| text.as_bytes()[idx] == b'\n')
+        .map(|idx| idx + 1)
+        .unwrap_or(span.start);
+    let end = if text[span.end..].starts_with("\r\n") {
+        span.end + 2
+    } else if text[span.end..].starts_with('\n') {
+        span.end + 1
+    } else {
+        span.end
+    };
+    Ok(format!("{}{}", &text[..start], &text[end..]))
+}
+
+/// The text of one symbol of a `.kicad_sym` library, as it is written in the file.
+pub fn symbol_text<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let children = sexpr::child_spans(text).ok()?;
+    children
+        .into_iter()
+        .find(|(_, child)| child.head() == Some("symbol") && child.arg(1) == Some(name))
+        .map(|(span, _)| &text[span])
+}
+
+/// Replaces a symbol of a `.kicad_sym` library text with another one, or appends it if
+/// there is no symbol with that name yet.
+pub fn replace_symbol_in(text: &str, name: &str, symbol: &str) -> io::Result<String> {
+    let children =
+        sexpr::child_spans(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Some((span, _)) = children
+        .iter()
+        .find(|(_, child)| child.head() == Some("symbol") && child.arg(1) == Some(name))
+    {
+        return Ok(format!(
+            "{}{}{}",
+            &text[..span.start],
+            symbol,
+            &text[span.end..]
+        ));
+    }
+    // child_spans made sure the text ends with the closing parenthesis of the library
+    let end = text.rfind(')').unwrap();
+    let before = text[..end].trim_end();
+    Ok(format!("{}\n  {}\n{}", before, symbol, &text[end..]))
+}
+
+/// Sets an existing property of one symbol in the text of a `.kicad_sym` library.
+pub fn set_symbol_property_in(
+    text: &str,
+    symbol: &str,
+    key: &str,
+    value: &str,
+) -> io::Result<String> {
+    let children =
+        sexpr::child_spans(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let not_found = || {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no property \"{}\" in symbol \"{}\"", key, symbol),
+        )
+    };
+    let (span, _) = children
+        .iter()
+        .find(|(_, child)| child.head() == Some("symbol") && child.arg(1) == Some(symbol))
+        .ok_or_else(not_found)?;
+    let re_property = Regex::new(&format!(
+        r#"(\(property\s+"{}"\s+)"(?:[^"\\]|\\.)*""#,
+        regex::escape(&escape(key))
+    ))
+    .unwrap();
+    let captures = re_property
+        .captures(&text[span.clone()])
+        .ok_or_else(not_found)?;
+    let found = captures.get(0).unwrap();
+    Ok(format!(
+        "{}{}{}{}",
+        &text[..span.start + found.start()],
+        &captures[1],
+        quote(value),
+        &text[span.start + found.end()..]
+    ))
+}
+
+/// Rewrites the text of a `.kicad_sym` library in canonical form, so that appending a part
+/// gives a small diff in version control: symbols sorted by name, with symbols derived from
+/// another one right after it, fixed indentation and uniformly written numbers.
+pub fn normalize_symbol_library(text: &str) -> io::Result<String> {
+    let root = sexpr::parse(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if root.head() != Some("kicad_symbol_lib") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a KiCad symbol library",
+        ));
+    }
+    let (mut symbols, header): (Vec<&Sexpr>, Vec<&Sexpr>) = root
+        .items()
+        .iter()
+        .partition(|item| item.head() == Some("symbol"));
+    // KiCad needs the parent of a derived symbol to come first
+    symbols.sort_by_cached_key(|symbol| {
+        let name = symbol.arg(1).unwrap_or_default().to_owned();
+        match symbol
+            .children("extends")
+            .next()
+            .and_then(|extends| extends.arg(1))
+        {
+            Some(parent) => (parent.to_owned(), true, name),
+            None => (name, false, String::new()),
+        }
+    });
+    Ok(Sexpr::List(header.into_iter().chain(symbols).cloned().collect()).pretty())
+}
+
+/// Normalizes a symbol library file in place, returning whether anything changed.
+pub fn normalize_library(path: &Path) -> io::Result<bool> {
+    let text = read_to_string(path)?;
+    let normalized = normalize_symbol_library(&text)?;
+    if normalized == text {
+        return Ok(false);
+    }
+    write_atomic(path, normalized)?;
+    Ok(true)
+}
+
+/// Normalizes all symbol libraries of the library, returning how many changed.
+pub fn normalize_libraries(paths: &LibraryPaths) -> io::Result<usize> {
+    let mut changed = 0;
+    for library in files_with_extension(&paths.symbol_dir, &["kicad_sym"]) {
+        if normalize_library(&library)? {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Renames a symbol in the text of a `.kicad_sym` library, including its units and
+/// symbols derived from it.
+pub fn rename_symbol_in(text: &str, old: &str, new: &str) -> io::Result<String> {
+    check_name(new)?;
+    let symbols = crate::library::symbols_from_str(text, Path::new(""))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if !symbols.iter().any(|symbol| symbol.name == old) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no symbol \"{}\"", old),
+        ));
+    }
+    if symbols.iter().any(|symbol| symbol.name == new) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("there already is a symbol \"{}\"", new),
+        ));
+    }
+    let old = regex::escape(&escape(old));
+    let new = escape(new);
+    // the units of a symbol are sub symbols named NAME_unit_style
+    let re_symbol = Regex::new(&format!(r#"\((symbol|extends)(\s+)"{}(_\d+_\d+)?""#, old)).unwrap();
+    Ok(re_symbol
+        .replace_all(text, |captures: &regex::Captures<'_>| {
+            format!(
+                "({}{}\"{}{}\"",
+                &captures[1],
+                &captures[2],
+                new,
+                captures.get(3).map_or("", |unit| unit.as_str())
+            )
+        })
+        .into_owned())
+}
+
+/// Points the `Footprint` property of all symbols in a library text from one footprint
+/// to another, keeping the library prefix.
+pub fn relink_footprint_in(text: &str, old: &str, new: &str) -> String {
+    let new = escape(new);
+    let re_property = Regex::new(&format!(
+        r#"(\(property\s+"Footprint"\s+")([^"]*:)?{}""#,
+        regex::escape(&escape(old))
+    ))
+    .unwrap();
+    re_property
+        .replace_all(text, |captures: &regex::Captures<'_>| {
+            format!(
+                "{}{}{}\"",
+                &captures[1],
+                captures.get(2).map_or("", |lib| lib.as_str()),
+                new
+            )
+        })
+        .into_owned()
+}
+
+/// Points the model references of a footprint text from one model file stem to another.
+pub fn relink_model_in(text: &str, old: &str, new: &str) -> String {
+    let re_model = Regex::new(&format!(
+        r#"(\(model\s+"(?:[^"]*[/\\])?){}(\.(?i:wrl|step|stp)")"#,
+        regex::escape(old)
+    ))
+    .unwrap();
+    re_model
+        .replace_all(text, |captures: &regex::Captures<'_>| {
+            format!("{}{}{}", &captures[1], new, &captures[2])
+        })
+        .into_owned()
+}
+
+/// Replaces one model path of a footprint text as a whole.
+pub fn set_model_path_in(text: &str, old: &str, new: &str) -> String {
+    let re_model = Regex::new(&format!(r#"\(model\s+{}"#, regex::escape(&quote(old)))).unwrap();
+    re_model
+        .replace_all(text, |_: &regex::Captures<'_>| {
+            format!("(model {}", quote(new))
+        })
+        .into_owned()
+}
+
+/// Changes the name a footprint file declares for itself.
+pub fn rename_footprint_in(text: &str, new: &str) -> String {
+    let re_name =
+        Regex::new(r#"^(\s*\((?:footprint|module)\s+)("(?:[^"\\]|\\.)*"|[^\s()]+)"#).unwrap();
+    re_name
+        .replace(text, |captures: &regex::Captures<'_>| {
+            format!("{}{}", &captures[1], quote(new))
+        })
+        .into_owned()
+}
+
+pub fn update_file(path: &Path, edit: impl FnOnce(&str) -> io::Result<String>) -> io::Result<()> {
+    let text = read_to_string(path)?;
+    let edited = edit(&text)?;
+    if edited != text {
+        write_atomic(path, edited)?;
+    }
+    Ok(())
+}
+
+pub fn rename_symbol(library: &Path, old: &str, new: &str) -> io::Result<()> {
+    update_file(library, |text| rename_symbol_in(text, old, new))
+}
+
+/// Renames a footprint file and updates all symbols pointing to it.
+pub fn rename_footprint(paths: &LibraryPaths, footprint: &Footprint, new: &str) -> io::Result<()> {
+    check_name(new)?;
+    let new_path = footprint.path.with_file_name(format!("{}.kicad_mod", new));
+    if new_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", new_path.display()),
+        ));
+    }
+    update_file(&footprint.path, |text| Ok(rename_footprint_in(text, new)))?;
+    rename(&footprint.path, &new_path)?;
+    for library in files_with_extension(&paths.symbol_dir, &["kicad_sym"]) {
+        update_file(&library, |text| {
+            Ok(relink_footprint_in(text, &footprint.name, new))
+        })?;
+    }
+    Ok(())
+}
+
+/// Renames all files of a model (.wrl and .step) and updates the footprints using it.
+pub fn rename_model(paths: &LibraryPaths, models: &[PathBuf], new: &str) -> io::Result<()> {
+    check_name(new)?;
+    let Some(old) = models.first().and_then(|model| stem(model)) else {
+        return Ok(());
+    };
+    for model in models {
+        let mut new_path = model.with_file_name(new);
+        if let Some(ext) = model.extension() {
+            new_path.set_extension(ext);
+        }
+        if new_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", new_path.display()),
+            ));
+        }
+    }
+    for model in models {
+        let mut new_path = model.with_file_name(new);
+        if let Some(ext) = model.extension() {
+            new_path.set_extension(ext);
+        }
+        rename(model, new_path)?;
+    }
+    for footprint in files_with_extension(&paths.footprint_dir, &["kicad_mod"]) {
+        update_file(&footprint, |text| Ok(relink_model_in(text, &old, new)))?;
+    }
+    Ok(())
+}
+
+/// Names of the symbols of a library derived from `parent` with `(extends ...)`.
+fn derived_symbols(text: &str, parent: &str) -> io::Result<Vec<String>> {
+    let root = sexpr::parse(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(root
+        .children("symbol")
+        .filter(|symbol| {
+            symbol
+                .children("extends")
+                .next()
+                .and_then(|extends| extends.arg(1))
+                == Some(parent)
+        })
+        .filter_map(|symbol| symbol.arg(1).map(str::to_owned))
+        .collect())
+}
+
+/// Deletes an entry from the library. Footprints and models still used by other
+/// symbols are kept, so nothing else ends up with a dangling link. A symbol others are
+/// derived from is not deleted before them.
+pub fn delete_entry(entry: &LibraryEntry, all: &[LibraryEntry]) -> io::Result<()> {
+    let others = || all.iter().filter(move |other| *other != entry);
+    if let Some(symbol) = &entry.symbol {
+        let derived = derived_symbols(&read_to_string(&symbol.library)?, &symbol.name)?;
+        if !derived.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is derived from {}, delete it first",
+                    derived.join(", "),
+                    symbol.name
+                ),
+            ));
+        }
+        update_file(&symbol.library, |text| remove_symbol(text, &symbol.name))?;
+    }
+    if let Some(footprint) = &entry.footprint {
+        let shared = others().any(|other| {
+            other.symbol.is_some()
+                && other
+                    .footprint
+                    .as_ref()
+                    .is_some_and(|f| f.path == footprint.path)
+        });
+        if !shared {
+            remove_file(&footprint.path)?;
+        }
+    }
+    for model in &entry.models {
+        let shared = others().any(|other| {
+            (other.symbol.is_some() || other.footprint.is_some()) && other.models.contains(model)
+        });
+        if !shared {
+            remove_file(model)?;
+        }
+    }
+    if let Some(datasheet) = &entry.datasheet {
+        remove_file(datasheet)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+
+    const LIBRARY: &str = r#"(kicad_symbol_lib (version 20210201) (generator TousstNicolas/JLC2KiCad_lib)
+  (symbol "0402WGF1001TCE" (in_bom yes) (on_board yes)
+    (property "Footprint" "footprint:R0402" (id 2) (at 0 -7.62 0))
+    (property "LCSC" "C11702" (id 5) (at 0 -12.7 0))
+    (symbol "0402WGF1001TCE_0_1" (rectangle (start -1.02 2.54) (end 1.02 -2.54)))
+  )
+  (symbol "0402WGF1002TCE" (in_bom yes) (on_board yes)
+    (property "Footprint" "footprint:R0402" (id 2) (at 0 -7.62 0))
+    (property "LCSC" "C25744" (id 5) (at 0 -12.7 0))
+  )
+  (symbol "CL05A106MQ5NUNC" (extends "0402WGF1001TCE")
+    (property "Footprint" "footprint:C0402" (id 2) (at 0 -7.62 0))
+    (property "LCSC" "C15525" (id 5) (at 0 -12.7 0))
+  )
+)
+"#;
+
+    const FOOTPRINT: &str = r#"(module R0402 (layer F.Cu) (tedit 5DD50112)
+  (fp_text reference REF** (at 0 -4) (layer F.SilkS))
+  (model "${KIPRJMOD}/packages3d/R0402.wrl"
+    (offset (xyz 0 0 0)) (scale (xyz 1 1 1)) (rotate (xyz 0 0 0)))
+)
+"#;
+
+    fn setup() -> (tempfile::TempDir, LibraryPaths) {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = LibraryPaths {
+            symbol_dir: dir.path().join("symbol"),
+            footprint_dir: dir.path().join("footprint"),
+            model_dir: dir.path().join("footprint/packages3d"),
+            datasheet_dir: dir.path().join("datasheets"),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        create_dir_all(&paths.model_dir).unwrap();
+        create_dir_all(&paths.datasheet_dir).unwrap();
+        write(paths.symbol_dir.join("default_lib.kicad_sym"), LIBRARY).unwrap();
+        write(paths.footprint_dir.join("R0402.kicad_mod"), FOOTPRINT).unwrap();
+        write(
+            paths.footprint_dir.join("C0402.kicad_mod"),
+            FOOTPRINT.replace("R0402", "C0402"),
+        )
+        .unwrap();
+        write(paths.model_dir.join("R0402.wrl"), "").unwrap();
+        write(paths.model_dir.join("R0402.step"), "").unwrap();
+        write(paths.model_dir.join("SOT-23.step"), "").unwrap();
+        write(
+            paths
+                .datasheet_dir
+                .join("2206010216_UNI-ROYAL-0402WGF1001TCE_C11702.pdf"),
+            "",
+        )
+        .unwrap();
+        (dir, paths)
+    }
+
+    #[test]
+    fn groups_library_files() {
+        let (_dir, paths) = setup();
+        let entries = scan_library(&paths);
+        let titles: Vec<String> = entries.iter().map(LibraryEntry::title).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "0402WGF1001TCE",
+                "0402WGF1002TCE",
+                "CL05A106MQ5NUNC",
+                "SOT-23.step"
+            ]
+        );
+        assert_eq!(entries[0].footprint.as_ref().unwrap().name, "R0402");
+        assert_eq!(entries[0].models.len(), 2);
+        assert!(entries[0].datasheet.is_some());
+        assert_eq!(entries[2].footprint.as_ref().unwrap().name, "C0402");
+        assert!(entries[2].models.is_empty());
+        assert!(entries[0].matches("c11702"));
+        assert!(!entries[1].matches("c11702"));
+    }
+
+    #[test]
+    fn renames_keep_links() {
+        let (_dir, paths) = setup();
+        let library = paths.symbol_dir.join("default_lib.kicad_sym");
+        rename_symbol(&library, "0402WGF1001TCE", "R_1k_0402").unwrap();
+        let text = read_to_string(&library).unwrap();
+        assert!(text.contains(r#"(symbol "R_1k_0402" (in_bom"#));
+        assert!(text.contains(r#"(symbol "R_1k_0402_0_1""#));
+        assert!(text.contains(r#"(extends "R_1k_0402")"#));
+        assert!(rename_symbol(&library, "R_1k_0402", "0402WGF1002TCE").is_err());
+
+        let entries = scan_library(&paths);
+        rename_footprint(
+            &paths,
+            entries[0].footprint.as_ref().unwrap(),
+            "R_0402_1005Metric",
+        )
+        .unwrap();
+        let text = read_to_string(&library).unwrap();
+        assert_eq!(text.matches(r#""footprint:R_0402_1005Metric""#).count(), 2);
+        let footprint =
+            read_to_string(paths.footprint_dir.join("R_0402_1005Metric.kicad_mod")).unwrap();
+        assert!(footprint.starts_with(r#"(module "R_0402_1005Metric" (layer"#));
+
+        let entries = scan_library(&paths);
+        rename_model(&paths, &entries[0].models, "R_0402").unwrap();
+        let footprint =
+            read_to_string(paths.footprint_dir.join("R_0402_1005Metric.kicad_mod")).unwrap();
+        assert!(footprint.contains(r#"(model "${KIPRJMOD}/packages3d/R_0402.wrl""#));
+        let entries = scan_library(&paths);
+        assert_eq!(entries[0].models.len(), 2);
+        assert_eq!(entries.len(), 4);
+    }
+
+    #[test]
+    fn sets_properties() {
+        let text =
+            set_symbol_property_in(LIBRARY, "0402WGF1002TCE", "Footprint", "footprint:R_0402")
+                .unwrap();
+        assert_eq!(text.matches(r#""footprint:R0402""#).count(), 1);
+        assert!(text.contains(r#"(property "Footprint" "footprint:R_0402" (id 2)"#));
+        assert!(set_symbol_property_in(LIBRARY, "0402WGF1002TCE", "Datasheet", "x").is_err());
+    }
+
+    #[test]
+    fn replaces_symbols() {
+        let old = symbol_text(LIBRARY, "0402WGF1002TCE").unwrap();
+        assert!(old.starts_with("(symbol \"0402WGF1002TCE\""));
+        assert!(old.ends_with("(at 0 -12.7 0))\n  )"));
+        let new = old.replace("C25744", "C25745");
+        let text = replace_symbol_in(LIBRARY, "0402WGF1002TCE", &new).unwrap();
+        assert_eq!(text, LIBRARY.replace("C25744", "C25745"));
+        let text = replace_symbol_in(LIBRARY, "R_10k", "(symbol \"R_10k\")").unwrap();
+        assert!(text.ends_with("  )\n  (symbol \"R_10k\")\n)\n"));
+    }
+
+    #[test]
+    fn normalizes_libraries() {
+        let text = normalize_symbol_library(LIBRARY).unwrap();
+        let names: Vec<String> = crate::library::symbols_from_str(&text, Path::new(""))
+            .unwrap()
+            .into_iter()
+            .map(|symbol| symbol.name)
+            .collect();
+        // the derived symbol stays right after its parent
+        assert_eq!(
+            names,
+            vec!["0402WGF1001TCE", "CL05A106MQ5NUNC", "0402WGF1002TCE"]
+        );
+        assert!(text.starts_with(
+            "(kicad_symbol_lib\n  (version 20210201)\n  (generator TousstNicolas/JLC2KiCad_lib)\n  (symbol"
+        ));
+        assert_eq!(normalize_symbol_library(&text).unwrap(), text);
+        assert!(normalize_symbol_library(FOOTPRINT).is_err());
+    }
+
+    #[test]
+    fn deletes_without_dangling_links() {
+        let (_dir, paths) = setup();
+        let library = paths.symbol_dir.join("default_lib.kicad_sym");
+        let entries = scan_library(&paths);
+        // the capacitor is derived from the first resistor
+        assert!(delete_entry(&entries[0], &entries).is_err());
+        assert_eq!(read_to_string(&library).unwrap(), LIBRARY);
+        delete_entry(&entries[2], &entries).unwrap();
+        let entries = scan_library(&paths);
+        // the footprint is still used by the second resistor
+        delete_entry(&entries[0], &entries).unwrap();
+        let entries = scan_library(&paths);
+        assert_eq!(entries.len(), 2);
+        // nothing extends a symbol that is gone
+        assert!(!read_to_string(&library).unwrap().contains("(extends"));
+        assert_eq!(entries[0].title(), "0402WGF1002TCE");
+        assert_eq!(entries[0].models.len(), 2);
+
+        delete_entry(&entries[0], &entries).unwrap();
+        let entries = scan_library(&paths);
+        let titles: Vec<String> = entries.iter().map(LibraryEntry::title).collect();
+        assert_eq!(titles, vec!["SOT-23.step"]);
+        let text = read_to_string(&library).unwrap();
+        assert_eq!(
+            text,
+            "(kicad_symbol_lib (version 20210201) (generator TousstNicolas/JLC2KiCad_lib)\n)\n"
+        );
+    }
+}
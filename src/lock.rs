@@ -0,0 +1,254 @@
+use std::{
+    fmt,
+    fs::{OpenOptions, create_dir_all, read_to_string, remove_file},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::stock_history::format_date;
+
+/// Name of the lock file in the output directory of the library.
+const LOCK_FILE: &str = ".easyeda_to_kicad_lib.lock";
+
+/// Nothing we do to the library takes this long, so an older lock was left behind by a
+/// crashed instance or a machine that lost its connection to the share.
+const STALE_AFTER: i64 = 10 * 60;
+
+/// Who holds the lock of a library, written into the lock file.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LockInfo {
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+    /// unix timestamp
+    pub since: i64,
+    /// what is done to the library, e.g. "adding C11702"
+    pub action: String,
+}
+
+impl LockInfo {
+    fn current(action: &str) -> Self {
+        Self {
+            user: user_name(),
+            host: host_name(),
+            pid: std::process::id(),
+            since: jiff::Timestamp::now().as_second(),
+            action: action.to_owned(),
+        }
+    }
+
+    fn is_stale(&self, now: i64) -> bool {
+        now - self.since > STALE_AFTER
+            || (self.host == host_name() && process_alive(self.pid) == Some(false))
+    }
+}
+
+impl fmt::Display for LockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} on {} ({}, since {})",
+            self.user,
+            self.host,
+            self.action,
+            format_date(self.since, true)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// somebody else is writing to the library
+    Held(LockInfo),
+    Io(io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Held(info) => write!(f, "the library is locked by {}", info),
+            LockError::Io(e) => write!(f, "could not lock the library: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> Self {
+        LockError::Io(e)
+    }
+}
+
+pub fn user_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or("unknown".to_owned())
+}
+
+pub fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("unknown".to_owned())
+}
+
+/// Whether a process of this machine still runs, `None` if we cannot tell.
+fn process_alive(pid: u32) -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        Some(Path::new("/proc").join(pid.to_string()).exists())
+    } else {
+        None
+    }
+}
+
+/// An advisory lock on a library directory, shared with other instances of the app through
+/// a lock file. It is released when dropped.
+#[derive(Debug)]
+pub struct LibraryLock {
+    path: PathBuf,
+}
+
+impl Drop for LibraryLock {
+    fn drop(&mut self) {
+        if let Err(e) = remove_file(&self.path) {
+            log::warn!("Could not remove lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn read_lock(path: &Path) -> io::Result<LockInfo> {
+    let text = read_to_string(path)?;
+    serde_json::from_str(&text).or_else(|_| {
+        // half written or from something else, so all we know is its age
+        let modified = path.metadata()?.modified()?;
+        let since = jiff::Timestamp::try_from(modified)
+            .map(|time| time.as_second())
+            .unwrap_or_default();
+        Ok(LockInfo {
+            user: "unknown".to_owned(),
+            host: "unknown".to_owned(),
+            pid: 0,
+            since,
+            action: "unknown".to_owned(),
+        })
+    })
+}
+
+/// Locks the library in `dir` for `action`, failing right away if somebody else holds the
+/// lock. Stale locks are removed.
+pub fn try_lock(dir: &Path, action: &str) -> Result<LibraryLock, LockError> {
+    create_dir_all(dir)?;
+    let path = dir.join(LOCK_FILE);
+    // the second attempt is after removing a stale lock
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let lock = LibraryLock { path };
+                file.write_all(
+                    serde_json::to_string_pretty(&LockInfo::current(action))
+                        .unwrap()
+                        .as_bytes(),
+                )?;
+                file.sync_all()?;
+                return Ok(lock);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let info = match read_lock(&path) {
+                    Ok(info) => info,
+                    // released in the meantime
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                if !info.is_stale(jiff::Timestamp::now().as_second()) {
+                    return Err(LockError::Held(info));
+                }
+                log::info!("Removing stale lock of {}", info);
+                match remove_file(&path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(LockError::Io(io::Error::other(
+        "the lock file keeps reappearing",
+    )))
+}
+
+/// Locks the library like [`try_lock`], waiting up to `timeout` for somebody else to finish.
+pub fn lock(dir: &Path, action: &str, timeout: Duration) -> Result<LibraryLock, LockError> {
+    let start = Instant::now();
+    loop {
+        match try_lock(dir, action) {
+            Err(LockError::Held(_)) if start.elapsed() < timeout => {
+                thread::sleep(Duration::from_millis(500))
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Replaces a file by writing a temporary file next to it and renaming that over it, so
+/// others reading it from a network share never see half of it.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut file = tempfile::Builder::new()
+        .prefix(".easyeda_to_kicad_lib")
+        .tempfile_in(dir)?;
+    file.write_all(contents.as_ref())?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+
+    #[test]
+    fn locks_libraries() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = try_lock(dir.path(), "adding C11702").unwrap();
+        match try_lock(dir.path(), "normalizing") {
+            Err(LockError::Held(info)) => {
+                assert_eq!(info.action, "adding C11702");
+                assert_eq!(info.user, user_name());
+            }
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+        drop(lock);
+        let lock = try_lock(dir.path(), "normalizing").unwrap();
+        std::mem::forget(lock);
+
+        // a lock left behind long ago is taken over
+        let mut info = read_lock(&dir.path().join(LOCK_FILE)).unwrap();
+        info.since -= STALE_AFTER + 1;
+        write(
+            dir.path().join(LOCK_FILE),
+            serde_json::to_string(&info).unwrap(),
+        )
+        .unwrap();
+        assert!(try_lock(dir.path(), "adding C25744").is_ok());
+        assert!(!dir.path().join(LOCK_FILE).exists());
+    }
+
+    #[test]
+    fn writes_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("default_lib.kicad_sym");
+        write(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "new");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{cell::OnceCell, fmt};
+
+use indexmap::{IndexMap, indexmap};
+
+use crate::{part_id::parse_part_id, pricing::price_breaks_from_json};
+
+pub fn get_lcsc_detail(
+    lcscnumber: &str,
+    client: &reqwest::blocking::Client,
+) -> Option<serde_json::Value> {
+    // this is the fallback function for when JLCPCB gives us no images or prices, then we resort to asking LCSC
+    let res_or_err = client
+        .get(format!(
+            "https://wmsc.lcsc.com/ftps/wm/product/detail?productCode={}",
+            lcscnumber
+        ))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(
+            reqwest::header::USER_AGENT,
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:134.0) Gecko/20100101 Firefox/134.0",
+        )
+        .send();
+    if let Ok(res) = res_or_err {
+        if res.status().is_success() {
+            let pictext = res.text().ok()?;
+            let json: serde_json::Value = serde_json::from_str(&pictext).ok()?;
+
+            // there is a case where we get a fully valid response in an HTML
+            // and JSON sense but it tells us via a code field in the JSON
+            // that no part could be found, in that case we return early with nothing
+            if let Some(code) = json.get("code") {
+                if code != 200 {
+                    return None;
+                }
+            }
+            return json.get("result").cloned();
+        }
+    }
+    // if we fall through to here, we failed getting data somewhere along the way
+    None
+}
+
+pub fn get_imglist(lcsc_detail: &serde_json::Value) -> Option<Vec<String>> {
+    let mut imglist = vec![];
+    if let Some(imagelist) = lcsc_detail.get("productImages") {
+        if let Some(imagevec) = imagelist.as_array() {
+            for img in imagevec.iter() {
+                imglist.push(img.to_string().trim_matches('"').to_owned());
+            }
+            return Some(imglist);
+        }
+    }
+    None
+}
+
+/// Looks up a part, `None` if there is no such part or the lookup failed.
+pub fn get_part(search_term: &str) -> Option<IndexMap<String, String>> {
+    lookup_part(search_term).ok()
+}
+
+/// Why a part lookup did not produce any data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupError {
+    /// the search term does not contain an LCSC number
+    InvalidNumber,
+    /// JLCPCB answered, but does not know the part (anymore)
+    NotFound,
+    /// no usable answer, e.g. because we are offline
+    Unavailable,
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            LookupError::InvalidNumber => "not a valid LCSC number or part URL",
+            LookupError::NotFound => "no such part",
+            LookupError::Unavailable => "JLCPCB could not be reached",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Gathers everything JLCPCB (and LCSC as fallback) know about a part into a table of
+/// pretty parameter names to values. Keys starting with `meta_` are not meant for display.
+pub fn lookup_part(search_term: &str) -> Result<IndexMap<String, String>, LookupError> {
+    // ensure we only make requests if what we have looks like an LCSC number and can work,
+    // also saves us from urlencoding and such because it will only ever be "C" followed by some numbers
+    let Some(part_id) = parse_part_id(search_term) else {
+        return Err(LookupError::InvalidNumber);
+    };
+    let lcscnumber = part_id.lcsc_number.as_str();
+    let client = reqwest::blocking::Client::new();
+    // LCSC is only asked if JLCPCB leaves gaps, and then at most once
+    let lcsc_detail = OnceCell::new();
+    let get_lcsc_detail = || {
+        lcsc_detail
+            .get_or_init(|| get_lcsc_detail(lcscnumber, &client))
+            .as_ref()
+    };
+    let res_or_err = client
+        .get(format!(
+            "https://cart.jlcpcb.com/shoppingCart/smtGood/getComponentDetail?componentCode={}",
+            lcscnumber
+        ))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send();
+    if let Ok(res) = res_or_err {
+        let res_status = res.status();
+        if res_status.is_success() {
+            // an unexpected answer, e.g. an error page, is no reason to crash
+            let res_text = res.text().map_err(|_| LookupError::Unavailable)?;
+            let json: serde_json::Value =
+                serde_json::from_str(&res_text).map_err(|_| LookupError::Unavailable)?;
+            // only debug: println!("{}", json);
+            let parameters = indexmap! {
+                "componentCode" => "Component Code",
+                "firstTypeNameEn" => "Primary Category",
+                "secondTypeNameEn" => "Secondary Category",
+                "componentBrandEn" => "Brand",
+                "componentName" => "Full Name",
+                "componentDesignator" => "Designator",
+                "componentModelEn" => "Model",
+                "componentSpecificationEn" => "Specification",
+                "assemblyProcess" => "Assembly Process",
+                "describe" => "Description",
+                "matchedPartDetail" => "Details",
+                "stockCount" => "Stock",
+                "leastNumber" => "Minimal Quantity",
+                "leastNumberPrice" => "Minimum Price",
+            };
+
+            // there is a case where we get a fully valid response in an HTML
+            // and JSON sense but it tells us via a code field in the JSON
+            // that no part could be found, in that case we exit early
+            if let Some(code) = json.get("code") {
+                if code != 200 {
+                    return Err(LookupError::NotFound);
+                }
+            }
+
+            // if the data section is there as expected, we start taking it apart
+            if let Some(data) = json.get("data") {
+                let mut tabledata: IndexMap<String, String> = indexmap! {};
+                tabledata.insert("meta_source".to_owned(), part_id.source.to_string());
+
+                // determine if it is a JLCPCB basic or extended assembly part
+                if let Some(parttype) = data.get("componentLibraryType") {
+                    if parttype == "base" {
+                        tabledata.insert("Type".to_owned(), "Basic".to_owned());
+                    } else if parttype == "expand" {
+                        tabledata.insert("Type".to_owned(), "Extended".to_owned());
+                    }
+                }
+
+                // now pretty-format the parameters that should always be there
+                for (key, title) in parameters {
+                    if let Some(value) = data.get(key) {
+                        tabledata.insert(
+                            title.to_owned(),
+                            value.to_string().trim_matches('"').to_owned(),
+                        );
+                    }
+                }
+
+                // now the component specific attributes, these are in a nested array within
+                // the JSON and vary by component
+                if let Some(attributes) = data.get("attributes") {
+                    if let Some(array) = attributes.as_array() {
+                        for attribute in array {
+                            if let Some(name) = attribute.get("attribute_name_en") {
+                                if let Some(value) = attribute.get("attribute_value_name") {
+                                    tabledata.insert(
+                                        name.to_string().trim_matches('"').to_owned(),
+                                        value.to_string().trim_matches('"').to_owned(),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // the full price ladder, with LCSC as fallback if JLCPCB has none
+                let mut price_breaks = price_breaks_from_json(data);
+                let mut order_multiple = None;
+                let mut reel_quantity = data.get("encapsulationNumber").and_then(|v| v.as_u64());
+                if price_breaks.is_empty() {
+                    if let Some(lcsc_detail) = get_lcsc_detail() {
+                        price_breaks = price_breaks_from_json(lcsc_detail);
+                        // LCSC sells cut tape in multiples of its minimum buy quantity
+                        order_multiple = lcsc_detail.get("minBuyNumber").and_then(|v| v.as_u64());
+                        reel_quantity = reel_quantity.or_else(|| {
+                            lcsc_detail.get("minPacketNumber").and_then(|v| v.as_u64())
+                        });
+                    }
+                }
+                for (idx, price_break) in price_breaks.iter().enumerate() {
+                    tabledata.insert(
+                        format!("meta_price_break{}", idx),
+                        format!("{}:{}", price_break.quantity, price_break.unit_price),
+                    );
+                }
+                if let Some(order_multiple) = order_multiple {
+                    tabledata.insert("meta_order_multiple".to_owned(), order_multiple.to_string());
+                }
+                if let Some(reel_quantity) = reel_quantity {
+                    tabledata.insert("meta_reel_quantity".to_owned(), reel_quantity.to_string());
+                }
+
+                // here we gather metadata for the image and datasheet URLs
+                if let Some(imagelist) = data.get("imageList") {
+                    if let Some(imagevec) = imagelist.as_array() {
+                        for (idx, i) in imagevec.iter().enumerate() {
+                            if let Some(imageurl) = i.get("productBigImage") {
+                                // this is a f*ed up case where JLC returns API IDs instead of URLs
+                                if imageurl.is_null() {
+                                    // this does not work right now because of MIME type issues, get from LCSC instead
+                                    // if let Some(imageid) = i.get("productBigImageAccessId")
+                                    // {
+                                    //     let apiurl = format!("https://jlcpcb.com/api/file/downloadByFileSystemAccessId/{}.jpg", imageid.to_string().trim_matches('"').to_owned());
+                                    //     tabledata
+                                    //         .insert(format!("meta_image{}", idx), apiurl);
+                                    // }
+                                    if let Some(lcsc_imglist) =
+                                        get_lcsc_detail().and_then(get_imglist)
+                                    {
+                                        for (idx, i) in lcsc_imglist.iter().enumerate() {
+                                            tabledata
+                                                .insert(format!("meta_image{}", idx), i.to_owned());
+                                        }
+                                    }
+                                    break;
+                                } else {
+                                    tabledata.insert(
+                                        format!("meta_image{}", idx),
+                                        imageurl.to_string().trim_matches('"').to_owned(),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // This mess is because LCSC can or can not have an offical data sheet link directly to
+                // the manufacturer, but sometimes the key also exists but is empty. In case it exists
+                // and is valid we must take it, because then the other one just redirects to the product
+                // page URL, leaving us without a datasheet that we can actually download. If it does
+                // not exist the datasheet is actually stored at LCSC and the key in the else branch
+                // does return a valid PDF. So yeah...
+                if let Some(officialdatasheeturl) = data.get("dataManualOfficialLink")
+                    && officialdatasheeturl != ""
+                {
+                    tabledata.insert(
+                        "meta_datasheeturl".to_owned(),
+                        officialdatasheeturl
+                            .to_string()
+                            .trim_matches('"')
+                            .to_owned(),
+                    );
+                } else if let Some(datasheeturl) = data.get("dataManualUrl") {
+                    tabledata.insert(
+                        "meta_datasheeturl".to_owned(),
+                        datasheeturl.to_string().trim_matches('"').to_owned(),
+                    );
+                }
+                return Ok(tabledata);
+            }
+        }
+    }
+    // if we fall through to here, we failed getting data somewhere along the way
+    Err(LookupError::Unavailable)
+}
@@ -7,6 +7,12 @@
 fn main() -> eframe::Result<()> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // headless commands, e.g. refreshing the watchlist from cron
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(exit_code) = easyeda_to_kicad_lib_ui::cli::run(&args) {
+        std::process::exit(exit_code);
+    }
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1024.0, 1000.0])
@@ -16,7 +22,7 @@ fn main() -> eframe::Result<()> {
         ..Default::default()
     };
     eframe::run_native(
-        "EasyEDA_to_KiCAD_Lib_UI",
+        easyeda_to_kicad_lib_ui::APP_ID,
         native_options,
         Box::new(|cc| {
             // This gives us image support:
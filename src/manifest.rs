@@ -0,0 +1,489 @@
+use std::{
+    fmt,
+    fs::{OpenOptions, read_to_string},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    library_manager::{
+        LibraryEntry, LibraryPaths, normalize_libraries, rename_symbol, scan_library,
+    },
+    lock::write_atomic,
+    provenance::locked_hashes,
+};
+
+/// The parts a library should contain, kept by hand in its output directory.
+pub const MANIFEST_FILE: &str = "parts.toml";
+/// Hashes of the files sync generated, so a fresh checkout can check it rebuilt the same.
+pub const LOCK_FILE: &str = "parts.lock";
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// Per-part overrides of the manifest, e.g. `[parts.C11702]` with `symbol = "R_1k"`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PartSpec {
+    /// name of the symbol in the library instead of the one the converter picks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub no_footprint: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub no_symbol: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub parts: IndexMap<String, PartSpec>,
+}
+
+impl Manifest {
+    pub fn path(output: &Path) -> PathBuf {
+        output.join(MANIFEST_FILE)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let manifest: Manifest = toml::from_str(text).map_err(|e| e.to_string())?;
+        // codes are matched against the LCSC property of the symbols, which is uppercase
+        Ok(Manifest {
+            parts: manifest
+                .parts
+                .into_iter()
+                .map(|(code, spec)| (code.trim().to_uppercase(), spec))
+                .collect(),
+        })
+    }
+
+    pub fn load(output: &Path) -> Result<Self, String> {
+        let path = Self::path(output);
+        let text = read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Parts of the manifest without symbol, footprint or model in the library.
+    pub fn missing<'a>(&'a self, entries: &[LibraryEntry]) -> Vec<&'a str> {
+        self.parts
+            .keys()
+            .map(String::as_str)
+            .filter(|code| !entries.iter().any(|entry| entry.lcsc_code() == Some(code)))
+            .collect()
+    }
+
+    /// Parts in the library the manifest does not list, e.g. added by hand.
+    pub fn unlisted(&self, entries: &[LibraryEntry]) -> Vec<String> {
+        let mut unlisted: Vec<String> = vec![];
+        for code in entries.iter().filter_map(LibraryEntry::lcsc_code) {
+            if !self.parts.contains_key(code) && !unlisted.iter().any(|other| other == code) {
+                unlisted.push(code.to_owned());
+            }
+        }
+        unlisted
+    }
+}
+
+/// Adds parts to the manifest without rewriting it, so comments and layout stay as they are.
+pub fn append_to_manifest(output: &Path, codes: &[String]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Manifest::path(output))?;
+    for code in codes {
+        write!(file, "\n[parts.{}]\n", code)?;
+    }
+    Ok(())
+}
+
+/// Content of `parts.lock`, the file hashes of every part of the manifest.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct PartsLock {
+    pub parts: IndexMap<String, IndexMap<String, String>>,
+}
+
+impl PartsLock {
+    pub fn path(output: &Path) -> PathBuf {
+        output.join(LOCK_FILE)
+    }
+
+    pub fn load(output: &Path) -> Result<Self, String> {
+        let path = Self::path(output);
+        let text = read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, output: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        write_atomic(
+            &Self::path(output),
+            format!(
+                "# Generated by sync from {}, do not edit.\n\n{}",
+                MANIFEST_FILE, text
+            ),
+        )
+    }
+
+    /// Hashes the files of the parts of the manifest as they are in the library now.
+    pub fn of(manifest: &Manifest, entries: &[LibraryEntry], output: &Path) -> Self {
+        PartsLock {
+            parts: manifest
+                .parts
+                .keys()
+                .filter_map(|code| {
+                    let entry = entries
+                        .iter()
+                        .find(|entry| entry.lcsc_code() == Some(code))?;
+                    Some((code.clone(), locked_hashes(entry, output)))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What syncing the library with its manifest did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub imported: Vec<String>,
+    /// parts whose symbol got the name the manifest asks for
+    pub renamed: Vec<String>,
+    pub failed: Vec<(String, String)>,
+    pub unlisted: Vec<String>,
+    /// parts whose files differ from the previous lock file
+    pub changed: Vec<String>,
+    /// parts written to the lock file
+    pub locked: usize,
+}
+
+impl SyncReport {
+    pub fn has_problems(&self) -> bool {
+        !self.failed.is_empty() || !self.unlisted.is_empty()
+    }
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.imported.is_empty() {
+            writeln!(f, "Imported: {}", self.imported.join(", "))?;
+        }
+        if !self.renamed.is_empty() {
+            writeln!(f, "Renamed symbols of: {}", self.renamed.join(", "))?;
+        }
+        for (code, error) in &self.failed {
+            writeln!(f, "Could not import {}: {}", code, error)?;
+        }
+        if !self.unlisted.is_empty() {
+            writeln!(f, "Not in {}: {}", MANIFEST_FILE, self.unlisted.join(", "))?;
+        }
+        if !self.changed.is_empty() {
+            writeln!(
+                f,
+                "Changed since the last sync: {}",
+                self.changed.join(", ")
+            )?;
+        }
+        write!(f, "{} parts in {}", self.locked, LOCK_FILE)
+    }
+}
+
+/// Imports the parts of the manifest missing from the library with `import`, applies the
+/// overrides of the manifest and writes the lock file. The caller holds the library lock.
+pub fn sync(
+    output: &Path,
+    paths: &LibraryPaths,
+    normalize: bool,
+    mut import: impl FnMut(&str, &PartSpec) -> Result<(), String>,
+) -> Result<SyncReport, String> {
+    let manifest = Manifest::load(output)?;
+    let mut report = SyncReport::default();
+    let entries = scan_library(paths);
+    for code in manifest.missing(&entries) {
+        match import(code, &manifest.parts[code]) {
+            Ok(()) => report.imported.push(code.to_owned()),
+            Err(e) => report.failed.push((code.to_owned(), e)),
+        }
+    }
+
+    let mut entries = scan_library(paths);
+    for (code, spec) in &manifest.parts {
+        let Some(name) = &spec.symbol else {
+            continue;
+        };
+        let symbol = entries
+            .iter()
+            .filter(|entry| entry.lcsc_code() == Some(code))
+            .find_map(|entry| entry.symbol.as_ref());
+        if let Some(symbol) = symbol.filter(|symbol| symbol.name != *name) {
+            rename_symbol(&symbol.library, &symbol.name, name)
+                .map_err(|e| format!("Could not rename the symbol of {}: {}", code, e))?;
+            report.renamed.push(code.clone());
+        }
+    }
+    if normalize {
+        normalize_libraries(paths).map_err(|e| format!("Could not normalize: {}", e))?;
+    }
+    if !report.renamed.is_empty() || normalize {
+        entries = scan_library(paths);
+    }
+
+    let lock = PartsLock::of(&manifest, &entries, output);
+    if let Ok(previous) = PartsLock::load(output) {
+        report.changed = lock
+            .parts
+            .iter()
+            .filter(|(code, files)| previous.parts.get(*code).is_some_and(|old| old != *files))
+            .map(|(code, _)| code.clone())
+            .collect();
+    }
+    lock.save(output)
+        .map_err(|e| format!("Could not write {}: {}", LOCK_FILE, e))?;
+    report.locked = lock.parts.len();
+    report.unlisted = manifest.unlisted(&entries);
+    Ok(report)
+}
+
+/// A difference between the library and its lock file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+    /// the file was edited by hand or generated by another converter
+    Changed {
+        code: String,
+        file: String,
+    },
+    Missing {
+        code: String,
+        file: String,
+    },
+    /// a file of the part the lock file does not know
+    Unexpected {
+        code: String,
+        file: String,
+    },
+    /// in the manifest and the lock file but not in the library
+    NotImported(String),
+    /// in the manifest but not in the lock file, sync was not run since
+    NotLocked(String),
+    /// in the lock file but no longer in the manifest
+    Removed(String),
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Changed { code, file } => write!(f, "{}: {} changed", code, file),
+            Drift::Missing { code, file } => write!(f, "{}: {} is missing", code, file),
+            Drift::Unexpected { code, file } => write!(f, "{}: {} is not locked", code, file),
+            Drift::NotImported(code) => write!(f, "{}: not in the library", code),
+            Drift::NotLocked(code) => write!(f, "{}: not in {}, run sync", code, LOCK_FILE),
+            Drift::Removed(code) => write!(f, "{}: no longer in {}, run sync", code, MANIFEST_FILE),
+        }
+    }
+}
+
+/// Compares the files of the library with the hashes of the lock file, without writing
+/// anything, e.g. in CI.
+pub fn verify(output: &Path, paths: &LibraryPaths) -> Result<Vec<Drift>, String> {
+    let manifest = Manifest::load(output)?;
+    let locked = PartsLock::load(output)?;
+    let current = PartsLock::of(&manifest, &scan_library(paths), output);
+    let mut drift = vec![];
+    for code in manifest.parts.keys() {
+        let (Some(locked), Some(current)) = (locked.parts.get(code), current.parts.get(code))
+        else {
+            drift.push(match locked.parts.get(code) {
+                Some(_) => Drift::NotImported(code.clone()),
+                None => Drift::NotLocked(code.clone()),
+            });
+            continue;
+        };
+        for (file, hash) in locked {
+            let (code, file) = (code.clone(), file.clone());
+            match current.get(&file) {
+                None => drift.push(Drift::Missing { code, file }),
+                Some(current) if current != hash => drift.push(Drift::Changed { code, file }),
+                Some(_) => {}
+            }
+        }
+        for file in current.keys().filter(|file| !locked.contains_key(*file)) {
+            drift.push(Drift::Unexpected {
+                code: code.clone(),
+                file: file.clone(),
+            });
+        }
+    }
+    for code in locked.parts.keys() {
+        if !manifest.parts.contains_key(code) {
+            drift.push(Drift::Removed(code.clone()));
+        }
+    }
+    Ok(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+
+    fn symbol(name: &str, code: &str) -> String {
+        format!(
+            "  (symbol \"{}\" (property \"Footprint\" \"footprint:R0402\") (property \"LCSC\" \"{}\"))\n",
+            name, code
+        )
+    }
+
+    #[test]
+    fn syncs_libraries() {
+        let manifest = Manifest::parse(
+            "# resistors\n[parts.c11702]\nsymbol = \"R_1k\"\n\n[parts.C25744]\nno_footprint = true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.parts["C11702"],
+            PartSpec {
+                symbol: Some("R_1k".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert!(manifest.parts["C25744"].no_footprint);
+        assert!(Manifest::parse("[parts.C1]\nsymbols = \"R\"\n").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths = LibraryPaths {
+            symbol_dir: dir.path().join("symbol"),
+            footprint_dir: dir.path().join("footprint"),
+            model_dir: dir.path().join("footprint/packages3d"),
+            datasheet_dir: dir.path().join("datasheets"),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        let library = paths.symbol_dir.join("default_lib.kicad_sym");
+        // one part of the manifest and one added by hand are in the library already
+        write(
+            &library,
+            format!(
+                "(kicad_symbol_lib\n{}{})\n",
+                symbol("0402WGF1001TCE", "C11702"),
+                symbol("LED", "C2286")
+            ),
+        )
+        .unwrap();
+        write(
+            dir.path().join(MANIFEST_FILE),
+            "[parts.C11702]\nsymbol = \"R_1k\"\n\n[parts.C25744]\n\n[parts.C99]\n",
+        )
+        .unwrap();
+
+        let mut imported = vec![];
+        let report = sync(dir.path(), &paths, false, |code, _| {
+            imported.push(code.to_owned());
+            if code == "C99" {
+                return Err("no such part".to_owned());
+            }
+            let text = read_to_string(&library).unwrap();
+            write(
+                &library,
+                text.replace(
+                    "(kicad_symbol_lib\n",
+                    &format!("(kicad_symbol_lib\n{}", symbol("R_10k", code)),
+                ),
+            )
+            .unwrap();
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(imported, vec!["C25744", "C99"]);
+        assert_eq!(report.imported, vec!["C25744"]);
+        assert_eq!(report.renamed, vec!["C11702"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.unlisted, vec!["C2286"]);
+        assert_eq!(report.locked, 2);
+        assert!(
+            read_to_string(&library)
+                .unwrap()
+                .contains("(symbol \"R_1k\"")
+        );
+
+        let lock = PartsLock::load(dir.path()).unwrap();
+        assert_eq!(
+            lock.parts["C11702"].keys().collect::<Vec<_>>(),
+            vec!["symbol/default_lib.kicad_sym#R_1k"]
+        );
+
+        append_to_manifest(dir.path(), &report.unlisted).unwrap();
+        let manifest = Manifest::load(dir.path()).unwrap();
+        assert_eq!(manifest.parts.len(), 4);
+        assert!(manifest.unlisted(&scan_library(&paths)).is_empty());
+
+        // the next sync only notices what changed by hand
+        let text = read_to_string(&library).unwrap();
+        write(
+            &library,
+            text.replace("\"R_10k\" (property", "\"R_10k\" (in_bom yes) (property"),
+        )
+        .unwrap();
+        let report = sync(dir.path(), &paths, false, |_, _| Err("offline".to_owned())).unwrap();
+        assert_eq!(report.changed, vec!["C25744"]);
+        assert_eq!(report.locked, 3);
+        assert_eq!(
+            verify(dir.path(), &paths).unwrap(),
+            vec![Drift::NotLocked("C99".to_owned())]
+        );
+
+        let text = read_to_string(&library).unwrap();
+        write(&library, text.replace("(in_bom yes) ", "")).unwrap();
+        write(
+            dir.path().join(MANIFEST_FILE),
+            "[parts.C11702]\n[parts.C25744]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            verify(dir.path(), &paths).unwrap(),
+            vec![
+                Drift::Changed {
+                    code: "C25744".to_owned(),
+                    file: "symbol/default_lib.kicad_sym#R_10k".to_owned()
+                },
+                Drift::Removed("C2286".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn verifies_without_datasheets() {
+        // the datasheets of the app live outside the versioned library
+        let dir = tempfile::tempdir().unwrap();
+        let datasheets = tempfile::tempdir().unwrap();
+        let paths = LibraryPaths {
+            symbol_dir: dir.path().join("symbol"),
+            footprint_dir: dir.path().join("footprint"),
+            model_dir: dir.path().join("footprint/packages3d"),
+            datasheet_dir: datasheets.path().to_owned(),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        write(
+            paths.symbol_dir.join("default_lib.kicad_sym"),
+            format!("(kicad_symbol_lib\n{})\n", symbol("R_1k", "C11702")),
+        )
+        .unwrap();
+        write(dir.path().join(MANIFEST_FILE), "[parts.C11702]\n").unwrap();
+        write(datasheets.path().join("2206010216_C11702.pdf"), "%PDF").unwrap();
+        let report = sync(dir.path(), &paths, false, |_, _| Err("offline".to_owned())).unwrap();
+        assert_eq!(report.locked, 1);
+        assert_eq!(
+            PartsLock::load(dir.path()).unwrap().parts["C11702"]
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["symbol/default_lib.kicad_sym#R_1k"]
+        );
+
+        // a checkout without the datasheet has not drifted
+        std::fs::remove_file(datasheets.path().join("2206010216_C11702.pdf")).unwrap();
+        assert_eq!(verify(dir.path(), &paths).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+
+use regex::Regex;
+
+use crate::lcsc_qr::parse_lcsc_label;
+
+/// Where a part number was taken from. Mostly useful for feedback in the UI
+/// and to make sure the parser picked the right branch in the tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartSource {
+    /// a plain `C123` number, in any case
+    Number,
+    /// a JLCPCB part detail page
+    Jlcpcb,
+    /// the JLCPCB parts library / component search
+    JlcpcbPartsLibrary,
+    /// the international LCSC shop, desktop or product-detail pages
+    Lcsc,
+    /// the mobile LCSC shop (m.lcsc.com)
+    LcscMobile,
+    /// the chinese LCSC shop (szlcsc.com)
+    Szlcsc,
+    /// an EasyEDA (or LCEDA) component link
+    EasyEda,
+    /// the QR code payload of an LCSC bag or reel label
+    LcscLabel,
+}
+
+impl fmt::Display for PartSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PartSource::Number => "part number",
+            PartSource::Jlcpcb => "JLCPCB URL",
+            PartSource::JlcpcbPartsLibrary => "JLCPCB parts library URL",
+            PartSource::Lcsc => "LCSC URL",
+            PartSource::LcscMobile => "LCSC mobile URL",
+            PartSource::Szlcsc => "SZLCSC URL",
+            PartSource::EasyEda => "EasyEDA URL",
+            PartSource::LcscLabel => "LCSC label",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A normalized LCSC part number (always upper case `C` followed by digits)
+/// together with the kind of input it was extracted from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartId {
+    pub lcsc_number: String,
+    pub source: PartSource,
+}
+
+impl PartId {
+    fn new(number: &str, source: PartSource) -> Self {
+        Self {
+            lcsc_number: number.to_uppercase(),
+            source,
+        }
+    }
+}
+
+/// Extracts the LCSC number from whatever the user typed or pasted into the search box.
+///
+/// Accepts bare numbers (`C11702`, `c11702`) as well as URLs from JLCPCB, LCSC (desktop,
+/// mobile and chinese shop) and EasyEDA. The scheme is optional for URLs. The QR code
+/// payload of LCSC labels, as typed by a barcode scanner, is understood as well.
+/// Returns `None` if nothing that looks like an LCSC number can be found.
+pub fn parse_part_id(input: &str) -> Option<PartId> {
+    let term = input.trim();
+    let re_lcscnumber = Regex::new(r"^[Cc]\d+$").unwrap();
+
+    // case one, it's the number directly
+    if re_lcscnumber.is_match(term) {
+        return Some(PartId::new(term, PartSource::Number));
+    }
+
+    // case two, a barcode scanner typed the QR code of an LCSC label
+    if let Some(label) = parse_lcsc_label(term) {
+        return Some(PartId::new(&label.product_code, PartSource::LcscLabel));
+    }
+
+    // case three, we got passed a URL, so split it up into host, path and query
+    // (the fragment is of no interest for any of the shops)
+    let without_scheme = match term.find("://") {
+        Some(idx) => &term[idx + 3..],
+        None => term,
+    };
+    let without_fragment = without_scheme.split('#').next().unwrap_or_default();
+    let (host_and_path, query) = match without_fragment.split_once('?') {
+        Some((hp, q)) => (hp, q),
+        None => (without_fragment, ""),
+    };
+    let (host, path) = match host_and_path.split_once('/') {
+        Some((h, p)) => (h, p),
+        None => (host_and_path, ""),
+    };
+    let host = host.to_lowercase();
+    let source = source_from_host(&host, path)?;
+
+    number_from_path(path)
+        .or_else(|| number_from_query(query))
+        .map(|number| PartId::new(&number, source))
+}
+
+fn source_from_host(host: &str, path: &str) -> Option<PartSource> {
+    // strip a potential port
+    let host = host.split(':').next().unwrap_or_default();
+    let is_domain = |domain: &str| host == domain || host.ends_with(&format!(".{}", domain));
+
+    if is_domain("jlcpcb.com") {
+        if path.starts_with("parts") {
+            Some(PartSource::JlcpcbPartsLibrary)
+        } else {
+            Some(PartSource::Jlcpcb)
+        }
+    } else if is_domain("szlcsc.com") {
+        Some(PartSource::Szlcsc)
+    } else if host == "m.lcsc.com" {
+        Some(PartSource::LcscMobile)
+    } else if is_domain("lcsc.com") {
+        Some(PartSource::Lcsc)
+    } else if is_domain("easyeda.com") || is_domain("lceda.cn") {
+        Some(PartSource::EasyEda)
+    } else {
+        None
+    }
+}
+
+/// Looks for the part number at the end of a path segment, which covers
+/// `/partdetail/C123`, `/product-detail/Some-Name_C123.html` and `/component/NAME-C123`.
+/// The segments are searched back to front, as the number usually sits at the end.
+fn number_from_path(path: &str) -> Option<String> {
+    let re_segment = Regex::new(r"(?i)(?:^|[_\-])(C\d+)(?:\.html?)?$").unwrap();
+    path.split('/')
+        .rev()
+        .filter(|segment| !segment.is_empty())
+        .find_map(|segment| {
+            let decoded = urlencoding::decode(segment).ok()?;
+            re_segment
+                .captures(&decoded)
+                .map(|captures| captures[1].to_owned())
+        })
+}
+
+/// Looks for the part number in query parameter values like `searchTxt=C123`,
+/// `k=C123`, `productCode=C123` or `s_z=n_C123`.
+fn number_from_query(query: &str) -> Option<String> {
+    let re_value = Regex::new(r"(?i)^(?:[a-z]+_)?(C\d+)$").unwrap();
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(_, value)| {
+            let decoded = urlencoding::decode(value).ok()?;
+            re_value
+                .captures(decoded.trim())
+                .map(|captures| captures[1].to_owned())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_supported_formats() {
+        let cases = [
+            // bare numbers
+            ("C11702", "C11702", PartSource::Number),
+            ("c11702", "C11702", PartSource::Number),
+            ("  C11702 \n", "C11702", PartSource::Number),
+            // JLCPCB part details
+            (
+                "https://jlcpcb.com/partdetail/Uniroyal_Elec-0402WGF1001TCE/C11702",
+                "C11702",
+                PartSource::Jlcpcb,
+            ),
+            (
+                "https://jlcpcb.com/partdetail/C11702",
+                "C11702",
+                PartSource::Jlcpcb,
+            ),
+            (
+                "https://jlcpcb.com/partdetail/Uniroyal_Elec-0402WGF1001TCE/C11702?utm_source=x",
+                "C11702",
+                PartSource::Jlcpcb,
+            ),
+            (
+                "jlcpcb.com/partdetail/Uniroyal_Elec-0402WGF1001TCE/c11702/",
+                "C11702",
+                PartSource::Jlcpcb,
+            ),
+            // JLCPCB parts library
+            (
+                "https://jlcpcb.com/parts/componentSearch?isSearch=true&searchTxt=C11702",
+                "C11702",
+                PartSource::JlcpcbPartsLibrary,
+            ),
+            (
+                "https://cart.jlcpcb.com/parts/componentSearch?searchTxt=c11702",
+                "C11702",
+                PartSource::JlcpcbPartsLibrary,
+            ),
+            // LCSC desktop
+            (
+                "https://www.lcsc.com/product-detail/Chip-Resistor-Surface-Mount_UNI-ROYAL-Uniroyal-Elec-0402WGF1001TCE_C11702.html",
+                "C11702",
+                PartSource::Lcsc,
+            ),
+            (
+                "https://www.lcsc.com/product-detail/C11702.html",
+                "C11702",
+                PartSource::Lcsc,
+            ),
+            (
+                "https://www.lcsc.com/product-detail/Chip-Resistor_UNI-ROYAL-0402WGF1001TCE_C11702.html?s_z=n_C11702#specs",
+                "C11702",
+                PartSource::Lcsc,
+            ),
+            (
+                "https://www.lcsc.com/search?q=C11702",
+                "C11702",
+                PartSource::Lcsc,
+            ),
+            (
+                "https://wmsc.lcsc.com/ftps/wm/product/detail?productCode=C11702",
+                "C11702",
+                PartSource::Lcsc,
+            ),
+            // LCSC mobile
+            (
+                "https://m.lcsc.com/product-detail/Chip-Resistor_UNI-ROYAL-0402WGF1001TCE_C11702.html",
+                "C11702",
+                PartSource::LcscMobile,
+            ),
+            (
+                "https://m.lcsc.com/product-detail/C11702.html?spm=abc",
+                "C11702",
+                PartSource::LcscMobile,
+            ),
+            // chinese LCSC
+            (
+                "https://item.szlcsc.com/C11702.html",
+                "C11702",
+                PartSource::Szlcsc,
+            ),
+            (
+                "https://so.szlcsc.com/global.html?k=C11702&hot-key=abc",
+                "C11702",
+                PartSource::Szlcsc,
+            ),
+            // EasyEDA
+            (
+                "https://easyeda.com/component/0402WGF1001TCE-C11702",
+                "C11702",
+                PartSource::EasyEda,
+            ),
+            (
+                "https://pro.lceda.cn/search?wd=C11702",
+                "C11702",
+                PartSource::EasyEda,
+            ),
+            // LCSC label QR codes
+            (
+                "{pbn:PICK2209150046,on:SO2209151379,pc:C11702,pm:0402WGF1001TCE,qty:100,mc:,cc:1}",
+                "C11702",
+                PartSource::LcscLabel,
+            ),
+        ];
+
+        for (input, number, source) in cases {
+            assert_eq!(
+                parse_part_id(input),
+                Some(PartId {
+                    lcsc_number: number.to_owned(),
+                    source
+                }),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unusable_input() {
+        let cases = [
+            "",
+            "   ",
+            "C",
+            "C12a",
+            "11702",
+            "resistor 10k",
+            // szlcsc item pages only carry an internal product id
+            "https://item.szlcsc.com/8729.html",
+            // unknown hosts are never guessed
+            "https://example.com/parts/C11702",
+            "https://www.lcsc.com/products/Resistors_439.html",
+            "https://jlcpcb.com/parts/basic_parts",
+            // a C-number that is only part of a word must not match
+            "https://www.lcsc.com/product-detail/ABC11702.html",
+        ];
+
+        for input in cases {
+            assert_eq!(parse_part_id(input), None, "input: {}", input);
+        }
+    }
+}
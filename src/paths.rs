@@ -0,0 +1,143 @@
+//! Expansion and checks of the path settings.
+
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+/// Expands a leading `~` to the home directory and `$VAR`, `${VAR}` and `$(VAR)` the way
+/// KiCad does, taking variables from `var`. Unknown variables are left alone.
+pub fn expand_path_with(path: &str, var: impl Fn(&str) -> Option<String>) -> PathBuf {
+    let re_variable = Regex::new(r"\$(?:\{(\w+)\}|\((\w+)\)|(\w+))").unwrap();
+    let path = re_variable.replace_all(path, |captures: &Captures<'_>| {
+        let name = (1..=3).find_map(|idx| captures.get(idx)).unwrap().as_str();
+        var(name).unwrap_or_else(|| captures[0].to_owned())
+    });
+    if let Some(rest) = path.strip_prefix('~') {
+        if rest.is_empty() || rest.starts_with(['/', '\\']) {
+            if let Some(home) = var("HOME").or_else(|| var("USERPROFILE")) {
+                return PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']));
+            }
+        }
+    }
+    PathBuf::from(path.into_owned())
+}
+
+/// Expands a path setting with variables from the environment, as the settings default to
+/// `~/kicad_libs/`.
+pub fn expand_path(path: &str) -> PathBuf {
+    expand_path_with(path, |name| std::env::var(name).ok())
+}
+
+/// Whether a directory setting can be used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DirCheck {
+    Writable,
+    /// missing, but gets created on the first import
+    Creatable,
+    NotADirectory,
+    ReadOnly,
+    /// missing, and so is every parent up to one that cannot be written to
+    Uncreatable,
+}
+
+impl DirCheck {
+    pub fn is_ok(self) -> bool {
+        matches!(self, DirCheck::Writable | DirCheck::Creatable)
+    }
+}
+
+impl std::fmt::Display for DirCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DirCheck::Writable => "exists",
+            DirCheck::Creatable => "will be created",
+            DirCheck::NotADirectory => "is not a directory",
+            DirCheck::ReadOnly => "is not writable",
+            DirCheck::Uncreatable => "cannot be created",
+        })
+    }
+}
+
+fn is_writable(dir: &Path) -> bool {
+    // permission bits do not tell about ACLs or read-only mounts, so this tries
+    tempfile::Builder::new()
+        .prefix(".easyeda_to_kicad_lib")
+        .tempfile_in(dir)
+        .is_ok()
+}
+
+pub fn check_dir(dir: &Path) -> DirCheck {
+    if dir.exists() {
+        return match (dir.is_dir(), is_writable(dir)) {
+            (false, _) => DirCheck::NotADirectory,
+            (true, false) => DirCheck::ReadOnly,
+            (true, true) => DirCheck::Writable,
+        };
+    }
+    let parent = dir
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("."));
+    if parent.is_dir() && is_writable(parent) {
+        DirCheck::Creatable
+    } else {
+        DirCheck::Uncreatable
+    }
+}
+
+/// Where an executable setting points to, looking it up in `PATH` if it is only a name.
+pub fn find_executable(exe: &Path) -> Option<PathBuf> {
+    if exe.components().count() > 1 || exe.is_absolute() {
+        return exe.is_file().then(|| exe.to_owned());
+    }
+    let names: Vec<PathBuf> = if cfg!(windows) {
+        ["", ".exe", ".cmd", ".bat"]
+            .iter()
+            .map(|ext| PathBuf::from(format!("{}{}", exe.display(), ext)))
+            .collect()
+    } else {
+        vec![exe.to_owned()]
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_and_checks_paths() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/ada".to_owned()),
+            "KIPRJMOD" => Some("/work/amp".to_owned()),
+            "LIBS" => Some("/srv/libs".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_path_with("~/kicad_libs/", var),
+            PathBuf::from("/home/ada/kicad_libs/")
+        );
+        assert_eq!(
+            expand_path_with("${KIPRJMOD}/lib/$(LIBS)/$LIBS", var),
+            PathBuf::from("/work/amp/lib//srv/libs//srv/libs")
+        );
+        assert_eq!(
+            expand_path_with("$UNSET/~x", var),
+            PathBuf::from("$UNSET/~x")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check_dir(dir.path()), DirCheck::Writable);
+        assert_eq!(check_dir(&dir.path().join("a/b")), DirCheck::Creatable);
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(check_dir(&file), DirCheck::NotADirectory);
+        assert!(!check_dir(&file.join("sub")).is_ok());
+        assert_eq!(find_executable(&file), Some(file.clone()));
+        assert_eq!(find_executable(&dir.path().join("missing")), None);
+    }
+}
@@ -0,0 +1,208 @@
+use indexmap::IndexMap;
+
+/// Largest quantity the calculator accepts, far beyond any real order.
+pub const MAX_QUANTITY: u64 = 100_000_000;
+
+/// One step of the price ladder: from `quantity` pieces on, each costs `unit_price` USD.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceBreak {
+    pub quantity: u64,
+    pub unit_price: f64,
+}
+
+/// Reads the price ladder from a JLCPCB (`prices`/`componentPrices` with `startNumber`)
+/// or LCSC (`productPriceList` with `ladder`) product JSON, sorted by quantity.
+pub fn price_breaks_from_json(data: &serde_json::Value) -> Vec<PriceBreak> {
+    let mut breaks: Vec<PriceBreak> = ["prices", "componentPrices", "productPriceList"]
+        .iter()
+        .filter_map(|key| data.get(key).and_then(|list| list.as_array()))
+        .flatten()
+        .filter_map(|entry| {
+            let quantity = ["startNumber", "ladder"]
+                .iter()
+                .find_map(|key| entry.get(key).and_then(|v| v.as_u64()))?;
+            let unit_price = ["productPrice", "usdPrice"]
+                .iter()
+                .find_map(|key| entry.get(key).and_then(json_number))?;
+            Some(PriceBreak {
+                quantity,
+                unit_price,
+            })
+        })
+        .collect();
+    breaks.sort_by_key(|price_break| price_break.quantity);
+    breaks.dedup_by_key(|price_break| price_break.quantity);
+    breaks
+}
+
+/// Prices sometimes come as numbers and sometimes as strings.
+fn json_number(value: &serde_json::Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+/// Everything needed to work out what an order of a part costs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    pub breaks: Vec<PriceBreak>,
+    /// minimum order quantity
+    pub minimum_order: u64,
+    /// cut tape is sold in multiples of this
+    pub order_multiple: u64,
+    /// pieces on a full reel, if known
+    pub reel_quantity: Option<u64>,
+}
+
+/// The cost of ordering a given quantity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    /// what actually has to be ordered after applying minimum and multiples
+    pub order_quantity: u64,
+    pub unit_price: f64,
+    pub extended_price: f64,
+    pub full_reels: u64,
+    pub cut_tape: u64,
+}
+
+impl PriceTable {
+    /// Reads the price information stored in the `meta_` entries of a looked up part.
+    pub fn from_part(part: &IndexMap<String, String>) -> Option<Self> {
+        let breaks: Vec<PriceBreak> = part
+            .iter()
+            .filter(|(key, _)| key.starts_with("meta_price_break"))
+            .filter_map(|(_, value)| {
+                let (quantity, unit_price) = value.split_once(':')?;
+                Some(PriceBreak {
+                    quantity: quantity.parse().ok()?,
+                    unit_price: unit_price.parse().ok()?,
+                })
+            })
+            .collect();
+        if breaks.is_empty() {
+            return None;
+        }
+        let number = |key: &str| part.get(key).and_then(|v| v.parse::<u64>().ok());
+        Some(Self {
+            minimum_order: number("Minimal Quantity")
+                .unwrap_or(breaks[0].quantity)
+                .max(1),
+            order_multiple: number("meta_order_multiple").unwrap_or(1).max(1),
+            reel_quantity: number("meta_reel_quantity").filter(|&qty| qty > 0),
+            breaks,
+        })
+    }
+
+    /// Quantity that really has to be ordered to get at least `quantity` pieces. Absurd
+    /// quantities end up at the largest multiple not above [`MAX_QUANTITY`].
+    pub fn order_quantity(&self, quantity: u64) -> u64 {
+        let quantity = quantity.max(self.minimum_order).min(MAX_QUANTITY);
+        let rounded = quantity.div_ceil(self.order_multiple) * self.order_multiple;
+        if rounded > MAX_QUANTITY {
+            (MAX_QUANTITY / self.order_multiple).max(1) * self.order_multiple
+        } else {
+            rounded
+        }
+    }
+
+    pub fn quote(&self, quantity: u64) -> Quote {
+        let order_quantity = self.order_quantity(quantity);
+        let unit_price = self
+            .breaks
+            .iter()
+            .rev()
+            .find(|price_break| price_break.quantity <= order_quantity)
+            .unwrap_or(&self.breaks[0])
+            .unit_price;
+        let (full_reels, cut_tape) = match self.reel_quantity {
+            Some(reel) => (order_quantity / reel, order_quantity % reel),
+            None => (0, order_quantity),
+        };
+        Quote {
+            order_quantity,
+            unit_price,
+            extended_price: unit_price * order_quantity as f64,
+            full_reels,
+            cut_tape,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    #[test]
+    fn parses_price_ladders() {
+        let jlc = serde_json::json!({
+            "prices": [
+                {"startNumber": 100, "endNumber": 999, "productPrice": 0.0009},
+                {"startNumber": 20, "endNumber": 99, "productPrice": "0.0012"},
+            ]
+        });
+        assert_eq!(
+            price_breaks_from_json(&jlc),
+            vec![
+                PriceBreak {
+                    quantity: 20,
+                    unit_price: 0.0012
+                },
+                PriceBreak {
+                    quantity: 100,
+                    unit_price: 0.0009
+                },
+            ]
+        );
+
+        let lcsc = serde_json::json!({
+            "productPriceList": [{"ladder": 10, "usdPrice": 0.05}]
+        });
+        assert_eq!(price_breaks_from_json(&lcsc).len(), 1);
+        assert!(price_breaks_from_json(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn quotes_respect_minimum_and_multiples() {
+        let part: IndexMap<String, String> = indexmap! {
+            "Minimal Quantity".to_owned() => "20".to_owned(),
+            "meta_price_break0".to_owned() => "20:0.0012".to_owned(),
+            "meta_price_break1".to_owned() => "200:0.0009".to_owned(),
+            "meta_price_break2".to_owned() => "10000:0.0005".to_owned(),
+            "meta_order_multiple".to_owned() => "10".to_owned(),
+            "meta_reel_quantity".to_owned() => "10000".to_owned(),
+        };
+        let table = PriceTable::from_part(&part).unwrap();
+
+        // below the minimum order quantity
+        let quote = table.quote(5);
+        assert_eq!(quote.order_quantity, 20);
+        assert_eq!(quote.unit_price, 0.0012);
+
+        // rounded up to the next multiple, which reaches the next price break
+        let quote = table.quote(195);
+        assert_eq!(quote.order_quantity, 200);
+        assert_eq!(quote.unit_price, 0.0009);
+        assert!((quote.extended_price - 0.18).abs() < 1e-9);
+
+        // reels and cut tape
+        let quote = table.quote(12345);
+        assert_eq!(quote.order_quantity, 12350);
+        assert_eq!(quote.unit_price, 0.0005);
+        assert_eq!((quote.full_reels, quote.cut_tape), (1, 2350));
+
+        // absurd quantities stay multiples
+        for order_multiple in [table.order_multiple, 3, 7] {
+            let table = PriceTable {
+                order_multiple,
+                ..table.clone()
+            };
+            let capped = table.quote(u64::MAX).order_quantity;
+            assert_eq!(capped % order_multiple, 0);
+            assert!(capped <= MAX_QUANTITY && capped > MAX_QUANTITY - order_multiple);
+        }
+
+        assert_eq!(PriceTable::from_part(&indexmap! {}), None);
+    }
+}
@@ -0,0 +1,118 @@
+/// Name of the profile settings from before profiles existed end up in.
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// The settings of one library, e.g. a personal one, the company one or one per customer.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Profile {
+    pub output_path: String,
+    pub symbol_lib: String,
+    pub symbol_lib_dir: String,
+    pub footprint_lib: String,
+    pub model_dir: String,
+    pub model_base_variable: String,
+    pub datasheet_dir: String,
+    pub download_datasheet: bool,
+    pub skip_existing: bool,
+    pub no_footprint: bool,
+    pub no_symbol: bool,
+    /// the `.kicad_pro` whose own libraries the profile imports into, if any
+    pub project: Option<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            output_path: "~/kicad_libs/".to_owned(),
+            symbol_lib: "default_lib".to_owned(),
+            symbol_lib_dir: "symbol".to_owned(),
+            footprint_lib: "footprint".to_owned(),
+            model_dir: "packages3d".to_owned(),
+            model_base_variable: "".to_owned(),
+            datasheet_dir: "~/kicad_libs/datasheets".to_owned(),
+            download_datasheet: true,
+            skip_existing: false,
+            no_footprint: false,
+            no_symbol: false,
+            project: None,
+        }
+    }
+}
+
+/// A profile as exported to a file, to hand it to a colleague.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ProfileFile {
+    name: String,
+    #[serde(flatten)]
+    profile: Profile,
+}
+
+impl Profile {
+    pub fn export(&self, name: &str) -> Result<String, String> {
+        toml::to_string(&ProfileFile {
+            name: name.to_owned(),
+            profile: self.clone(),
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    /// Reads an exported profile, returning its name and settings.
+    pub fn import(text: &str) -> Result<(String, Profile), String> {
+        let file: ProfileFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        Ok((file.name, file.profile))
+    }
+}
+
+/// A name not taken yet, e.g. "Company (2)" if there is a "Company" already.
+pub fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    let name = name.trim();
+    let name = if name.is_empty() {
+        DEFAULT_PROFILE
+    } else {
+        name
+    };
+    if !taken(name) {
+        return name.to_owned();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_profiles() {
+        let profile = Profile {
+            output_path: "~/customers/acme".to_owned(),
+            model_base_variable: "$ACME_3D".to_owned(),
+            no_footprint: true,
+            ..Default::default()
+        };
+        let text = profile.export("ACME").unwrap();
+        assert!(text.starts_with("name = \"ACME\"\n"));
+        assert_eq!(
+            Profile::import(&text).unwrap(),
+            ("ACME".to_owned(), profile)
+        );
+        // settings missing in the file keep their defaults
+        let (name, imported) = Profile::import("name = \"Old\"\nsymbol_lib = \"old\"\n").unwrap();
+        assert_eq!(name, "Old");
+        assert_eq!(imported.symbol_lib, "old");
+        assert_eq!(imported.footprint_lib, "footprint");
+        assert!(Profile::import("symbol_lib = \"nameless\"").is_err());
+
+        let taken = ["ACME", "ACME (2)"];
+        assert_eq!(
+            unique_name("ACME", |name| taken.contains(&name)),
+            "ACME (3)"
+        );
+        assert_eq!(
+            unique_name(" Personal ", |name| taken.contains(&name)),
+            "Personal"
+        );
+    }
+}
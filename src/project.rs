@@ -0,0 +1,258 @@
+use std::{
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    library_manager::{
+        LibraryPaths, files_with_extension, read_footprint, set_model_path_in, update_file,
+    },
+    lock::write_atomic,
+    profile::Profile,
+    sexpr::{self, Sexpr},
+};
+
+/// The variable KiCad sets to the directory of the open project.
+pub const PROJECT_VARIABLE: &str = "KIPRJMOD";
+
+/// Directory of the project libraries, relative to the project.
+const LIBRARY_DIR: &str = "lib";
+
+/// A KiCad project whose own libraries parts are imported into, so it does not depend on
+/// anyone's global library when handed over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub dir: PathBuf,
+    pub name: String,
+}
+
+impl Project {
+    pub fn open(file: &Path) -> Result<Self, String> {
+        if file.extension().and_then(|ext| ext.to_str()) != Some("kicad_pro") {
+            return Err(format!("{} is not a KiCad project", file.display()));
+        }
+        if !file.is_file() {
+            return Err(format!("{} does not exist", file.display()));
+        }
+        let dir = file
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Ok(Project {
+            dir: dir.canonicalize().unwrap_or(dir.to_owned()),
+            name: file
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Settings importing into `${KIPRJMOD}/lib`, one symbol and one footprint library
+    /// named after the project.
+    pub fn profile(&self, file: &Path) -> Profile {
+        let lib = self.dir.join(LIBRARY_DIR);
+        Profile {
+            output_path: lib.to_string_lossy().into_owned(),
+            symbol_lib: self.name.clone(),
+            symbol_lib_dir: "symbols".to_owned(),
+            footprint_lib: format!("{}.pretty", self.name),
+            model_dir: "3dmodels".to_owned(),
+            // absolute model paths are made relative to the project after each import
+            model_base_variable: String::new(),
+            datasheet_dir: lib.join("datasheets").to_string_lossy().into_owned(),
+            project: Some(file.to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// The path KiCad resolves to `path` inside the project, e.g. `${KIPRJMOD}/lib/x.wrl`.
+    fn relative_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        Some(format!(
+            "${{{}}}/{}",
+            PROJECT_VARIABLE,
+            relative.to_string_lossy().replace('\\', "/")
+        ))
+    }
+
+    /// Rewrites absolute model paths into the project as `${KIPRJMOD}/...`. Returns how
+    /// many footprints were changed.
+    pub fn relocate_models(&self, footprint_dir: &Path) -> io::Result<usize> {
+        let mut changed = 0;
+        for path in files_with_extension(footprint_dir, &["kicad_mod"]) {
+            let footprint = read_footprint(&path)?;
+            let moves: Vec<(String, String)> = footprint
+                .models
+                .iter()
+                .filter_map(|model| Some((model.clone(), self.relative_path(Path::new(model))?)))
+                .collect();
+            if !moves.is_empty() {
+                update_file(&path, |text| {
+                    Ok(moves.iter().fold(text.to_owned(), |text, (old, new)| {
+                        set_model_path_in(&text, old, new)
+                    }))
+                })?;
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Lists the project libraries in the project's `sym-lib-table` and `fp-lib-table`.
+    pub fn register_libraries(&self, paths: &LibraryPaths, symbol_lib: &str) -> io::Result<()> {
+        let symbol_library = paths.symbol_dir.join(format!("{}.kicad_sym", symbol_lib));
+        if let Some(uri) = self.relative_path(&symbol_library) {
+            add_to_lib_table(&self.dir.join("sym-lib-table"), symbol_lib, &uri)?;
+        }
+        if let Some(uri) = self.relative_path(&paths.footprint_dir) {
+            // the converter names the footprint library in the symbols after the directory
+            // it was given, `.pretty` included
+            let name = paths
+                .footprint_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or(self.name.clone());
+            add_to_lib_table(&self.dir.join("fp-lib-table"), &name, &uri)?;
+        }
+        Ok(())
+    }
+
+    /// Everything to do after the converter imported a part into the project.
+    pub fn finish_import(&self, paths: &LibraryPaths, symbol_lib: &str) -> io::Result<()> {
+        self.relocate_models(&paths.footprint_dir)?;
+        self.register_libraries(paths, symbol_lib)
+    }
+}
+
+/// The project a library directory belongs to, found by looking for a `.kicad_pro` in the
+/// directory and its parents, as `${KIPRJMOD}` only means something inside a project.
+pub fn project_dir_of(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| !files_with_extension(dir, &["kicad_pro"]).is_empty())
+        .map(Path::to_owned)
+}
+
+/// Adds a library to a KiCad library table, creating the table if needed. A library of the
+/// same name is left alone. Returns whether the table changed.
+pub fn add_to_lib_table(table: &Path, name: &str, uri: &str) -> io::Result<bool> {
+    let kind = match table.file_name().and_then(|name| name.to_str()) {
+        Some("fp-lib-table") => "fp_lib_table",
+        _ => "sym_lib_table",
+    };
+    let text = match read_to_string(table) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => format!("({}\n  (version 7)\n)\n", kind),
+        Err(e) => return Err(e),
+    };
+    let root = sexpr::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let listed = root.children("lib").any(|lib| {
+        lib.children("name")
+            .next()
+            .and_then(|lib_name| lib_name.arg(1))
+            == Some(name)
+    });
+    if listed {
+        return Ok(false);
+    }
+    let field = |key: &str, value: &str| {
+        Sexpr::List(vec![
+            Sexpr::Atom(key.to_owned()),
+            Sexpr::Str(value.to_owned()),
+        ])
+    };
+    let lib = Sexpr::List(vec![
+        Sexpr::Atom("lib".to_owned()),
+        field("name", name),
+        field("type", "KiCad"),
+        field("uri", uri),
+        field("options", ""),
+        field("descr", ""),
+    ]);
+    let body = text.trim_end();
+    let body = body.strip_suffix(')').unwrap_or(body).trim_end();
+    write_atomic(table, format!("{}\n  {}\n)\n", body, lib.canonical()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::library::read_symbols;
+
+    #[test]
+    fn imports_into_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("amp.kicad_pro");
+        write(&file, "{}").unwrap();
+        assert!(Project::open(&dir.path().join("amp.kicad_pcb")).is_err());
+        let project = Project::open(&file).unwrap();
+        assert_eq!(project.name, "amp");
+        let profile = project.profile(&file);
+        assert_eq!(profile.footprint_lib, "amp.pretty");
+
+        let lib = project.dir.join("lib");
+        let paths = LibraryPaths {
+            symbol_dir: lib.join("symbols"),
+            footprint_dir: lib.join("amp.pretty"),
+            model_dir: lib.join("amp.pretty/3dmodels"),
+            datasheet_dir: lib.join("datasheets"),
+        };
+        create_dir_all(&paths.footprint_dir).unwrap();
+        let footprint = paths.footprint_dir.join("R0402.kicad_mod");
+        write(
+            &footprint,
+            format!(
+                "(module R0402 (layer F.Cu)\n  (model \"{}\")\n  (model \"${{KICAD8_3DMODEL_DIR}}/R.wrl\")\n)\n",
+                paths.model_dir.join("R0402.wrl").display()
+            ),
+        )
+        .unwrap();
+        // the symbol as the converter writes it with the settings of the project
+        create_dir_all(&paths.symbol_dir).unwrap();
+        let symbol_library = paths.symbol_dir.join("amp.kicad_sym");
+        write(
+            &symbol_library,
+            format!(
+                "(kicad_symbol_lib\n  (symbol \"R_1k\" (property \"Footprint\" \"{}:R0402\") (property \"LCSC\" \"C11702\")))\n",
+                profile.footprint_lib
+            ),
+        )
+        .unwrap();
+        // KiCad's own table of an existing project
+        write(
+            project.dir.join("fp-lib-table"),
+            "(fp_lib_table\n  (version 7)\n  (lib (name \"Connectors\")(type \"KiCad\")(uri \"${KIPRJMOD}/con.pretty\")(options \"\")(descr \"\"))\n)\n",
+        )
+        .unwrap();
+
+        project.finish_import(&paths, "amp").unwrap();
+        assert_eq!(
+            read_footprint(&footprint).unwrap().models,
+            vec![
+                "${KIPRJMOD}/lib/amp.pretty/3dmodels/R0402.wrl",
+                "${KICAD8_3DMODEL_DIR}/R.wrl"
+            ]
+        );
+        assert_eq!(
+            read_to_string(project.dir.join("sym-lib-table")).unwrap(),
+            "(sym_lib_table\n  (version 7)\n  \
+             (lib (name \"amp\") (type \"KiCad\") (uri \"${KIPRJMOD}/lib/symbols/amp.kicad_sym\") (options \"\") (descr \"\"))\n)\n"
+        );
+        let fp_lib_table = read_to_string(project.dir.join("fp-lib-table")).unwrap();
+        assert!(fp_lib_table.contains("(name \"Connectors\")"));
+        // the footprint library is registered under the name the symbols use
+        let symbols = read_symbols(&symbol_library).unwrap();
+        let (nickname, _) = symbols[0].properties["Footprint"].split_once(':').unwrap();
+        assert!(fp_lib_table.contains(&format!(
+            "(lib (name \"{}\") (type \"KiCad\") (uri \"${{KIPRJMOD}}/lib/amp.pretty\")",
+            nickname
+        )));
+        // a second import changes nothing
+        assert!(!add_to_lib_table(&project.dir.join("sym-lib-table"), "amp", "x").unwrap());
+        assert_eq!(project.relocate_models(&paths.footprint_dir).unwrap(), 0);
+        assert_eq!(project_dir_of(&paths.model_dir), Some(project.dir.clone()));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    fmt::Write as _,
+    fs::{create_dir_all, read, read_to_string, remove_file},
+    io,
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+use sha2::{Digest, Sha256};
+use subprocess::{Exec, Redirection};
+
+use crate::{
+    library_manager::{LibraryEntry, symbol_text},
+    lock::{host_name, user_name, write_atomic},
+};
+
+/// Directory in the output directory of the library holding one sidecar per part.
+const PROVENANCE_DIR: &str = "provenance";
+
+/// Where a part of the library came from, stored next to it as `provenance/<LCSC>.json`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Provenance {
+    pub lcsc: String,
+    /// unix timestamp
+    pub imported: i64,
+    pub user: String,
+    pub host: String,
+    pub app_version: String,
+    pub converter: String,
+    /// `None` if the converter does not tell
+    pub converter_version: Option<String>,
+    /// the command line the converter ran with, which holds all library settings
+    pub converter_args: Vec<String>,
+    pub datasheet_url: Option<String>,
+    /// everything LCSC returned for the part when it was looked up
+    pub part: IndexMap<String, String>,
+    /// SHA-256 of the generated files, by path relative to the output directory, symbols
+    /// as `symbol/default_lib.kicad_sym#R_1k` as they share a file
+    pub files: IndexMap<String, String>,
+}
+
+impl Provenance {
+    pub fn path(output: &Path, code: &str) -> PathBuf {
+        output.join(PROVENANCE_DIR).join(format!("{}.json", code))
+    }
+
+    pub fn load(output: &Path, code: &str) -> io::Result<Self> {
+        let text = read_to_string(Self::path(output, code))?;
+        serde_json::from_str(&text).map_err(io::Error::other)
+    }
+
+    pub fn save(&self, output: &Path) -> io::Result<()> {
+        let path = Self::path(output, &self.lcsc);
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        write_atomic(&path, text + "\n")
+    }
+
+    /// Removes the sidecar of a part deleted from the library, if it has one.
+    pub fn remove(output: &Path, code: &str) -> io::Result<()> {
+        match remove_file(Self::path(output, code)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Records who imported a part, when and how, hashing its files as they are now.
+pub fn record(
+    entry: &LibraryEntry,
+    output: &Path,
+    converter: &str,
+    converter_args: &[String],
+    part: Option<&IndexMap<String, String>>,
+    datasheet_url: Option<&str>,
+) -> Option<Provenance> {
+    Some(Provenance {
+        lcsc: entry.lcsc_code()?.to_owned(),
+        imported: jiff::Timestamp::now().as_second(),
+        user: user_name(),
+        host: host_name(),
+        app_version: env!("CARGO_PKG_VERSION").to_owned(),
+        converter: Path::new(converter)
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(converter.to_owned()),
+        converter_version: converter_version(converter),
+        converter_args: converter_args.to_vec(),
+        datasheet_url: datasheet_url.map(str::to_owned),
+        part: part.cloned().unwrap_or_default(),
+        files: part_hashes(entry, output),
+    })
+}
+
+/// Asks the converter for its version, which not every release of JLC2KiCadLib answers.
+pub fn converter_version(exe: &str) -> Option<String> {
+    let output = Exec::cmd(exe)
+        .arg("--version")
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Merge)
+        .capture()
+        .ok()?;
+    let version = output.stdout_str();
+    let version = version.lines().last()?.trim();
+    (output.success() && !version.is_empty()).then(|| version.to_owned())
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(data) {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn relative(path: &Path, output: &Path) -> String {
+    path.strip_prefix(output)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Hashes the files of a part. The symbol is hashed as written in its library, so other
+/// parts added to the same library do not change it.
+pub fn part_hashes(entry: &LibraryEntry, output: &Path) -> IndexMap<String, String> {
+    hash_files(entry, output, |_| true)
+}
+
+/// Hashes the files of a part for a lock file shared between machines: only what the
+/// converter generated into `output`, keyed by paths relative to it. Datasheets are left
+/// out, as only the app downloads them.
+pub fn locked_hashes(entry: &LibraryEntry, output: &Path) -> IndexMap<String, String> {
+    let entry = LibraryEntry {
+        datasheet: None,
+        ..entry.clone()
+    };
+    hash_files(&entry, output, |path| path.starts_with(output))
+}
+
+fn hash_files(
+    entry: &LibraryEntry,
+    output: &Path,
+    include: impl Fn(&Path) -> bool,
+) -> IndexMap<String, String> {
+    let mut hashes = IndexMap::new();
+    if let Some(symbol) = entry
+        .symbol
+        .as_ref()
+        .filter(|symbol| include(&symbol.library))
+    {
+        if let Some(text) = read_to_string(&symbol.library)
+            .ok()
+            .as_deref()
+            .and_then(|text| symbol_text(text, &symbol.name))
+        {
+            hashes.insert(
+                format!("{}#{}", relative(&symbol.library, output), symbol.name),
+                sha256_hex(text.as_bytes()),
+            );
+        }
+    }
+    let files = entry
+        .footprint
+        .iter()
+        .map(|footprint| &footprint.path)
+        .chain(&entry.models)
+        .chain(&entry.datasheet)
+        .filter(|path| include(path));
+    for path in files {
+        if let Ok(data) = read(path) {
+            hashes.insert(relative(path, output), sha256_hex(&data));
+        }
+    }
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::library_manager::{LibraryPaths, scan_library};
+
+    #[test]
+    fn records_provenance() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths = LibraryPaths {
+            symbol_dir: dir.path().join("symbol"),
+            footprint_dir: dir.path().join("footprint"),
+            model_dir: dir.path().join("footprint/packages3d"),
+            datasheet_dir: dir.path().join("datasheets"),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        create_dir_all(&paths.footprint_dir).unwrap();
+        write(
+            paths.symbol_dir.join("default_lib.kicad_sym"),
+            "(kicad_symbol_lib\n  (symbol \"R_1k\" (property \"Footprint\" \"footprint:R0402\")\n    \
+             (property \"LCSC\" \"C11702\"))\n)\n",
+        )
+        .unwrap();
+        write(
+            paths.footprint_dir.join("R0402.kicad_mod"),
+            "(module R0402 (layer F.Cu))\n",
+        )
+        .unwrap();
+
+        let entries = scan_library(&paths);
+        let part = IndexMap::from([("Model".to_owned(), "0402WGF1001TCE".to_owned())]);
+        let provenance = record(
+            &entries[0],
+            dir.path(),
+            "/usr/bin/JLC2KiCadLib",
+            &["C11702".to_owned()],
+            Some(&part),
+            Some("https://www.lcsc.com/datasheet/C11702.pdf"),
+        )
+        .unwrap();
+        assert_eq!(provenance.converter, "JLC2KiCadLib");
+        assert_eq!(
+            provenance.files.keys().collect::<Vec<_>>(),
+            vec![
+                "symbol/default_lib.kicad_sym#R_1k",
+                "footprint/R0402.kicad_mod"
+            ]
+        );
+        assert_eq!(
+            provenance.files["footprint/R0402.kicad_mod"],
+            sha256_hex(b"(module R0402 (layer F.Cu))\n")
+        );
+
+        provenance.save(dir.path()).unwrap();
+        assert_eq!(Provenance::load(dir.path(), "C11702").unwrap(), provenance);
+        Provenance::remove(dir.path(), "C11702").unwrap();
+        assert!(Provenance::load(dir.path(), "C11702").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Reads the QR codes on LCSC bags and reels from photos or screenshots.
+
+use image::{DynamicImage, GenericImageView, imageops::FilterType};
+
+/// Larger images are scaled down before decoding, phone photos are way bigger than needed.
+const MAX_IMAGE_DIMENSION: u32 = 2000;
+
+/// Decodes all QR codes that can be found in the image and returns their payloads.
+pub fn decode_qr_codes(image: &DynamicImage) -> Vec<String> {
+    let (width, height) = image.dimensions();
+    let image = if width.max(height) > MAX_IMAGE_DIMENSION {
+        image.resize(
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION,
+            FilterType::Triangle,
+        )
+    } else {
+        image.clone()
+    };
+    let gray = image.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        gray.width() as usize,
+        gray.height() as usize,
+        |x, y| gray.get_pixel(x as u32, y as u32)[0],
+    );
+    prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| grid.decode().ok())
+        .map(|(_, payload)| payload)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma, imageops};
+    use qrcode::{Color, EcLevel, QrCode};
+
+    use super::*;
+
+    /// Renders a QR code with a quiet zone into a grayscale image.
+    fn render(code: &QrCode, module_pixels: u32) -> GrayImage {
+        let width = code.width() as u32;
+        let colors = code.to_colors();
+        let size = (width + 8) * module_pixels;
+        GrayImage::from_fn(size, size, |x, y| {
+            let (mx, my) = (x / module_pixels, y / module_pixels);
+            let dark = (4..width + 4).contains(&mx)
+                && (4..width + 4).contains(&my)
+                && colors[((my - 4) * width + mx - 4) as usize] == Color::Dark;
+            Luma([if dark { 20 } else { 235 }])
+        })
+    }
+
+    const LABEL: &str = "{pbn:PICK2209150046,on:SO2209151379,pc:C11702,pm:0402WGF1001TCE,qty:100,mc:,cc:1,pdi:67033862,hp:0,wc:JS}";
+
+    #[test]
+    fn decodes_rendered_codes() {
+        for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+            let code = QrCode::with_error_correction_level(LABEL, level).unwrap();
+            let image = DynamicImage::ImageLuma8(render(&code, 4));
+            assert_eq!(
+                decode_qr_codes(&image),
+                vec![LABEL.to_owned()],
+                "{:?}",
+                level
+            );
+        }
+        // numeric and alphanumeric segments, and a large version with version information
+        for payload in ["0123456789012345", "HELLO WORLD $%*+-./:", &LABEL.repeat(8)] {
+            let code = QrCode::new(payload).unwrap();
+            let image = DynamicImage::ImageLuma8(render(&code, 3));
+            assert_eq!(decode_qr_codes(&image), vec![payload.to_owned()]);
+        }
+    }
+
+    #[test]
+    fn decodes_rotated_and_damaged_codes() {
+        let code = QrCode::with_error_correction_level(LABEL, EcLevel::M).unwrap();
+        let mut image = render(&code, 5);
+        // scribble over a few data modules, error correction has to deal with it
+        for x in 60..80 {
+            for y in 100..104 {
+                image.put_pixel(x, y, Luma([20]));
+            }
+        }
+        // and a non-integer scale so that modules do not line up with pixels
+        let image = imageops::rotate90(&image);
+        let image = imageops::resize(
+            &image,
+            image.width() * 13 / 10,
+            image.height() * 13 / 10,
+            imageops::FilterType::Triangle,
+        );
+        assert_eq!(
+            decode_qr_codes(&DynamicImage::ImageLuma8(image)),
+            vec![LABEL.to_owned()]
+        );
+    }
+
+    #[test]
+    fn decodes_multiple_codes() {
+        let first = render(&QrCode::new("{pc:C11702,qty:100}").unwrap(), 4);
+        let second = render(&QrCode::new("{pc:C8734,qty:5}").unwrap(), 6);
+        let mut sheet =
+            GrayImage::from_pixel(first.width() + second.width() + 40, 300, Luma([235]));
+        imageops::overlay(&mut sheet, &first, 0, 10);
+        imageops::overlay(&mut sheet, &second, first.width() as i64 + 40, 60);
+
+        let mut payloads = decode_qr_codes(&DynamicImage::ImageLuma8(sheet));
+        payloads.sort();
+        assert_eq!(payloads, vec!["{pc:C11702,qty:100}", "{pc:C8734,qty:5}"]);
+    }
+
+    #[test]
+    fn finds_nothing_in_blank_images() {
+        let image = GrayImage::from_pixel(200, 200, Luma([235]));
+        assert!(decode_qr_codes(&DynamicImage::ImageLuma8(image)).is_empty());
+    }
+}
@@ -0,0 +1,388 @@
+use std::{fmt, sync::LazyLock};
+
+use indexmap::IndexMap;
+use regex::Regex;
+
+/// Units we understand, everything else is not treated as a quantity.
+const UNITS: [&str; 15] = [
+    "", "Ω", "F", "H", "V", "A", "W", "Hz", "%", "°C", "ppm", "ppm/°C", "dB", "s", "VA",
+];
+
+/// A physical value parsed from an attribute like "10kΩ", "±1%" or "62.5mW", in SI base units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: String,
+    /// relative tolerance in percent, if the text carried one, e.g. "10µF ±10%"
+    pub tolerance: Option<f64>,
+}
+
+impl Quantity {
+    /// Equal in unit and value, allowing for floating point noise from the prefix scaling.
+    pub fn approx_eq(&self, other: &Quantity) -> bool {
+        self.unit == other.unit
+            && (self.value - other.value).abs() <= 1e-9 * self.value.abs().max(other.value.abs())
+    }
+}
+
+impl fmt::Display for Quantity {
+    /// Formats the value with an SI prefix, e.g. 0.00001 F as "10µF".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefixes = [
+            (1e9, "G"),
+            (1e6, "M"),
+            (1e3, "k"),
+            (1.0, ""),
+            (1e-3, "m"),
+            (1e-6, "µ"),
+            (1e-9, "n"),
+            (1e-12, "p"),
+        ];
+        let magnitude = self.value.abs();
+        let (scale, prefix) = match self.unit.as_str() {
+            // these are never prefixed
+            "%" | "°C" | "ppm" | "ppm/°C" | "dB" => (1.0, ""),
+            _ => prefixes
+                .iter()
+                .copied()
+                .find(|(scale, _)| magnitude >= *scale * 0.999_999_999)
+                .unwrap_or((1.0, "")),
+        };
+        // round away the floating point noise of the scaling
+        let value = format!("{:.6}", self.value / scale);
+        let value = value.trim_end_matches('0').trim_end_matches('.');
+        write!(f, "{}{}{}", value, prefix, self.unit)?;
+        if let Some(tolerance) = self.tolerance {
+            write!(f, " ±{}%", tolerance)?;
+        }
+        Ok(())
+    }
+}
+
+fn prefix_scale(prefix: &str) -> Option<f64> {
+    Some(match prefix {
+        "" => 1.0,
+        "p" => 1e-12,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" | "K" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        _ => return None,
+    })
+}
+
+/// Brings the different spellings of units and prefixes into one form and drops whitespace.
+fn normalize(text: &str) -> String {
+    static RE_OHM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)ohms?").unwrap());
+    let text = RE_OHM.replace_all(text, "Ω");
+    text.replace(['µ', 'μ'], "u")
+        // the ohm sign looks just like a greek omega
+        .replace('Ω', "Ω")
+        .replace('℃', "°C")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// Parses one value, `None` if the text does not start with a number and a known unit.
+/// Only the first value of things like "1.1V@800mA" or "-55℃~+155℃" is taken.
+pub fn parse_quantity(text: &str) -> Option<Quantity> {
+    // number, optional prefix (also used as decimal point in "4k7" or "4R7"), digits, unit
+    static RE_QUANTITY: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^([+\-±]?)(\d+(?:\.\d+)?|\.\d+)([pnumkKMGR]?)(\d*)(.*)$").unwrap()
+    });
+    static RE_TOLERANCE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"±(\d+(?:\.\d+)?)%").unwrap());
+
+    let text = normalize(text);
+    let first = text
+        .split([',', '~', '@', ';', '(', '（'])
+        .next()
+        .unwrap_or_default();
+    // a tolerance given together with a value, like "10uF±10%"
+    let (first, tolerance) = match RE_TOLERANCE.captures(first) {
+        Some(captures) if !first.starts_with('±') => (
+            &first[..captures.get(0).unwrap().start()],
+            captures[1].parse().ok(),
+        ),
+        _ => (first, None),
+    };
+    let captures = RE_QUANTITY.captures(first)?;
+    let sign = if &captures[1] == "-" { -1.0 } else { 1.0 };
+    let mut number = captures[2].to_owned();
+    let mut prefix = &captures[3];
+    let digits = &captures[4];
+    let mut unit = captures[5].to_owned();
+    if !digits.is_empty() {
+        // the prefix is the decimal point, R meaning no prefix at all
+        number = format!("{}.{}", number.trim_end_matches('.'), digits);
+        if prefix == "R" {
+            prefix = "";
+            if unit.is_empty() {
+                unit = "Ω".to_owned();
+            }
+        }
+    } else if prefix == "R" {
+        // "47R"
+        prefix = "";
+        if !unit.is_empty() {
+            return None;
+        }
+        unit = "Ω".to_owned();
+    }
+    if !UNITS.contains(&unit.as_str()) {
+        // "100ppm" starts with something that looks like a prefix, but is not
+        let combined = format!("{}{}", prefix, unit);
+        if digits.is_empty() && UNITS.contains(&combined.as_str()) {
+            prefix = "";
+            unit = combined;
+        } else {
+            return None;
+        }
+    }
+    let value: f64 = number.parse().ok()?;
+    Some(Quantity {
+        value: sign * value * prefix_scale(prefix)?,
+        unit,
+        tolerance,
+    })
+}
+
+/// Whether two attribute values mean the same, numerically if both are quantities
+/// ("1kΩ" and "1000Ω"), otherwise ignoring case and whitespace.
+pub fn same_value(a: &str, b: &str) -> bool {
+    match (parse_quantity(a), parse_quantity(b)) {
+        (Some(a), Some(b)) => a.approx_eq(&b) && a.tolerance == b.tolerance,
+        _ => {
+            let simplify = |text: &str| {
+                text.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>()
+                    .to_lowercase()
+            };
+            simplify(a) == simplify(b)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+/// One condition of a part filter, e.g. "≥16V".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub comparison: Comparison,
+    pub quantity: Quantity,
+}
+
+impl Condition {
+    pub fn matches(&self, quantity: &Quantity) -> bool {
+        if quantity.unit != self.quantity.unit {
+            return false;
+        }
+        let equal = quantity.approx_eq(&self.quantity);
+        match self.comparison {
+            Comparison::Less => quantity.value < self.quantity.value && !equal,
+            Comparison::LessOrEqual => quantity.value <= self.quantity.value || equal,
+            Comparison::Equal => equal,
+            Comparison::GreaterOrEqual => quantity.value >= self.quantity.value || equal,
+            Comparison::Greater => quantity.value > self.quantity.value && !equal,
+        }
+    }
+}
+
+/// A filter like "capacitors ≥10µF, ≥16V": words have to appear somewhere in the part,
+/// conditions have to be met by at least one of its attributes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartFilter {
+    pub words: Vec<String>,
+    pub conditions: Vec<Condition>,
+}
+
+impl PartFilter {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // "≥ 16V" is the same as "≥16V"
+        static RE_OPERATOR: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"(>=|<=|[<>=≥≤])\s+").unwrap());
+        let text = RE_OPERATOR.replace_all(text, "$1");
+        let mut filter = Self::default();
+        for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+            let (comparison, rest) = [
+                (">=", Comparison::GreaterOrEqual),
+                ("<=", Comparison::LessOrEqual),
+                ("≥", Comparison::GreaterOrEqual),
+                ("≤", Comparison::LessOrEqual),
+                (">", Comparison::Greater),
+                ("<", Comparison::Less),
+                ("=", Comparison::Equal),
+            ]
+            .iter()
+            .find_map(|(operator, comparison)| {
+                token
+                    .strip_prefix(operator)
+                    .map(|rest| (Some(*comparison), rest))
+            })
+            .unwrap_or((None, token));
+            match (comparison, parse_quantity(rest)) {
+                (Some(comparison), Some(quantity)) => filter.conditions.push(Condition {
+                    comparison,
+                    quantity,
+                }),
+                (Some(_), None) => return Err(format!("\"{}\" is not a value with a unit", rest)),
+                // a value without comparison, e.g. "10k"
+                (None, Some(quantity)) if !quantity.unit.is_empty() || rest != token => {
+                    filter.conditions.push(Condition {
+                        comparison: Comparison::Equal,
+                        quantity,
+                    })
+                }
+                _ => filter.words.push(token.to_lowercase()),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.conditions.is_empty()
+    }
+
+    /// Checks a looked up part (or library symbol properties), `meta_` entries are ignored.
+    pub fn matches(&self, part: &IndexMap<String, String>) -> bool {
+        let values: Vec<&String> = part
+            .iter()
+            .filter(|(key, _)| !key.starts_with("meta_"))
+            .map(|(_, value)| value)
+            .collect();
+        let quantities: Vec<Quantity> = values.iter().filter_map(|v| parse_quantity(v)).collect();
+        self.words.iter().all(|word| {
+            // "capacitors" should also find a "Capacitor"
+            let singular = word.strip_suffix('s').unwrap_or(word);
+            values
+                .iter()
+                .any(|value| value.to_lowercase().contains(singular))
+        }) && self.conditions.iter().all(|condition| {
+            quantities
+                .iter()
+                .any(|quantity| condition.matches(quantity))
+        })
+    }
+
+    /// The value that met the first condition, to sort results numerically.
+    pub fn first_match(&self, part: &IndexMap<String, String>) -> Option<Quantity> {
+        let condition = self.conditions.first()?;
+        part.iter()
+            .filter(|(key, _)| !key.starts_with("meta_"))
+            .filter_map(|(_, value)| parse_quantity(value))
+            .find(|quantity| condition.matches(quantity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    fn quantity(text: &str) -> (f64, String) {
+        let quantity = parse_quantity(text).unwrap_or_else(|| panic!("not parsed: {}", text));
+        (quantity.value, quantity.unit)
+    }
+
+    fn assert_quantity(text: &str, value: f64, unit: &str) {
+        let (parsed, parsed_unit) = quantity(text);
+        assert!(
+            (parsed - value).abs() <= 1e-9 * value.abs(),
+            "{}: {} != {}",
+            text,
+            parsed,
+            value
+        );
+        assert_eq!(parsed_unit, unit, "{}", text);
+    }
+
+    #[test]
+    fn parses_attribute_values() {
+        assert_quantity("10kΩ", 10e3, "Ω");
+        assert_quantity("1 kohm", 1e3, "Ω");
+        assert_quantity("1kΩ", 1e3, "Ω");
+        assert_quantity("4k7", 4.7e3, "");
+        assert_quantity("4R7", 4.7, "Ω");
+        assert_quantity("47R", 47.0, "Ω");
+        assert_quantity("±1%", 1.0, "%");
+        assert_quantity("62.5mW", 62.5e-3, "W");
+        assert_quantity("50V", 50.0, "V");
+        assert_quantity("10µF", 10e-6, "F");
+        assert_quantity("10μF", 10e-6, "F");
+        assert_quantity("2.2uH", 2.2e-6, "H");
+        assert_quantity("100pF", 100e-12, "F");
+        assert_quantity("16MHz", 16e6, "Hz");
+        assert_quantity("100ppm/℃", 100.0, "ppm/°C");
+        assert_quantity("-55℃~+155℃", -55.0, "°C");
+        assert_quantity("1.1V@800mA", 1.1, "V");
+        assert_quantity("3A", 3.0, "A");
+        assert_eq!(parse_quantity("10µF ±10%").unwrap().tolerance, Some(10.0));
+
+        for text in ["", "-", "SOT-223", "X7R", "0402", "Tape & Reel", "C11702"] {
+            if let Some(quantity) = parse_quantity(text) {
+                // plain numbers are fine, but nothing with a made up unit
+                assert_eq!(quantity.unit, "", "{}", text);
+            }
+        }
+        assert_eq!(parse_quantity("X7R"), None);
+        assert_eq!(parse_quantity("SOT-223"), None);
+    }
+
+    #[test]
+    fn compares_values() {
+        assert!(same_value("1kΩ", "1000 Ohm"));
+        assert!(same_value("100nF", "0.1uF"));
+        assert!(!same_value("100nF", "100pF"));
+        assert!(same_value("SOT-23", "sot-23"));
+        assert!(!same_value("±1%", "±5%"));
+    }
+
+    #[test]
+    fn formats_with_prefixes() {
+        for (text, formatted) in [
+            ("10µF", "10µF"),
+            ("0.1uF", "100nF"),
+            ("4k7", "4.7k"),
+            ("62.5mW", "62.5mW"),
+            ("±1%", "1%"),
+            ("16MHz", "16MHz"),
+        ] {
+            assert_eq!(parse_quantity(text).unwrap().to_string(), formatted);
+        }
+    }
+
+    #[test]
+    fn filters_parts() {
+        let capacitor: IndexMap<String, String> = indexmap! {
+            "Primary Category".to_owned() => "Capacitors".to_owned(),
+            "Capacitance".to_owned() => "22uF".to_owned(),
+            "Voltage Rated".to_owned() => "25V".to_owned(),
+            "meta_source".to_owned() => "5V".to_owned(),
+        };
+        let filter = PartFilter::parse("capacitors ≥ 10µF, >=16V").unwrap();
+        assert_eq!(filter.words, vec!["capacitors"]);
+        assert_eq!(filter.conditions.len(), 2);
+        assert!(filter.matches(&capacitor));
+        assert_eq!(filter.first_match(&capacitor).unwrap().to_string(), "22µF");
+        assert!(!PartFilter::parse("≥50V").unwrap().matches(&capacitor));
+        assert!(!PartFilter::parse("<=5V").unwrap().matches(&capacitor));
+        assert!(PartFilter::parse("22µF").unwrap().matches(&capacitor));
+        assert!(!PartFilter::parse("resistor").unwrap().matches(&capacitor));
+        assert!(PartFilter::parse("≥ banana").is_err());
+    }
+}
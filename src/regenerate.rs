@@ -0,0 +1,347 @@
+use std::{
+    fs::{create_dir_all, read, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+
+use subprocess::{Exec, Redirection};
+
+use crate::{
+    library::read_library,
+    library_manager::{
+        LibraryEntry, LibraryPaths, files_with_extension, footprint_name, model_stem,
+        read_footprint, relink_footprint_in, rename_footprint_in, rename_symbol_in,
+        replace_symbol_in, symbol_text, update_file,
+    },
+    lock::write_atomic,
+};
+
+/// A library file that regenerating a part would change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    /// the symbol library or footprint file that is written
+    pub target: PathBuf,
+    /// name of the symbol in the target library, for symbols
+    pub name: String,
+    /// the current content, `None` if it does not exist yet
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// What regenerating a part with the current converter and settings changes in the library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartDiff {
+    pub code: String,
+    pub symbol: Option<FileChange>,
+    pub footprint: Option<FileChange>,
+    /// model files that are new or differ, staged file and where it goes
+    pub models: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PartDiff {
+    pub fn is_empty(&self) -> bool {
+        self.symbol.is_none() && self.footprint.is_none() && self.models.is_empty()
+    }
+
+    /// What changed in a few words, e.g. "symbol, footprint, 2 models".
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        if self.symbol.is_some() {
+            parts.push("symbol".to_owned());
+        }
+        if self.footprint.is_some() {
+            parts.push("footprint".to_owned());
+        }
+        match self.models.len() {
+            0 => {}
+            1 => parts.push("1 model".to_owned()),
+            n => parts.push(format!("{} models", n)),
+        }
+        if parts.is_empty() {
+            "unchanged".to_owned()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Runs the converter and waits for it, unlike adding parts which happens in the background.
+pub fn run_converter(exe: &str, args: &[String]) -> Result<(), String> {
+    let output = Exec::cmd(exe)
+        .args(args)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Merge)
+        .capture()
+        .map_err(|e| format!("could not run {}: {}", exe, e))?;
+    if output.success() {
+        Ok(())
+    } else {
+        Err(output.stdout_str().trim().to_owned())
+    }
+}
+
+/// Compares what the converter generated into `staged` for a part with its `entry` in the
+/// live library. Names the user gave the symbol or footprint in the library are kept.
+pub fn diff_part(
+    code: &str,
+    entry: Option<&LibraryEntry>,
+    staged: &LibraryPaths,
+    live: &LibraryPaths,
+    symbol_lib: &str,
+) -> Result<PartDiff, String> {
+    let staged_symbol = read_library(&staged.symbol_dir)
+        .into_iter()
+        .find(|symbol| symbol.lcsc_code() == Some(code));
+    let live_symbol = entry.and_then(|entry| entry.symbol.as_ref());
+    let live_footprint = entry.and_then(|entry| entry.footprint.as_ref());
+
+    let staged_footprint_name = staged_symbol
+        .as_ref()
+        .and_then(footprint_name)
+        .map(str::to_owned)
+        .or_else(|| live_footprint.map(|footprint| footprint.name.clone()));
+    let staged_footprint = staged_footprint_name.as_ref().and_then(|name| {
+        files_with_extension(&staged.footprint_dir, &["kicad_mod"])
+            .iter()
+            .filter_map(|path| read_footprint(path).ok())
+            .find(|footprint| footprint.name == *name)
+    });
+    if staged_symbol.is_none() && staged_footprint.is_none() {
+        return Err("the converter generated nothing".to_owned());
+    }
+
+    let mut diff = PartDiff {
+        code: code.to_owned(),
+        symbol: None,
+        footprint: None,
+        models: vec![],
+    };
+
+    if let Some(footprint) = &staged_footprint {
+        let text = read_to_string(&footprint.path).map_err(|e| e.to_string())?;
+        // model paths without base variable point into the staging directory
+        let mut new = text.replace(
+            &*staged.footprint_dir.to_string_lossy(),
+            &live.footprint_dir.to_string_lossy(),
+        );
+        let target = match live_footprint {
+            Some(live_footprint) => {
+                if live_footprint.name != footprint.name {
+                    new = rename_footprint_in(&new, &live_footprint.name);
+                }
+                live_footprint.path.clone()
+            }
+            None => live
+                .footprint_dir
+                .join(format!("{}.kicad_mod", footprint.name)),
+        };
+        let old = read_to_string(&target).ok();
+        if old.as_ref() != Some(&new) {
+            diff.footprint = Some(FileChange {
+                target,
+                name: footprint.name.clone(),
+                old,
+                new,
+            });
+        }
+
+        for model in files_with_extension(&staged.model_dir, &["wrl", "step", "stp"]) {
+            let (Some(file_name), Some(stem)) = (model.file_name(), model.file_stem()) else {
+                continue;
+            };
+            let referenced = footprint
+                .models
+                .iter()
+                .any(|referenced| model_stem(referenced).as_deref() == stem.to_str());
+            let target = live.model_dir.join(file_name);
+            if referenced && read(&model).ok() != read(&target).ok() {
+                diff.models.push((model, target));
+            }
+        }
+    }
+
+    if let Some(symbol) = &staged_symbol {
+        let text = read_to_string(&symbol.library).map_err(|e| e.to_string())?;
+        let mut new = symbol_text(&text, &symbol.name)
+            .ok_or("the generated symbol cannot be read")?
+            .to_owned();
+        let (target, name, old) = match live_symbol {
+            Some(live_symbol) => {
+                if live_symbol.name != symbol.name {
+                    // rename in a library of its own, as that also takes care of the units
+                    let library = format!("(kicad_symbol_lib\n  {}\n)", new);
+                    let renamed = rename_symbol_in(&library, &symbol.name, &live_symbol.name)
+                        .map_err(|e| e.to_string())?;
+                    new = symbol_text(&renamed, &live_symbol.name)
+                        .unwrap_or_default()
+                        .to_owned();
+                }
+                let library = read_to_string(&live_symbol.library).map_err(|e| e.to_string())?;
+                let old = symbol_text(&library, &live_symbol.name).map(str::to_owned);
+                (live_symbol.library.clone(), live_symbol.name.clone(), old)
+            }
+            None => (
+                live.symbol_dir.join(format!("{}.kicad_sym", symbol_lib)),
+                symbol.name.clone(),
+                None,
+            ),
+        };
+        if let (Some(staged_name), Some(live_footprint)) =
+            (staged_footprint_name.as_deref(), live_footprint)
+        {
+            if staged_name != live_footprint.name {
+                new = relink_footprint_in(&new, staged_name, &live_footprint.name);
+            }
+        }
+        if old.as_ref() != Some(&new) {
+            diff.symbol = Some(FileChange {
+                target,
+                name,
+                old,
+                new,
+            });
+        }
+    }
+    Ok(diff)
+}
+
+/// Writes the regenerated files of a part into the live library.
+pub fn accept(diff: &PartDiff) -> io::Result<()> {
+    if let Some(symbol) = &diff.symbol {
+        if symbol.target.exists() {
+            update_file(&symbol.target, |text| {
+                replace_symbol_in(text, &symbol.name, &symbol.new)
+            })?;
+        } else {
+            if let Some(dir) = symbol.target.parent() {
+                create_dir_all(dir)?;
+            }
+            write_atomic(
+                &symbol.target,
+                format!(
+                    "(kicad_symbol_lib (version 20211014) (generator easyeda_to_kicad_lib_ui)\n  {}\n)\n",
+                    symbol.new
+                ),
+            )?;
+        }
+    }
+    if let Some(footprint) = &diff.footprint {
+        if let Some(dir) = footprint.target.parent() {
+            create_dir_all(dir)?;
+        }
+        write_atomic(&footprint.target, &footprint.new)?;
+    }
+    for (staged, target) in &diff.models {
+        if let Some(dir) = target.parent() {
+            create_dir_all(dir)?;
+        }
+        write_atomic(target, read(staged)?)?;
+    }
+    Ok(())
+}
+
+/// The library paths inside a staging directory, laid out like the live library.
+pub fn staged_paths(staging: &Path, live: &LibraryPaths, output: &Path) -> LibraryPaths {
+    let relocate = |path: &Path| {
+        path.strip_prefix(output)
+            .map(|relative| staging.join(relative))
+            .unwrap_or_else(|_| staging.join(path.file_name().unwrap_or_default()))
+    };
+    LibraryPaths {
+        symbol_dir: relocate(&live.symbol_dir),
+        footprint_dir: relocate(&live.footprint_dir),
+        model_dir: relocate(&live.model_dir),
+        datasheet_dir: staging.join("datasheets"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::library_manager::scan_library;
+
+    fn library(dir: &Path) -> LibraryPaths {
+        let paths = LibraryPaths {
+            symbol_dir: dir.join("symbol"),
+            footprint_dir: dir.join("footprint"),
+            model_dir: dir.join("footprint/packages3d"),
+            datasheet_dir: dir.join("datasheets"),
+        };
+        create_dir_all(&paths.symbol_dir).unwrap();
+        create_dir_all(&paths.model_dir).unwrap();
+        paths
+    }
+
+    fn symbol_library(name: &str, footprint: &str, pins: u32) -> String {
+        format!(
+            "(kicad_symbol_lib (version 20211014) (generator TousstNicolas/JLC2KiCad_lib)\n  \
+             (symbol \"{0}\" (in_bom yes)\n    (property \"Footprint\" \"footprint:{1}\" (id 2))\n    \
+             (property \"LCSC\" \"C11702\" (id 5))\n    (symbol \"{0}_0_1\" (pin_count {2}))\n  )\n)\n",
+            name, footprint, pins
+        )
+    }
+
+    fn footprint(name: &str, dir: &Path, width: &str) -> String {
+        format!(
+            "(module {} (layer F.Cu)\n  (pad 1 smd rect (size {} 0.5))\n  (model \"{}/packages3d/R0402.wrl\")\n)\n",
+            name,
+            width,
+            dir.display()
+        )
+    }
+
+    #[test]
+    fn diffs_and_accepts_regenerated_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let live = library(&dir.path().join("live"));
+        let staged = library(&dir.path().join("staged"));
+        // the user renamed symbol and footprint in the live library
+        let live_library = live.symbol_dir.join("default_lib.kicad_sym");
+        write(&live_library, symbol_library("R_1k", "R_0402", 2)).unwrap();
+        write(
+            live.footprint_dir.join("R_0402.kicad_mod"),
+            footprint("\"R_0402\"", &live.footprint_dir, "0.5"),
+        )
+        .unwrap();
+        write(live.model_dir.join("R0402.wrl"), "old").unwrap();
+        write(
+            staged.symbol_dir.join("default_lib.kicad_sym"),
+            symbol_library("0402WGF1001TCE", "R0402", 3),
+        )
+        .unwrap();
+        write(
+            staged.footprint_dir.join("R0402.kicad_mod"),
+            footprint("R0402", &staged.footprint_dir, "0.6"),
+        )
+        .unwrap();
+        write(staged.model_dir.join("R0402.wrl"), "new").unwrap();
+        write(staged.model_dir.join("unrelated.wrl"), "").unwrap();
+
+        let entries = scan_library(&live);
+        let diff = diff_part("C11702", entries.first(), &staged, &live, "default_lib").unwrap();
+        assert_eq!(diff.summary(), "symbol, footprint, 1 model");
+        let symbol = diff.symbol.as_ref().unwrap();
+        assert_eq!(symbol.name, "R_1k");
+        assert!(symbol.new.contains("(symbol \"R_1k_0_1\" (pin_count 3))"));
+        assert!(symbol.new.contains("\"footprint:R_0402\""));
+        let footprint = diff.footprint.as_ref().unwrap();
+        assert!(footprint.new.starts_with("(module \"R_0402\""));
+        assert!(
+            footprint
+                .new
+                .contains(&*live.footprint_dir.to_string_lossy())
+        );
+
+        accept(&diff).unwrap();
+        assert_eq!(
+            read_to_string(live.model_dir.join("R0402.wrl")).unwrap(),
+            "new"
+        );
+        assert!(!live.model_dir.join("unrelated.wrl").exists());
+        let entries = scan_library(&live);
+        let diff = diff_part("C11702", entries.first(), &staged, &live, "default_lib").unwrap();
+        assert!(diff.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+use std::{borrow::Cow, fmt, ops::Range};
+
+/// A node of a KiCad S-expression file like `.kicad_sym` or `.kicad_mod`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexpr {
+    List(Vec<Sexpr>),
+    /// unquoted token, e.g. `property` or `1.27`
+    Atom(String),
+    /// quoted string, unescaped
+    Str(String),
+}
+
+impl Sexpr {
+    /// The leading atom of a list, e.g. `symbol` for `(symbol "R" ...)`.
+    pub fn head(&self) -> Option<&str> {
+        match self {
+            Sexpr::List(items) => match items.first() {
+                Some(Sexpr::Atom(head)) => Some(head),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn items(&self) -> &[Sexpr] {
+        match self {
+            Sexpr::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// The text of an atom or string.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(text) | Sexpr::Str(text) => Some(text),
+            Sexpr::List(_) => None,
+        }
+    }
+
+    /// The item at `idx` of a list as text, e.g. `arg(1)` is the name in `(symbol "R" ...)`.
+    pub fn arg(&self, idx: usize) -> Option<&str> {
+        self.items().get(idx).and_then(Sexpr::text)
+    }
+
+    /// All direct children that are lists starting with `head`.
+    pub fn children<'a>(&'a self, head: &'a str) -> impl Iterator<Item = &'a Sexpr> {
+        self.items()
+            .iter()
+            .filter(move |item| item.head() == Some(head))
+    }
+}
+
+/// Longest line [`Sexpr::pretty`] keeps a list on.
+const MAX_LINE: usize = 100;
+
+/// Writes numbers the same way however they were written, e.g. `1.270` and `-0.0` as
+/// `1.27` and `0`. Anything that is not a plain decimal number is left alone, as are
+/// leading zeros, which are more likely part of a name like pad `01`.
+pub fn normalize_number(atom: &str) -> Cow<'_, str> {
+    let digits = atom.strip_prefix('-').unwrap_or(atom);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    let is_number =
+        !(digits.is_empty() || digits.starts_with('.') || digits.ends_with('.') || leading_zero)
+            && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && digits.matches('.').count() <= 1;
+    match atom.parse::<f64>() {
+        Ok(value) if is_number => {
+            if value == 0.0 {
+                Cow::Borrowed("0")
+            } else {
+                Cow::Owned(value.to_string())
+            }
+        }
+        _ => Cow::Borrowed(atom),
+    }
+}
+
+fn write_quoted(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl Sexpr {
+    /// Single line text of the expression with normalized numbers, so expressions that
+    /// only differ in formatting have the same canonical text.
+    pub fn canonical(&self) -> String {
+        let mut out = String::new();
+        self.write_canonical(&mut out);
+        out
+    }
+
+    /// Multi line text of the expression in one fixed layout: lists that fit on a line are
+    /// kept on one, otherwise every nested item gets a line of its own, indented by two
+    /// spaces. Numbers are normalized, so the result does not depend on how it was written.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(0, &mut out);
+        out.push('\n');
+        out
+    }
+
+    fn write_pretty(&self, indent: usize, out: &mut String) {
+        let items = self.items();
+        let first_list = items.iter().position(|item| matches!(item, Sexpr::List(_)));
+        let canonical = self.canonical();
+        let Some(first_list) = first_list else {
+            out.push_str(&canonical);
+            return;
+        };
+        // the root always gets broken up, so its children end up on lines of their own
+        if indent > 0 && indent + canonical.len() <= MAX_LINE {
+            out.push_str(&canonical);
+            return;
+        }
+        out.push('(');
+        for (idx, item) in items[..first_list].iter().enumerate() {
+            if idx > 0 {
+                out.push(' ');
+            }
+            item.write_canonical(out);
+        }
+        for item in &items[first_list..] {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent + 2));
+            item.write_pretty(indent + 2, out);
+        }
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+        out.push(')');
+    }
+
+    fn write_canonical(&self, out: &mut String) {
+        match self {
+            Sexpr::Atom(atom) => out.push_str(&normalize_number(atom)),
+            Sexpr::Str(text) => write_quoted(text, out),
+            Sexpr::List(items) => {
+                out.push('(');
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        out.push(' ');
+                    }
+                    item.write_canonical(out);
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte offset into the parsed text
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a file consisting of exactly one S-expression.
+pub fn parse(text: &str) -> Result<Sexpr, ParseError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        text,
+        position: 0,
+    };
+    parser.skip_whitespace();
+    let sexpr = parser.parse_sexpr()?;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("trailing data after expression"));
+    }
+    Ok(sexpr)
+}
+
+/// Parses a file like [`parse`], but returns the direct children of the root expression
+/// together with their byte ranges, so they can be edited in place.
+pub fn child_spans(text: &str) -> Result<Vec<(Range<usize>, Sexpr)>, ParseError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        text,
+        position: 0,
+    };
+    parser.skip_whitespace();
+    if parser.bytes.get(parser.position) != Some(&b'(') {
+        return Err(parser.error("expected an expression"));
+    }
+    parser.position += 1;
+    let mut children = vec![];
+    loop {
+        parser.skip_whitespace();
+        match parser.bytes.get(parser.position) {
+            None => return Err(parser.error("missing closing parenthesis")),
+            Some(b')') => break,
+            Some(_) => {
+                let start = parser.position;
+                let child = parser.parse_sexpr()?;
+                children.push((start..parser.position, child));
+            }
+        }
+    }
+    parser.position += 1;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("trailing data after expression"));
+    }
+    Ok(children)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn parse_sexpr(&mut self) -> Result<Sexpr, ParseError> {
+        match self.bytes.get(self.position) {
+            None => Err(self.error("unexpected end of file")),
+            Some(b'(') => {
+                self.position += 1;
+                let mut items = vec![];
+                loop {
+                    self.skip_whitespace();
+                    match self.bytes.get(self.position) {
+                        None => return Err(self.error("missing closing parenthesis")),
+                        Some(b')') => {
+                            self.position += 1;
+                            return Ok(Sexpr::List(items));
+                        }
+                        Some(_) => items.push(self.parse_sexpr()?),
+                    }
+                }
+            }
+            Some(b')') => Err(self.error("unexpected closing parenthesis")),
+            Some(b'"') => {
+                self.position += 1;
+                let mut text = String::new();
+                let mut start = self.position;
+                loop {
+                    match self.bytes.get(self.position) {
+                        None => return Err(self.error("unterminated string")),
+                        Some(b'"') => {
+                            text.push_str(&self.text[start..self.position]);
+                            self.position += 1;
+                            return Ok(Sexpr::Str(text));
+                        }
+                        Some(b'\\') => {
+                            text.push_str(&self.text[start..self.position]);
+                            let escaped = match self.bytes.get(self.position + 1) {
+                                Some(b'n') => '\n',
+                                Some(b't') => '\t',
+                                Some(b'r') => '\r',
+                                Some(b'"') => '"',
+                                Some(b'\\') => '\\',
+                                _ => return Err(self.error("invalid escape sequence")),
+                            };
+                            text.push(escaped);
+                            self.position += 2;
+                            start = self.position;
+                        }
+                        Some(_) => self.position += 1,
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.position;
+                while self
+                    .bytes
+                    .get(self.position)
+                    .is_some_and(|b| !b.is_ascii_whitespace() && !matches!(b, b'(' | b')' | b'"'))
+                {
+                    self.position += 1;
+                }
+                Ok(Sexpr::Atom(self.text[start..self.position].to_owned()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kicad_files() {
+        let sexpr = parse(
+            "(kicad_symbol_lib (version 20211014)\n  (symbol \"R_0402\" (in_bom yes)\n    (property \"Value\" \"say \\\"hi\\\"\" (at 0 -2.54 0))))\n",
+        )
+        .unwrap();
+        assert_eq!(sexpr.head(), Some("kicad_symbol_lib"));
+        let symbol = sexpr.children("symbol").next().unwrap();
+        assert_eq!(symbol.arg(1), Some("R_0402"));
+        let property = symbol.children("property").next().unwrap();
+        assert_eq!(property.arg(2), Some("say \"hi\""));
+        assert_eq!(
+            property.children("at").next().unwrap().items()[2],
+            Sexpr::Atom("-2.54".to_owned())
+        );
+    }
+
+    #[test]
+    fn finds_child_spans() {
+        let text = "(lib (version 1)\n  (symbol \"R\" (pin 1))\n)";
+        let children = child_spans(text).unwrap();
+        // the head counts as child as well
+        assert_eq!(children.len(), 3);
+        assert_eq!(&text[children[2].0.clone()], "(symbol \"R\" (pin 1))");
+        assert_eq!(children[2].1.arg(1), Some("R"));
+    }
+
+    #[test]
+    fn writes_canonical_text() {
+        let sexpr =
+            parse("(pin (at  1.270 -0.0\n 90) (name \"a \\\"b\\\"\") 1e5 F.Cu 007)").unwrap();
+        assert_eq!(
+            sexpr.canonical(),
+            "(pin (at 1.27 0 90) (name \"a \\\"b\\\"\") 1e5 F.Cu 007)"
+        );
+    }
+
+    #[test]
+    fn writes_pretty_text() {
+        let text = "(kicad_symbol_lib (version 20211014) (symbol \"R\" (property \"Reference\" \"R\" \
+                    (at 2.0320 0 90) (effects (font (size 1.27 1.27)) hide)) (pin passive line \
+                    (at 0 3.81 270) (length 1.27) (name \"~\" (effects (font (size 1.27 1.27)))) \
+                    (number \"1\" (effects (font (size 1.27 1.27)))))))";
+        let pretty = parse(text).unwrap().pretty();
+        assert_eq!(
+            pretty,
+            "(kicad_symbol_lib\n  (version 20211014)\n  (symbol \"R\"\n    \
+             (property \"Reference\" \"R\" (at 2.032 0 90) (effects (font (size 1.27 1.27)) hide))\n    \
+             (pin passive line\n      (at 0 3.81 270)\n      (length 1.27)\n      \
+             (name \"~\" (effects (font (size 1.27 1.27))))\n      \
+             (number \"1\" (effects (font (size 1.27 1.27))))\n    )\n  )\n)\n"
+        );
+        assert_eq!(parse(&pretty).unwrap().pretty(), pretty);
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            parse("(a (b)").unwrap_err().message,
+            "missing closing parenthesis"
+        );
+        assert_eq!(parse("(a \"b)").unwrap_err().message, "unterminated string");
+        assert_eq!(parse("(a) b").unwrap_err().position, 4);
+        assert!(parse("").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+
+use crate::sexpr::{self, ParseError, Sexpr};
+
+/// Nodes that change on every save without meaning anything.
+const IGNORED: [&str; 3] = ["tedit", "tstamp", "uuid"];
+
+/// Heads of the drawing primitives of symbols and footprints.
+const GRAPHICS: [&str; 16] = [
+    "rectangle",
+    "polyline",
+    "circle",
+    "arc",
+    "bezier",
+    "text",
+    "fp_line",
+    "fp_rect",
+    "fp_circle",
+    "fp_arc",
+    "fp_poly",
+    "fp_curve",
+    "gr_line",
+    "gr_rect",
+    "gr_circle",
+    "gr_arc",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Groups changes the way one reviews a library part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Properties,
+    Pins,
+    Pads,
+    Graphics,
+    Models,
+    Other,
+}
+
+impl Category {
+    fn of(head: &str) -> Self {
+        match head {
+            "property" | "fp_text" => Category::Properties,
+            "pin" => Category::Pins,
+            "pad" => Category::Pads,
+            "model" => Category::Models,
+            head if GRAPHICS.contains(&head) => Category::Graphics,
+            _ => Category::Other,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Properties => "Properties",
+            Category::Pins => "Pins",
+            Category::Pads => "Pads",
+            Category::Graphics => "Graphics",
+            Category::Models => "3D Models",
+            Category::Other => "Other",
+        })
+    }
+}
+
+/// One difference between two S-expression files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeChange {
+    pub kind: ChangeKind,
+    pub category: Category,
+    /// where the node is, e.g. `symbol R_1k › symbol R_1k_0_1 › pin 2`
+    pub path: String,
+    /// canonical text before and after
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// What identifies a node among its siblings, beyond its head.
+fn identity(node: &Sexpr) -> Option<String> {
+    match node.head()? {
+        "symbol" | "property" | "pad" | "footprint" | "module" => node.arg(1).map(str::to_owned),
+        "pin" => node
+            .children("number")
+            .next()
+            .and_then(|number| number.arg(1))
+            .map(str::to_owned),
+        "fp_text" => match node.arg(1)? {
+            "user" => node.arg(2).map(|text| format!("user {}", text)),
+            kind => Some(kind.to_owned()),
+        },
+        _ => None,
+    }
+}
+
+fn label(node: &Sexpr) -> String {
+    let head = node.head().unwrap_or_default();
+    match identity(node) {
+        Some(identity) => format!("{} {}", head, identity),
+        None => head.to_owned(),
+    }
+}
+
+/// The atoms and strings of a list, without its head and child lists.
+fn args(node: &Sexpr) -> String {
+    node.items()
+        .iter()
+        .skip(1)
+        .filter(|item| !matches!(item, Sexpr::List(_)))
+        .map(Sexpr::canonical)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Keys the child lists of a node so they can be matched regardless of their order.
+/// Nodes without identity are matched by head if there is only one of them, e.g. `at`,
+/// otherwise by their content, so moved graphics count as removed and added.
+fn keyed_children(node: &Sexpr) -> Vec<(String, &Sexpr)> {
+    let lists: Vec<&Sexpr> = node
+        .items()
+        .iter()
+        .filter(|item| item.head().is_some_and(|head| !IGNORED.contains(&head)))
+        .collect();
+    let mut keyed: Vec<(String, &Sexpr)> = vec![];
+    for child in lists.iter().copied() {
+        let head = child.head().unwrap_or_default();
+        let mut key = match identity(child) {
+            Some(identity) => format!("{} {}", head, identity),
+            None if lists
+                .iter()
+                .filter(|other| other.head() == Some(head))
+                .count()
+                == 1 =>
+            {
+                head.to_owned()
+            }
+            None => child.canonical(),
+        };
+        // pads can share a number and graphics can be drawn twice
+        let base = key.clone();
+        let mut occurrence = 1;
+        while keyed.iter().any(|(other, _)| *other == key) {
+            occurrence += 1;
+            key = format!("{} #{}", base, occurrence);
+        }
+        keyed.push((key, child));
+    }
+    keyed
+}
+
+fn compare(old: &Sexpr, new: &Sexpr, path: &str, category: Category, out: &mut Vec<NodeChange>) {
+    let (old_args, new_args) = (args(old), args(new));
+    if old_args != new_args {
+        out.push(NodeChange {
+            kind: ChangeKind::Changed,
+            category,
+            path: path.to_owned(),
+            old: Some(old_args),
+            new: Some(new_args),
+        });
+    }
+    let old_children = keyed_children(old);
+    let new_children = keyed_children(new);
+    let child_path = |node: &Sexpr| {
+        if path.is_empty() {
+            label(node)
+        } else {
+            format!("{} › {}", path, label(node))
+        }
+    };
+    let child_category = |node: &Sexpr| match Category::of(node.head().unwrap_or_default()) {
+        Category::Other => category,
+        own => own,
+    };
+    for (key, old_child) in &old_children {
+        if !new_children.iter().any(|(other, _)| other == key) {
+            out.push(NodeChange {
+                kind: ChangeKind::Removed,
+                category: child_category(old_child),
+                path: child_path(old_child),
+                old: Some(old_child.canonical()),
+                new: None,
+            });
+        }
+    }
+    for (key, new_child) in &new_children {
+        match old_children.iter().find(|(other, _)| other == key) {
+            None => out.push(NodeChange {
+                kind: ChangeKind::Added,
+                category: child_category(new_child),
+                path: child_path(new_child),
+                old: None,
+                new: Some(new_child.canonical()),
+            }),
+            Some((_, old_child)) if old_child.canonical() != new_child.canonical() => {
+                let has_lists = old_child
+                    .items()
+                    .iter()
+                    .chain(new_child.items())
+                    .any(|item| matches!(item, Sexpr::List(_)));
+                if has_lists {
+                    compare(
+                        old_child,
+                        new_child,
+                        &child_path(new_child),
+                        child_category(new_child),
+                        out,
+                    );
+                } else {
+                    out.push(NodeChange {
+                        kind: ChangeKind::Changed,
+                        category: child_category(new_child),
+                        path: child_path(new_child),
+                        old: Some(old_child.canonical()),
+                        new: Some(new_child.canonical()),
+                    });
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// Compares two KiCad S-expression texts node by node. Formatting, number notation, the
+/// order of nodes and timestamps do not count as changes.
+pub fn diff_sexpr(old: &str, new: &str) -> Result<Vec<NodeChange>, ParseError> {
+    let old = sexpr::parse(old)?;
+    let new = sexpr::parse(new)?;
+    let mut changes = vec![];
+    if old.head() != new.head() {
+        changes.push(NodeChange {
+            kind: ChangeKind::Changed,
+            category: Category::Other,
+            path: String::new(),
+            old: Some(old.canonical()),
+            new: Some(new.canonical()),
+        });
+    } else {
+        compare(&old, &new, &label(&new), Category::Other, &mut changes);
+    }
+    changes.sort_by_key(|change| change.category);
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_structure() {
+        let old = r#"(symbol "R_1k" (in_bom yes)
+  (property "Value" "1k" (at 0 -2.54 0))
+  (property "Footprint" "footprint:R0402" (at 0 -5.08 0))
+  (symbol "R_1k_0_1"
+    (rectangle (start -1.016 2.54) (end 1.016 -2.54))
+    (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+    (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2"))))"#;
+        // reordered, reformatted and with different number notation
+        let new = r#"(symbol "R_1k"
+  (in_bom yes)
+  (property "Footprint" "footprint:R0402" (at 0 -5.080 0))
+  (property "Value" "1k" (at 0 -2.54 0))
+  (symbol "R_1k_0_1"
+    (pin passive line (at 0 -3.81 90) (length 1.27) (name "~") (number "2"))
+    (pin passive line (at 0 3.81 270) (length 1.27) (name "~") (number "1"))
+    (rectangle (start -1.016 2.54) (end 1.016 -2.540))))"#;
+        assert_eq!(diff_sexpr(old, new).unwrap(), vec![]);
+
+        let new = new
+            .replace(r#""Value" "1k""#, r#""Value" "1k5""#)
+            .replace("(at 0 -3.81 90)", "(at 0 -5.08 90)")
+            .replace("(end 1.016 -2.540)", "(end 1.27 -2.54)")
+            .replace(
+                r#"(rectangle"#,
+                r#"(pin passive line (at 0 0 0) (number "3")) (rectangle"#,
+            );
+        let changes = diff_sexpr(old, &new).unwrap();
+        let summary: Vec<(ChangeKind, Category, &str)> = changes
+            .iter()
+            .map(|change| (change.kind, change.category, change.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    ChangeKind::Changed,
+                    Category::Properties,
+                    "symbol R_1k › property Value"
+                ),
+                (
+                    ChangeKind::Changed,
+                    Category::Pins,
+                    "symbol R_1k › symbol R_1k_0_1 › pin 2 › at"
+                ),
+                (
+                    ChangeKind::Added,
+                    Category::Pins,
+                    "symbol R_1k › symbol R_1k_0_1 › pin 3"
+                ),
+                (
+                    ChangeKind::Changed,
+                    Category::Graphics,
+                    "symbol R_1k › symbol R_1k_0_1 › rectangle › end"
+                ),
+            ]
+        );
+        assert_eq!(changes[0].old.as_deref(), Some(r#""Value" "1k""#));
+        assert_eq!(changes[1].new.as_deref(), Some("(at 0 -5.08 90)"));
+    }
+}
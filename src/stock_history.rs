@@ -0,0 +1,216 @@
+use std::{
+    fs::{create_dir_all, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+
+use crate::{lock::write_atomic, pricing::PriceTable};
+
+/// Snapshots kept per part, the oldest are dropped first.
+const MAX_SNAPSHOTS: usize = 500;
+/// Parts whose latest lookup is kept, the ones not seen for the longest are dropped first.
+const MAX_DETAILS: usize = 1000;
+
+/// Stock and price of a part at the time of a lookup.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Snapshot {
+    /// unix timestamp in seconds
+    pub timestamp: i64,
+    pub stock: Option<u64>,
+    /// unit price at the lowest price break, in USD
+    pub unit_price: Option<f64>,
+}
+
+/// Every lookup of a part leaves a snapshot here, so we can tell if stock is collapsing
+/// or prices are going up. Kept as JSON next to the app state, which the app and the
+/// command line both add to.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct StockHistory {
+    parts: IndexMap<String, Vec<Snapshot>>,
+    /// the latest lookup of every part, so we can search through the parts we have seen
+    details: IndexMap<String, IndexMap<String, String>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// lookups recorded since the last save, with their time
+    #[serde(skip)]
+    unsaved: Vec<(IndexMap<String, String>, i64)>,
+}
+
+impl StockHistory {
+    /// Loads the history from the default location, starting empty if there is none yet.
+    pub fn load_default() -> Self {
+        match eframe::storage_dir(crate::APP_ID) {
+            Some(dir) => Self::load(&dir.join("stock_history.json")),
+            None => Self::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let mut history = Self::read(path);
+        history.path = Some(path.to_owned());
+        history
+    }
+
+    fn read(path: &Path) -> Self {
+        read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the lookups recorded since the last save to disk. They are added to the
+    /// history as saved there, so lookups saved by others in the meantime are kept.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        if self.unsaved.is_empty() {
+            return Ok(());
+        }
+        let mut saved = Self::read(&path);
+        for (part, timestamp) in &self.unsaved {
+            saved.record_at(part, *timestamp);
+        }
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write_atomic(&path, serde_json::to_string(&saved)?)?;
+        self.parts = saved.parts;
+        self.details = saved.details;
+        self.unsaved.clear();
+        Ok(())
+    }
+
+    /// Records a snapshot of a looked up part, which is written to disk by the next save.
+    pub fn record(&mut self, part: &IndexMap<String, String>) {
+        let timestamp = jiff::Timestamp::now().as_second();
+        self.record_at(part, timestamp);
+        if self.path.is_some() {
+            self.unsaved.push((part.clone(), timestamp));
+        }
+    }
+
+    /// Records a snapshot, keeping the history small enough to be written often.
+    fn record_at(&mut self, part: &IndexMap<String, String>, timestamp: i64) {
+        let Some(code) = part.get("Component Code") else {
+            return;
+        };
+        let snapshot = Snapshot {
+            timestamp,
+            stock: part.get("Stock").and_then(|stock| stock.parse().ok()),
+            unit_price: PriceTable::from_part(part)
+                .map(|prices| prices.breaks[0].unit_price)
+                .or_else(|| part.get("Minimum Price").and_then(|p| p.parse().ok())),
+        };
+        let snapshots = self.parts.entry(code.to_owned()).or_default();
+        let unchanged = |other: &Snapshot| {
+            other.stock == snapshot.stock && other.unit_price == snapshot.unit_price
+        };
+        // a run of unchanged lookups only needs its first and last snapshot for the chart
+        match snapshots.as_mut_slice() {
+            [.., before, last] if unchanged(before) && unchanged(last) => *last = snapshot,
+            _ => snapshots.push(snapshot),
+        }
+        if snapshots.len() > MAX_SNAPSHOTS {
+            snapshots.drain(..snapshots.len() - MAX_SNAPSHOTS);
+        }
+        // the most recently seen part goes last
+        self.details.shift_remove(code);
+        self.details.insert(code.to_owned(), part.clone());
+        if self.details.len() > MAX_DETAILS {
+            self.details.shift_remove_index(0);
+        }
+    }
+
+    pub fn details(&self) -> &IndexMap<String, IndexMap<String, String>> {
+        &self.details
+    }
+
+    pub fn snapshots(&self, code: &str) -> &[Snapshot] {
+        self.parts.get(code).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Formats a unix timestamp as a local date for display.
+pub fn format_date(timestamp: i64, with_time: bool) -> String {
+    let Ok(time) = jiff::Timestamp::from_second(timestamp) else {
+        return String::new();
+    };
+    let zoned = time.to_zoned(jiff::tz::TimeZone::system());
+    if with_time {
+        zoned.strftime("%Y-%m-%d %H:%M").to_string()
+    } else {
+        zoned.strftime("%Y-%m-%d").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    fn part(code: &str, stock: u64) -> IndexMap<String, String> {
+        indexmap! {
+            "Component Code".to_owned() => code.to_owned(),
+            "Stock".to_owned() => stock.to_string(),
+            "Minimum Price".to_owned() => "0.0012".to_owned(),
+        }
+    }
+
+    #[test]
+    fn records_snapshots() {
+        let mut history = StockHistory::default();
+        history.record_at(&part("C11702", 5000), 100);
+        history.record_at(&part("C11702", 5000), 200);
+        // unchanged lookups only move the end of the run
+        history.record_at(&part("C11702", 5000), 300);
+        history.record_at(&part("C11702", 4000), 400);
+        let snapshots = history.snapshots("C11702");
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|s| (s.timestamp, s.stock))
+                .collect::<Vec<_>>(),
+            vec![(100, Some(5000)), (300, Some(5000)), (400, Some(4000))]
+        );
+        assert_eq!(snapshots[0].unit_price, Some(0.0012));
+        assert_eq!(history.details()["C11702"]["Stock"], "4000");
+        assert!(history.snapshots("C1").is_empty());
+        history.record_at(&indexmap! {}, 500);
+        assert_eq!(history.details().len(), 1);
+
+        // what is saved stays bounded
+        for idx in 0..MAX_SNAPSHOTS as u64 {
+            history.record_at(&part("C11702", idx), 1000 + idx as i64);
+        }
+        assert_eq!(history.snapshots("C11702").len(), MAX_SNAPSHOTS);
+        assert_eq!(history.snapshots("C11702")[0].timestamp, 1000);
+        for idx in 0..MAX_DETAILS {
+            history.record_at(&part(&format!("C{}", idx), 1), 2000);
+        }
+        assert_eq!(history.details().len(), MAX_DETAILS);
+        assert!(!history.details().contains_key("C11702"));
+        history.record_at(&part("C0", 1), 3000);
+        assert_eq!(history.details().keys().last().unwrap(), "C0");
+    }
+
+    #[test]
+    fn keeps_lookups_saved_by_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stock_history.json");
+        let mut app = StockHistory::load(&path);
+        let mut cli = StockHistory::load(&path);
+        app.record(&part("C1", 10));
+        cli.record(&part("C2", 20));
+        cli.save().unwrap();
+        app.save().unwrap();
+        assert_eq!(app.details().len(), 2);
+        let saved = StockHistory::load(&path);
+        assert_eq!(saved.snapshots("C1").len(), 1);
+        assert_eq!(saved.snapshots("C2")[0].stock, Some(20));
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    fmt,
+    fs::{create_dir_all, read_to_string},
+    io,
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    lock::write_atomic,
+    lookup::{LookupError, lookup_part},
+};
+
+/// Result of looking up one watched part.
+pub type RefreshResult = (String, Result<IndexMap<String, String>, LookupError>);
+
+/// A part our designs depend on, together with what we knew about it at the last check.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct WatchedPart {
+    pub code: String,
+    /// alert once the stock drops below this
+    pub threshold: u64,
+    pub last_stock: Option<u64>,
+    /// "Basic" or "Extended"
+    pub last_type: Option<String>,
+    pub missing: bool,
+    /// unix timestamp of the last successful check
+    pub last_checked: Option<i64>,
+}
+
+impl Default for WatchedPart {
+    fn default() -> Self {
+        Self {
+            code: String::new(),
+            threshold: 100,
+            last_stock: None,
+            last_type: None,
+            missing: false,
+            last_checked: None,
+        }
+    }
+}
+
+/// Something noteworthy that happened to a watched part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    LowStock {
+        code: String,
+        stock: u64,
+        threshold: u64,
+    },
+    TypeChanged {
+        code: String,
+        from: String,
+        to: String,
+    },
+    Disappeared {
+        code: String,
+    },
+    Reappeared {
+        code: String,
+    },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::LowStock {
+                code,
+                stock,
+                threshold,
+            } => write!(
+                f,
+                "{}: stock dropped to {} (threshold {})",
+                code, stock, threshold
+            ),
+            Alert::TypeChanged { code, from, to } => {
+                write!(f, "{}: changed from {} to {} part", code, from, to)
+            }
+            Alert::Disappeared { code } => write!(f, "{}: part is no longer listed", code),
+            Alert::Reappeared { code } => write!(f, "{}: part is listed again", code),
+        }
+    }
+}
+
+impl WatchedPart {
+    pub fn new(code: &str, threshold: u64) -> Self {
+        Self {
+            code: code.to_owned(),
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Takes in the result of a fresh lookup and reports what changed since the last one.
+    /// Failed lookups (e.g. when offline) are ignored, only a definitive "not found" counts.
+    pub fn update(
+        &mut self,
+        lookup: &Result<IndexMap<String, String>, LookupError>,
+        now: i64,
+    ) -> Vec<Alert> {
+        let mut alerts = vec![];
+        let code = self.code.clone();
+        match lookup {
+            Ok(part) => {
+                if self.missing {
+                    alerts.push(Alert::Reappeared { code: code.clone() });
+                    self.missing = false;
+                }
+                if let Some(stock) = part.get("Stock").and_then(|s| s.parse::<u64>().ok()) {
+                    // only alert when crossing the threshold, not on every refresh
+                    let was_low = self.last_stock.is_some_and(|last| last < self.threshold);
+                    if stock < self.threshold && !was_low {
+                        alerts.push(Alert::LowStock {
+                            code: code.clone(),
+                            stock,
+                            threshold: self.threshold,
+                        });
+                    }
+                    self.last_stock = Some(stock);
+                }
+                if let Some(part_type) = part.get("Type") {
+                    if let Some(last_type) = &self.last_type {
+                        if last_type != part_type {
+                            alerts.push(Alert::TypeChanged {
+                                code,
+                                from: last_type.clone(),
+                                to: part_type.clone(),
+                            });
+                        }
+                    }
+                    self.last_type = Some(part_type.clone());
+                }
+                self.last_checked = Some(now);
+            }
+            Err(LookupError::NotFound) => {
+                if !self.missing {
+                    alerts.push(Alert::Disappeared { code });
+                    self.missing = true;
+                }
+                self.last_checked = Some(now);
+            }
+            Err(_) => {}
+        }
+        alerts
+    }
+}
+
+/// The list of watched parts, stored as JSON next to the app state so that the
+/// command line can refresh it as well.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Watchlist {
+    pub parts: Vec<WatchedPart>,
+    /// refresh interval while the app is open, 0 disables the periodic refresh
+    pub refresh_minutes: u32,
+    /// unix timestamp of the last refresh
+    pub last_refresh: Option<i64>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// the parts and interval as last loaded or saved, to tell our changes from those
+    /// others saved in the meantime
+    #[serde(skip)]
+    base: Vec<WatchedPart>,
+    #[serde(skip)]
+    base_refresh_minutes: u32,
+}
+
+impl Default for Watchlist {
+    fn default() -> Self {
+        Self {
+            parts: vec![],
+            refresh_minutes: 60,
+            last_refresh: None,
+            path: None,
+            base: vec![],
+            base_refresh_minutes: 60,
+        }
+    }
+}
+
+impl Watchlist {
+    /// Loads the watchlist from the default location, starting empty if there is none yet.
+    pub fn load_default() -> Self {
+        match eframe::storage_dir(crate::APP_ID) {
+            Some(dir) => Self::load(&dir.join("watchlist.json")),
+            None => Self::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let mut watchlist = Self::read(path).unwrap_or_default();
+        watchlist.path = Some(path.to_owned());
+        watchlist.base = watchlist.parts.clone();
+        watchlist.base_refresh_minutes = watchlist.refresh_minutes;
+        watchlist
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    /// Saves the watchlist merged with what was saved since it was loaded, as the app and
+    /// the command line both refresh it.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        if let Some(saved) = Self::read(&path) {
+            self.merge(saved);
+        }
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write_atomic(&path, serde_json::to_string_pretty(self)?)?;
+        self.base = self.parts.clone();
+        self.base_refresh_minutes = self.refresh_minutes;
+        Ok(())
+    }
+
+    /// Takes in the parts of `saved`, keeping the parts added and removed here and the
+    /// settings changed here. Of a part in both, the more recent check wins.
+    fn merge(&mut self, saved: Watchlist) {
+        let in_base = |code: &str| self.base.iter().find(|part| part.code == code);
+        let mut parts = vec![];
+        for theirs in saved.parts {
+            let base = in_base(&theirs.code);
+            match self.parts.iter().find(|part| part.code == theirs.code) {
+                // removed here
+                None if base.is_some() => {}
+                None => parts.push(theirs),
+                Some(ours) => {
+                    let mut part = if ours.last_checked >= theirs.last_checked {
+                        ours.clone()
+                    } else {
+                        theirs.clone()
+                    };
+                    if base.is_some_and(|base| base.threshold == ours.threshold) {
+                        part.threshold = theirs.threshold;
+                    } else {
+                        part.threshold = ours.threshold;
+                    }
+                    parts.push(part);
+                }
+            }
+        }
+        for ours in &self.parts {
+            // unless removed there
+            if in_base(&ours.code).is_none() && !parts.iter().any(|part| part.code == ours.code) {
+                parts.push(ours.clone());
+            }
+        }
+        self.parts = parts;
+        if self.refresh_minutes == self.base_refresh_minutes {
+            self.refresh_minutes = saved.refresh_minutes;
+        }
+        self.last_refresh = self.last_refresh.max(saved.last_refresh);
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.parts.iter().any(|part| part.code == code)
+    }
+
+    pub fn add(&mut self, code: &str, threshold: u64) {
+        if !self.contains(code) {
+            self.parts.push(WatchedPart::new(code, threshold));
+        }
+    }
+
+    pub fn remove(&mut self, code: &str) {
+        self.parts.retain(|part| part.code != code);
+    }
+
+    /// Whether the periodic refresh is due at `now`.
+    pub fn is_due(&self, now: i64) -> bool {
+        self.refresh_minutes > 0
+            && !self.parts.is_empty()
+            && self
+                .last_refresh
+                .is_none_or(|last| now - last >= self.refresh_minutes as i64 * 60)
+    }
+
+    pub fn codes(&self) -> Vec<String> {
+        self.parts.iter().map(|part| part.code.clone()).collect()
+    }
+
+    /// Applies the lookup results of a refresh and returns the resulting alerts.
+    pub fn apply(&mut self, results: &[RefreshResult], now: i64) -> Vec<Alert> {
+        let mut alerts = vec![];
+        for (code, result) in results {
+            if let Some(part) = self.parts.iter_mut().find(|part| &part.code == code) {
+                alerts.extend(part.update(result, now));
+            }
+        }
+        self.last_refresh = Some(now);
+        alerts
+    }
+}
+
+/// Looks up all given parts, this blocks for a while with a long watchlist.
+pub fn lookup_all(codes: &[String]) -> Vec<RefreshResult> {
+    codes
+        .iter()
+        .map(|code| (code.clone(), lookup_part(code)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::indexmap;
+
+    use super::*;
+
+    fn part(stock: &str, part_type: &str) -> Result<IndexMap<String, String>, LookupError> {
+        Ok(indexmap! {
+            "Stock".to_owned() => stock.to_owned(),
+            "Type".to_owned() => part_type.to_owned(),
+        })
+    }
+
+    #[test]
+    fn raises_alerts_on_changes() {
+        let mut watched = WatchedPart::new("C11702", 1000);
+        assert!(watched.update(&part("5000", "Basic"), 1).is_empty());
+
+        // dropping below the threshold alerts once
+        assert_eq!(
+            watched.update(&part("900", "Basic"), 2),
+            vec![Alert::LowStock {
+                code: "C11702".to_owned(),
+                stock: 900,
+                threshold: 1000
+            }]
+        );
+        assert!(watched.update(&part("800", "Basic"), 3).is_empty());
+
+        assert_eq!(
+            watched.update(&part("5000", "Extended"), 4),
+            vec![Alert::TypeChanged {
+                code: "C11702".to_owned(),
+                from: "Basic".to_owned(),
+                to: "Extended".to_owned()
+            }]
+        );
+
+        // being offline is no reason for alarm, a part missing from the catalogue is
+        assert!(watched.update(&Err(LookupError::Unavailable), 5).is_empty());
+        assert_eq!(watched.last_checked, Some(4));
+        assert_eq!(
+            watched.update(&Err(LookupError::NotFound), 6),
+            vec![Alert::Disappeared {
+                code: "C11702".to_owned()
+            }]
+        );
+        assert!(watched.update(&Err(LookupError::NotFound), 7).is_empty());
+        assert_eq!(
+            watched.update(&part("5000", "Extended"), 8),
+            vec![Alert::Reappeared {
+                code: "C11702".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn refresh_is_due_after_interval() {
+        let mut watchlist = Watchlist::default();
+        assert!(!watchlist.is_due(0));
+        watchlist.add("C11702", 100);
+        watchlist.add("C11702", 200);
+        assert_eq!(watchlist.parts.len(), 1);
+        assert!(watchlist.is_due(0));
+        watchlist.apply(&[], 1000);
+        assert!(!watchlist.is_due(1000 + 59 * 60));
+        assert!(watchlist.is_due(1000 + 60 * 60));
+    }
+
+    #[test]
+    fn merges_with_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watchlist.json");
+        let mut setup = Watchlist::load(&path);
+        setup.add("C1", 100);
+        setup.add("C2", 100);
+        setup.save().unwrap();
+
+        // the app has it open while the command line refreshes and edits it
+        let mut app = Watchlist::load(&path);
+        let mut cli = Watchlist::load(&path);
+        cli.apply(&[("C1".to_owned(), part("50", "Basic"))], 10);
+        cli.add("C3", 100);
+        cli.remove("C2");
+        cli.save().unwrap();
+
+        app.parts[0].threshold = 10;
+        app.add("C4", 100);
+        app.save().unwrap();
+
+        let saved = Watchlist::load(&path);
+        assert_eq!(saved.codes(), vec!["C1", "C3", "C4"]);
+        assert_eq!(saved.parts[0].last_stock, Some(50));
+        assert_eq!(saved.parts[0].threshold, 10);
+        assert_eq!(saved.last_refresh, Some(10));
+        assert_eq!(app.codes(), saved.codes());
+    }
+}